@@ -0,0 +1,88 @@
+use core::convert::Infallible;
+use embedded_hal_1::digital::{ErrorType, OutputPin};
+use embedded_hal_1::spi::SpiDevice;
+use embedded_hal_bus::spi::ExclusiveDevice;
+use serial_embedded_hal::{PortSettings, Serial};
+use spidriver::SPIDriver;
+use spidriver_hal::SPIDriverHAL;
+
+fn main() {
+    // This example demonstrates composing the SPIDriver HAL objects with the
+    // device wrappers from the embedded-hal-bus crate, by driving a strip of
+    // APA102 addressable LEDs through a driver written in terms of the
+    // embedded-hal 1.0 SpiDevice trait.
+    //
+    // The APA102 has no chip select signal, and so the strip must be the only
+    // device on the bus. ExclusiveDevice wraps the SPI part, which implements
+    // SpiBus, to produce the SpiDevice that the driver expects. It calls
+    // SpiBus::flush at the end of each transaction, which waits for the
+    // SPIDriver to finish sending the data. The driver never asks for a
+    // delay, so the device doesn't need one.
+
+    let port = Serial::new(
+        "/dev/ttyUSB0",
+        &PortSettings {
+            baud_rate: serial_embedded_hal::BaudRate::BaudOther(460800),
+            char_size: serial_embedded_hal::CharSize::Bits8,
+            parity: serial_embedded_hal::Parity::ParityNone,
+            stop_bits: serial_embedded_hal::StopBits::Stop1,
+            flow_control: serial_embedded_hal::FlowControl::FlowNone,
+        },
+    )
+    .unwrap();
+    let (tx, rx) = port.split();
+
+    let sdh = SPIDriverHAL::new(SPIDriver::new(tx, rx));
+    let parts = sdh.split();
+    let device = ExclusiveDevice::new_no_delay(parts.spi, NoCs).unwrap();
+    let mut strip = APA102::new(device);
+
+    // Light the first three LEDs red, green, and blue.
+    strip
+        .write(&[[0xff, 0x00, 0x00], [0x00, 0xff, 0x00], [0x00, 0x00, 0xff]])
+        .unwrap();
+}
+
+// NoCs stands in for the chip select pin that ExclusiveDevice expects, for
+// a device that doesn't have one.
+struct NoCs;
+
+impl ErrorType for NoCs {
+    type Error = Infallible;
+}
+
+impl OutputPin for NoCs {
+    fn set_low(&mut self) -> Result<(), Infallible> {
+        Ok(())
+    }
+
+    fn set_high(&mut self) -> Result<(), Infallible> {
+        Ok(())
+    }
+}
+
+struct APA102<D: SpiDevice> {
+    device: D,
+}
+
+impl<D: SpiDevice> APA102<D> {
+    pub fn new(device: D) -> Self {
+        Self { device }
+    }
+
+    // As with the other example, this driver knows only about the
+    // embedded-hal traits and is decoupled from the specific SPIDriver
+    // implementations of them.
+
+    pub fn write(&mut self, colors: &[[u8; 3]]) -> Result<(), D::Error> {
+        // Each LED takes a brightness byte followed by its blue, green, and
+        // red components, between a start frame of zeros and an end frame
+        // of ones.
+        let mut buf = vec![0x00; 4];
+        for [r, g, b] in colors {
+            buf.extend_from_slice(&[0xff, *b, *g, *r]);
+        }
+        buf.extend_from_slice(&[0xff; 4]);
+        self.device.write(&buf)
+    }
+}
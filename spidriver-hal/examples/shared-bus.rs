@@ -0,0 +1,182 @@
+use std::sync::Mutex;
+
+use embedded_hal::blocking::spi::{Transfer, Write};
+use embedded_hal::digital::v2::OutputPin;
+use embedded_hal::serial;
+use serial_embedded_hal::{PortSettings, Serial};
+use shared_bus::{BusManagerSimple, BusManagerStd};
+use spidriver::SPIDriver;
+use spidriver_hal::hal::SPI;
+use spidriver_hal::{Bus, SPIDriverHAL};
+
+fn main() {
+    // This example demonstrates sharing the SPI part between two drivers
+    // using a bus manager from the shared-bus crate: an SSD1322 display
+    // controller selected by the SPIDriver's chip select signal, and an SPI
+    // flash memory selected by auxillary output pin B.
+    //
+    // As well as the SPI signals, this example assumes:
+    //    SPIDriver Port A is connected to the D/C signal on the SSD1322.
+    //    SPIDriver Port B is connected to the chip select signal on the flash.
+    //
+    // Each driver gets its own proxy for the SPI bus from the bus manager,
+    // and its own ones of the other parts as its control signals.
+
+    let port = Serial::new(
+        "/dev/ttyUSB0",
+        &PortSettings {
+            baud_rate: serial_embedded_hal::BaudRate::BaudOther(460800),
+            char_size: serial_embedded_hal::CharSize::Bits8,
+            parity: serial_embedded_hal::Parity::ParityNone,
+            stop_bits: serial_embedded_hal::StopBits::Stop1,
+            flow_control: serial_embedded_hal::FlowControl::FlowNone,
+        },
+    )
+    .unwrap();
+    let (tx, rx) = port.split();
+
+    let sdh = SPIDriverHAL::new(SPIDriver::new(tx, rx));
+    let parts = sdh.split();
+    let bus = BusManagerSimple::new(parts.spi);
+    let mut display = SSD1322::new(bus.acquire_spi(), parts.cs, parts.pin_a).unwrap();
+    let mut flash = SpiFlash::new(bus.acquire_spi(), parts.pin_b).unwrap();
+
+    // The two drivers can be used in any order, because each one selects
+    // its own device only for the duration of each of its operations.
+    display.init().unwrap();
+    println!("flash ID: {:02x?}", flash.read_id().unwrap());
+
+    // Render horizontal stripes, assuming a 256x64 pixel display in 4-bit
+    // grayscale mode, where each byte represents two pixels.
+    let mut buf = [0_u8; 256 * 64 / 2];
+    for (y, row) in buf.chunks_mut(256 / 2).enumerate() {
+        row.fill(if y % 2 == 0 { 0xff } else { 0x00 });
+    }
+    display.command(0x5c, &buf).unwrap();
+}
+
+// The following functions are never called, but they fail to compile if
+// the SPI part stops satisfying the bounds that the shared-bus managers
+// place on the bus they share.
+
+// BusManagerSimple hands out proxies that implement the blocking SPI traits
+// whenever the bus does.
+#[allow(dead_code)]
+fn check_simple<TX, RX>(spi: SPI<&SPIDriverHAL<TX, RX>>)
+where
+    TX: serial::Write<u8>,
+    RX: serial::Read<u8>,
+{
+    fn check<P: Transfer<u8> + Write<u8>>(_: &P) {}
+    let bus = BusManagerSimple::new(spi);
+    check(&bus.acquire_spi());
+}
+
+// BusManagerStd can be shared between threads only if the bus is Send,
+// which the SPI part is when obtained from into_shared_parts on an
+// SPIDriverHAL using std::sync::Mutex.
+#[allow(dead_code)]
+fn check_std<TX, RX>(sdh: SPIDriverHAL<TX, RX, Mutex<Bus<TX, RX>>>)
+where
+    TX: serial::Write<u8> + Send,
+    RX: serial::Read<u8> + Send,
+{
+    fn check<T: Sync>(_: &T) {}
+    let parts = sdh.into_shared_parts();
+    check(&BusManagerStd::new(parts.spi));
+}
+
+// As with the other examples, these drivers know only about the
+// embedded-hal traits, and so are unaware that their bus is shared. They
+// are also much less complete than real drivers would be.
+
+struct SSD1322<S, CS, DC> {
+    spi: S,
+    cs: CS,
+    dc: DC,
+}
+
+impl<S, CS, DC, E> SSD1322<S, CS, DC>
+where
+    S: Write<u8, Error = E>,
+    CS: OutputPin<Error = E>,
+    DC: OutputPin<Error = E>,
+{
+    pub fn new(spi: S, mut cs: CS, dc: DC) -> Result<Self, E> {
+        cs.set_high()?;
+        Ok(Self { spi, cs, dc })
+    }
+
+    pub fn init(&mut self) -> Result<(), E> {
+        // These settings are for the NHD-3.12-25664UCY2 display module, as
+        // in the ssd1322-hal example.
+        self.command(0xfd, &[0b00010010])?; // Disable command lock
+        self.command(0xae, &[])?; // Disable display during init
+        self.command(0x15, &[0x1c, 0x5b])?; // Set column address
+        self.command(0x75, &[0x00, 0x3f])?; // Set row address
+        self.command(0xb3, &[0x91])?; // Set display clock
+        self.command(0xca, &[0x3f])?; // Set multiplex ratio
+        self.command(0xa2, &[0x00])?; // Set display offset
+        self.command(0xa1, &[0x00])?; // Set start line
+        self.command(0xa0, &[0b00010100, 0b00010001])?; // Set remap format
+        self.command(0xb5, &[0x00])?; // Turn off all GPIO
+        self.command(0xab, &[0x01])?; // Enable on-board regulator
+        self.command(0xb4, &[0xa0, 0xfd])?; // Set display enhancements A
+        self.command(0xc1, &[0x9f])?; // Set contrast current
+        self.command(0xc7, &[0x0f])?; // Set master current
+        self.command(0xb9, &[])?; // Select linear grayscale table
+        self.command(0xb1, &[0xe2])?; // Set phase length
+        self.command(0xd1, &[0xa2, 0x20])?; // Set display enhancements B
+        self.command(0xbb, &[0x1d])?; // Set precharge voltage
+        self.command(0xb6, &[0x08])?; // Set precharge period
+        self.command(0xbe, &[0x07])?; // Set VCOMH
+        self.command(0xa6, &[])?; // Normal display mode
+        self.command(0xaf, &[]) // Enable display
+    }
+
+    // command sends a command byte with the D/C signal low, followed by its
+    // data bytes, if any, with the D/C signal high.
+    pub fn command(&mut self, cmd: u8, data: &[u8]) -> Result<(), E> {
+        self.cs.set_low()?;
+        let result = self.send(cmd, data);
+        self.cs.set_high()?;
+        result
+    }
+
+    fn send(&mut self, cmd: u8, data: &[u8]) -> Result<(), E> {
+        self.dc.set_low()?;
+        self.spi.write(&[cmd])?;
+        if !data.is_empty() {
+            self.dc.set_high()?;
+            self.spi.write(data)?;
+        }
+        Ok(())
+    }
+}
+
+struct SpiFlash<S, P> {
+    spi: S,
+    cs: P,
+}
+
+impl<S, P, E> SpiFlash<S, P>
+where
+    S: Transfer<u8, Error = E>,
+    P: OutputPin<Error = E>,
+{
+    pub fn new(spi: S, mut cs: P) -> Result<Self, E> {
+        cs.set_high()?;
+        Ok(Self { spi, cs })
+    }
+
+    pub fn read_id(&mut self) -> Result<[u8; 3], E> {
+        // The JEDEC "read identification" command is 0x9f, after which the
+        // device returns its manufacturer ID and two bytes of device ID.
+        let mut buf = [0x9f, 0x00, 0x00, 0x00];
+        self.cs.set_low()?;
+        let result = self.spi.transfer(&mut buf).map(|_| ());
+        self.cs.set_high()?;
+        result?;
+        Ok([buf[1], buf[2], buf[3]])
+    }
+}
@@ -1,5 +1,5 @@
 use serial_embedded_hal::{PortSettings, Serial};
-use spidriver::SPIDriver;
+use spidriver::{PinState, SPIDriver};
 use spidriver_hal::SPIDriverHAL;
 
 fn main() {
@@ -32,8 +32,8 @@ fn main() {
 
     // Pulse the reset signal to reset the OLED driver chip before we do
     // anything else.
-    sd.set_b(false).unwrap();
-    sd.set_b(true).unwrap();
+    sd.set_b(PinState::Low).unwrap();
+    sd.set_b(PinState::High).unwrap();
 
     let sdh = SPIDriverHAL::new(sd);
     let parts = sdh.split();
@@ -51,7 +51,7 @@ fn main() {
     for y in 0..HEIGHT {
         // each byte represents two pixels
         for x in 0..(WIDTH / 2) {
-            buf[(y * WIDTH / 2 + x)] = if y % 2 == 0 { 0xf0 } else { 0x0f };
+            buf[y * WIDTH / 2 + x] = if y % 2 == 0 { 0xf0 } else { 0x0f };
         }
     }
     driver.cmd_n(0x5c, &mut buf[..]).unwrap();
@@ -110,11 +110,7 @@ where
     DC: embedded_hal::digital::v2::OutputPin,
 {
     pub fn new(spi: SPI, cs: CS, dc: DC) -> Self {
-        Self {
-            spi: spi,
-            cs: cs,
-            dc: dc,
-        }
+        Self { spi, cs, dc }
     }
 
     // A real-world driver would hopefully provide a higher-level API than
@@ -154,7 +150,7 @@ where
         self.write_byte(cmd)?;
         let mut remain = data;
         self.data_mode()?;
-        while remain.len() > 0 {
+        while !remain.is_empty() {
             let len: usize = if remain.len() > 64 { 64 } else { remain.len() };
             let (this, next) = remain.split_at_mut(len);
             self.write_bytes(this)?;
@@ -0,0 +1,78 @@
+//! Implementations of the `embedded-hal-async` SPI traits for target devices
+//! sharing an `AsyncSPIDriver`.
+//!
+//! An `AsyncSPIDriver` from the `spidriver` crate implements `SpiBus`, and
+//! its `device` method returns an `SpiDevice` that borrows it exclusively.
+//! To instead share one SPIDriver between several asynchronous drivers,
+//! such as drivers running in different tasks, place the `AsyncSPIDriver`
+//! in a mutex implementing `mutex::AsyncBusMutex` and then create a
+//! `SharedDevice` for each driver.
+//!
+//! Target devices selected by their own chip select pins, rather than by
+//! the SPIDriver's chip select signal, can instead share the
+//! `AsyncSPIDriver` using the `SpiBus`-based devices from crates such as
+//! `embassy-embedded-hal`.
+//!
+//! This module is available only when the `async` feature is enabled.
+
+use crate::mutex::AsyncBusMutex;
+use crate::Error;
+use embedded_hal_async::delay::DelayNs;
+use embedded_hal_async::spi::{self, Operation, SpiDevice};
+use embedded_io_async::{Read, Write};
+use spidriver::AsyncSPIDriver;
+
+/// `SharedDevice` implements the `embedded-hal-async` `SpiDevice` trait
+/// using the SPIDriver's own chip select signal, for an `AsyncSPIDriver`
+/// protected by a mutex of type `M`.
+///
+/// Each transaction waits to lock the mutex and then holds it until the
+/// transaction is complete, including across any delay operations, and so
+/// the transactions of devices sharing the same mutex never interleave.
+#[derive(Debug)]
+pub struct SharedDevice<'a, M, D> {
+    bus: &'a M,
+    delay: D,
+}
+
+impl<'a, M, D> SharedDevice<'a, M, D> {
+    /// `new` returns a device that performs its transactions through the
+    /// `AsyncSPIDriver` protected by the given mutex.
+    ///
+    /// `delay` implements any `Operation::DelayNs` in a transaction.
+    pub fn new(bus: &'a M, delay: D) -> Self {
+        Self { bus, delay }
+    }
+}
+
+impl<'a, M, D, TX, RX, TXErr, RXErr> spi::ErrorType for SharedDevice<'a, M, D>
+where
+    M: AsyncBusMutex<Bus = AsyncSPIDriver<TX, RX>>,
+    TX: Write<Error = TXErr>,
+    RX: Read<Error = RXErr>,
+    TXErr: core::fmt::Debug,
+    RXErr: core::fmt::Debug,
+{
+    type Error = Error<TXErr, RXErr>;
+}
+
+impl<'a, M, D, TX, RX, TXErr, RXErr> SpiDevice<u8> for SharedDevice<'a, M, D>
+where
+    M: AsyncBusMutex<Bus = AsyncSPIDriver<TX, RX>>,
+    TX: Write<Error = TXErr>,
+    RX: Read<Error = RXErr>,
+    D: DelayNs,
+    TXErr: core::fmt::Debug,
+    RXErr: core::fmt::Debug,
+{
+    async fn transaction(
+        &mut self,
+        operations: &mut [Operation<'_, u8>],
+    ) -> Result<(), Self::Error> {
+        let mut sd = self.bus.lock().await;
+        sd.device(&mut self.delay)
+            .transaction(operations)
+            .await
+            .map_err(Error::Comms)
+    }
+}
@@ -0,0 +1,410 @@
+//! Implementations of the embedded-hal 0.2 traits, available only when the
+//! `eh0_2` feature is enabled.
+
+use embedded_hal::blocking::delay;
+use embedded_hal::blocking::spi;
+use embedded_hal::digital::v2 as gpiov2;
+use embedded_hal::spi::FullDuplex;
+use spidriver::{CsState, Endianness, PinState, MAX_FRAME};
+
+use crate::hal::{Comms, Delay, PinA, PinB, CS, SPI};
+
+// DuplexQueue holds the words sent using FullDuplex::send that haven't yet
+// been transferred, and the words received in return that haven't yet been
+// returned by FullDuplex::read.
+pub(crate) struct DuplexQueue {
+    tx: [u8; MAX_FRAME],
+    tx_len: usize,
+    rx: [u8; MAX_FRAME],
+    rx_pos: usize,
+    rx_len: usize,
+}
+
+impl DuplexQueue {
+    pub(crate) fn new() -> Self {
+        Self {
+            tx: [0; MAX_FRAME],
+            tx_len: 0,
+            rx: [0; MAX_FRAME],
+            rx_pos: 0,
+            rx_len: 0,
+        }
+    }
+}
+
+impl<SD, E> SPI<SD>
+where
+    SD: Comms<Error = E>,
+{
+    // transfer_queued transfers all of the words waiting in the send queue
+    // as a single frame, making their responses available to read. The
+    // caller must first make sure that the receive queue is empty.
+    fn transfer_queued(&mut self) -> Result<(), E> {
+        let q = &mut self.queue;
+        let len = q.tx_len;
+        q.rx[..len].copy_from_slice(&q.tx[..len]);
+        q.tx_len = 0;
+        q.rx_pos = 0;
+        q.rx_len = 0;
+        self.sd.transfer(&mut q.rx[..len])?;
+        q.rx_len = len;
+        Ok(())
+    }
+}
+
+impl<SD, E> spi::Transfer<u8> for SPI<SD>
+where
+    SD: Comms<Error = E>,
+{
+    type Error = E;
+
+    /// Implements blocking SPI `Transfer` by passing the given data to the
+    /// SPIDriver in chunks of up to 64 bytes each, or of the size set using
+    /// `SPIDriver::set_chunk_size`.
+    ///
+    /// Because of the chunking behavior, larger messages may have inconsistent
+    /// timing at the chunk boundaries, which may affect devices with particularly
+    /// sensitive clock timing constraints.
+    fn transfer<'w>(&mut self, data: &'w mut [u8]) -> Result<&'w [u8], E> {
+        self.sd.transfer(data)
+    }
+}
+
+impl<SD, E> spi::Write<u8> for SPI<SD>
+where
+    SD: Comms<Error = E>,
+{
+    type Error = E;
+
+    /// Implements blocking SPI `Write` by passing the given data to the
+    /// SPIDriver in chunks of up to 64 bytes each, or of the size set using
+    /// `SPIDriver::set_chunk_size`.
+    ///
+    /// Because of the chunking behavior, larger messages may have inconsistent
+    /// timing at the chunk boundaries, which may affect devices with particularly
+    /// sensitive clock timing constraints.
+    fn write(&mut self, data: &[u8]) -> Result<(), E> {
+        self.sd.write(data)
+    }
+}
+
+impl<SD, E> spi::Transfer<u16> for SPI<SD>
+where
+    SD: Comms<Error = E>,
+{
+    type Error = E;
+
+    /// Implements blocking SPI `Transfer` for 16-bit words by passing the
+    /// given data to the SPIDriver in chunks of up to 32 words each, with
+    /// the bytes of each word in the order selected using `set_word_order`.
+    fn transfer<'w>(&mut self, words: &'w mut [u16]) -> Result<&'w [u16], E> {
+        let order = self.order;
+        let mut frame = [0_u8; MAX_FRAME];
+        for chunk in words.chunks_mut(MAX_FRAME / 2) {
+            let bytes = &mut frame[..chunk.len() * 2];
+            for (v, dst) in chunk.iter().zip(bytes.chunks_mut(2)) {
+                dst.copy_from_slice(&match order {
+                    Endianness::Big => v.to_be_bytes(),
+                    Endianness::Little => v.to_le_bytes(),
+                });
+            }
+            self.sd.transfer(bytes)?;
+            for (v, src) in chunk.iter_mut().zip(bytes.chunks(2)) {
+                let src = [src[0], src[1]];
+                *v = match order {
+                    Endianness::Big => u16::from_be_bytes(src),
+                    Endianness::Little => u16::from_le_bytes(src),
+                };
+            }
+        }
+        Ok(words)
+    }
+}
+
+impl<SD, E> spi::Write<u16> for SPI<SD>
+where
+    SD: Comms<Error = E>,
+{
+    type Error = E;
+
+    /// Implements blocking SPI `Write` for 16-bit words by packing the
+    /// given words into 64-byte frames, with the bytes of each word in the
+    /// order selected using `set_word_order`.
+    fn write(&mut self, words: &[u16]) -> Result<(), E> {
+        self.sd.write_u16s(words, self.order)
+    }
+}
+
+impl<SD, E> spi::WriteIter<u8> for SPI<SD>
+where
+    SD: Comms<Error = E>,
+{
+    type Error = E;
+
+    /// Implements blocking SPI `WriteIter` by collecting the words from the
+    /// iterator into frames of 64 bytes each and passing each frame to the
+    /// SPIDriver as soon as it is full, so the data need not be buffered
+    /// in full first.
+    ///
+    /// The chunking behavior has the same consequences as for `Write`.
+    fn write_iter<WI>(&mut self, words: WI) -> Result<(), E>
+    where
+        WI: IntoIterator<Item = u8>,
+    {
+        let mut frame = [0_u8; MAX_FRAME];
+        let mut len = 0;
+        for word in words {
+            frame[len] = word;
+            len += 1;
+            if len == MAX_FRAME {
+                self.sd.write(&frame)?;
+                len = 0;
+            }
+        }
+        if len > 0 {
+            self.sd.write(&frame[..len])?;
+        }
+        Ok(())
+    }
+}
+
+impl<SD, E> spi::Transactional<u8> for SPI<SD>
+where
+    SD: Comms<Error = E>,
+{
+    type Error = E;
+
+    /// Implements blocking SPI `Transactional` by asserting the SPIDriver's
+    /// chip select signal, performing each of the operations in order, and
+    /// then releasing the chip select signal again, even if one of the
+    /// operations failed.
+    ///
+    /// Each operation is split into chunks as described for `Write` and
+    /// `Transfer`, while the target device remains selected.
+    fn exec<'o>(&mut self, operations: &mut [spi::Operation<'o, u8>]) -> Result<(), E> {
+        self.sd.begin_transaction()?;
+        let result = self.sd.set_cs(CsState::Asserted).and_then(|()| {
+            let result = operations.iter_mut().try_for_each(|op| match op {
+                spi::Operation::Write(data) => self.sd.write(data),
+                spi::Operation::Transfer(data) => self.sd.transfer(data).map(|_| ()),
+            });
+            // We release the chip select signal even if an operation failed,
+            // but report the operation's error in preference to any error
+            // from releasing it.
+            let released = self.sd.set_cs(CsState::Released);
+            result.and(released)
+        });
+        self.sd.end_transaction();
+        result
+    }
+}
+
+impl<SD, E> FullDuplex<u8> for SPI<SD>
+where
+    SD: Comms<Error = E>,
+{
+    type Error = E;
+
+    /// Implements non-blocking SPI `FullDuplex::read` by returning the word
+    /// received in exchange for the oldest word sent that hasn't yet been
+    /// read.
+    ///
+    /// Words passed to `send` are queued rather than being transferred
+    /// immediately, and `read` then transfers everything in the queue as a
+    /// single protocol frame. Callers that send several words before
+    /// reading the responses therefore need fewer round-trips to the
+    /// SPIDriver. `read` returns `WouldBlock` if no words have been sent
+    /// since the last read.
+    fn read(&mut self) -> nb::Result<u8, E> {
+        let q = &mut self.queue;
+        if q.rx_pos == q.rx_len {
+            if q.tx_len == 0 {
+                return Err(nb::Error::WouldBlock);
+            }
+            self.transfer_queued().map_err(nb::Error::Other)?;
+        }
+        let q = &mut self.queue;
+        let word = q.rx[q.rx_pos];
+        q.rx_pos += 1;
+        Ok(word)
+    }
+
+    /// Implements non-blocking SPI `FullDuplex::send` by adding the word to
+    /// a queue of up to 64 words, to be transferred by a later call to
+    /// `read`.
+    ///
+    /// When the queue is full, `send` transfers the words already in it to
+    /// make room, unless the responses to earlier words haven't yet been
+    /// read, in which case it returns `WouldBlock`. A caller that reads the
+    /// response to each word, as `FullDuplex` requires, never encounters
+    /// that situation.
+    fn send(&mut self, word: u8) -> nb::Result<(), E> {
+        if self.queue.tx_len == MAX_FRAME {
+            if self.queue.rx_pos < self.queue.rx_len {
+                return Err(nb::Error::WouldBlock);
+            }
+            self.transfer_queued().map_err(nb::Error::Other)?;
+        }
+        let q = &mut self.queue;
+        q.tx[q.tx_len] = word;
+        q.tx_len += 1;
+        Ok(())
+    }
+}
+
+impl<SD, E> gpiov2::OutputPin for CS<SD>
+where
+    SD: Comms<Error = E>,
+{
+    type Error = E;
+
+    fn set_low(&mut self) -> Result<(), E> {
+        self.0.set_cs(self.state_for(PinState::Low))
+    }
+
+    fn set_high(&mut self) -> Result<(), E> {
+        self.0.set_cs(self.state_for(PinState::High))
+    }
+}
+
+impl<SD, E> gpiov2::StatefulOutputPin for CS<SD>
+where
+    SD: Comms<Error = E>,
+{
+    fn is_set_high(&self) -> Result<bool, E> {
+        Ok(self.0.cs()? == self.state_for(PinState::High))
+    }
+
+    fn is_set_low(&self) -> Result<bool, E> {
+        Ok(self.0.cs()? == self.state_for(PinState::Low))
+    }
+}
+
+impl<SD> gpiov2::toggleable::Default for CS<SD> where SD: Comms {}
+
+impl<SD, E> gpiov2::OutputPin for PinA<SD>
+where
+    SD: Comms<Error = E>,
+{
+    type Error = E;
+
+    fn set_low(&mut self) -> Result<(), E> {
+        self.0.set_a(PinState::Low)
+    }
+
+    fn set_high(&mut self) -> Result<(), E> {
+        self.0.set_a(PinState::High)
+    }
+}
+
+impl<SD, E> gpiov2::StatefulOutputPin for PinA<SD>
+where
+    SD: Comms<Error = E>,
+{
+    fn is_set_high(&self) -> Result<bool, E> {
+        Ok(self.0.a()? == PinState::High)
+    }
+
+    fn is_set_low(&self) -> Result<bool, E> {
+        Ok(self.0.a()? == PinState::Low)
+    }
+}
+
+impl<SD> gpiov2::toggleable::Default for PinA<SD> where SD: Comms {}
+
+impl<SD, E> gpiov2::OutputPin for PinB<SD>
+where
+    SD: Comms<Error = E>,
+{
+    type Error = E;
+
+    fn set_low(&mut self) -> Result<(), E> {
+        self.0.set_b(PinState::Low)
+    }
+
+    fn set_high(&mut self) -> Result<(), E> {
+        self.0.set_b(PinState::High)
+    }
+}
+
+impl<SD, E> gpiov2::StatefulOutputPin for PinB<SD>
+where
+    SD: Comms<Error = E>,
+{
+    fn is_set_high(&self) -> Result<bool, E> {
+        Ok(self.0.b()? == PinState::High)
+    }
+
+    fn is_set_low(&self) -> Result<bool, E> {
+        Ok(self.0.b()? == PinState::Low)
+    }
+}
+
+impl<SD> gpiov2::toggleable::Default for PinB<SD> where SD: Comms {}
+
+// The v1 OutputPin trait is deprecated, but LegacyPin exists to implement it
+// for the drivers that still use it.
+#[cfg(feature = "digital-v1")]
+#[allow(deprecated)]
+impl<P, E> embedded_hal::digital::v1::OutputPin for crate::hal::LegacyPin<P>
+where
+    P: gpiov2::OutputPin<Error = E>,
+    E: core::fmt::Debug,
+{
+    fn set_low(&mut self) {
+        report_pin_error(self.0.set_low())
+    }
+
+    fn set_high(&mut self) {
+        report_pin_error(self.0.set_high())
+    }
+}
+
+// report_pin_error handles an error from a LegacyPin, which has no way to
+// return it, by logging it when the log feature is enabled or by panicking
+// otherwise.
+#[cfg(feature = "digital-v1")]
+fn report_pin_error<E: core::fmt::Debug>(result: Result<(), E>) {
+    if let Err(err) = result {
+        #[cfg(feature = "log")]
+        log::error!("failed to set output pin level: {:?}", err);
+        #[cfg(not(feature = "log"))]
+        panic!("failed to set output pin level: {:?}", err);
+    }
+}
+
+impl delay::DelayUs<u32> for Delay {
+    fn delay_us(&mut self, us: u32) {
+        self.wait_us(us as u64)
+    }
+}
+
+impl delay::DelayUs<u16> for Delay {
+    fn delay_us(&mut self, us: u16) {
+        self.wait_us(us as u64)
+    }
+}
+
+impl delay::DelayUs<u8> for Delay {
+    fn delay_us(&mut self, us: u8) {
+        self.wait_us(us as u64)
+    }
+}
+
+impl delay::DelayMs<u32> for Delay {
+    fn delay_ms(&mut self, ms: u32) {
+        self.wait_us(ms as u64 * 1000)
+    }
+}
+
+impl delay::DelayMs<u16> for Delay {
+    fn delay_ms(&mut self, ms: u16) {
+        self.wait_us(ms as u64 * 1000)
+    }
+}
+
+impl delay::DelayMs<u8> for Delay {
+    fn delay_ms(&mut self, ms: u8) {
+        self.wait_us(ms as u64 * 1000)
+    }
+}
@@ -0,0 +1,437 @@
+//! Implementations of the embedded-hal 1.0 traits, available only when the
+//! `eh1` feature is enabled.
+
+use embedded_hal_1::delay::DelayNs;
+use embedded_hal_1::digital;
+use embedded_hal_1::spi::{self, Operation};
+use spidriver::{CsState, PinState};
+
+use crate::hal::{Comms, Delay, PinA, PinB, CS, SPI};
+
+/// `Device` implements the embedded-hal 1.0 `SpiDevice` trait in terms of an
+/// SPIDriver device, using either the SPIDriver's own chip select signal or,
+/// for a device obtained from `DeviceParts`, auxillary output pin A.
+///
+/// Each transaction asserts the chip select signal, performs all of its
+/// operations in order, and then releases the chip select signal again,
+/// even if one of the operations failed. Pin A is treated as an active-low
+/// chip select signal, like the SPIDriver's own. Operations longer than 64 bytes
+/// are split into several protocol frames, as with `SPIDriver::write_all`
+/// and `SPIDriver::transfer_all`, while the target device remains
+/// selected.
+///
+/// Because the SPIDriver protocol has no way to pause the SPI bus,
+/// `Operation::DelayNs` is implemented by calling the `DelayNs`
+/// implementation given to `SPIDriverHAL::device`. That delay is measured
+/// on the host, and so is only a lower bound on the delay between the
+/// operations on the bus.
+///
+/// The data sent during `Operation::Read`, and during `Operation::Transfer`
+/// when the read buffer is the longer of the two, is 0xff, matching the
+/// idle state of the MOSI line.
+///
+/// Transactions never interleave with those of other devices sharing the
+/// same SPIDriver. With an `SPIDriverHAL`, a transaction that begins while
+/// one on another thread is in progress waits for it to end, as described
+/// for the `std::sync::Mutex` implementation of `BusMutex`. A transaction
+/// that begins while another is in progress on the same thread, which can
+/// happen only if the delay implementation re-enters the HAL, fails with
+/// `Error::BusBusy`.
+pub struct Device<SD: Comms, D> {
+    sd: SD,
+    delay: D,
+    cs: ChipSelect,
+}
+
+impl<SD, D> Device<SD, D>
+where
+    SD: Comms,
+{
+    /// `new` creates a device that uses the chip select signal of the given
+    /// `Comms` implementation, and the given delay for any
+    /// `Operation::DelayNs`.
+    ///
+    /// `SPIDriverHAL::device` calls `new` with a reference to the
+    /// `SPIDriverHAL`.
+    pub fn new(sd: SD, delay: D) -> Self {
+        Self {
+            sd,
+            delay,
+            cs: ChipSelect::Cs,
+        }
+    }
+}
+
+// ChipSelect is the output pin that a Device uses to select its target.
+#[derive(Clone, Copy)]
+enum ChipSelect {
+    Cs,
+    PinA,
+}
+
+impl ChipSelect {
+    fn set<SD: Comms>(self, sd: &SD, selected: bool) -> Result<(), SD::Error> {
+        match (self, selected) {
+            (ChipSelect::Cs, true) => sd.set_cs(CsState::Asserted),
+            (ChipSelect::Cs, false) => sd.set_cs(CsState::Released),
+            (ChipSelect::PinA, true) => sd.set_a(PinState::Low),
+            (ChipSelect::PinA, false) => sd.set_a(PinState::High),
+        }
+    }
+}
+
+/// `DeviceParts` is a container for the parts of a SPIDriver that is
+/// shared by two target devices on the same SPI bus, obtained using
+/// `SPIDriverHAL::split_devices`.
+///
+/// The target devices share the SPIDriver's clock and data signals, and
+/// each has its own chip select signal: the SPIDriver's own for `device`,
+/// and auxillary output pin A for `device_a`. Because pin A then serves as
+/// a chip select signal, there's no separate part for it.
+pub struct DeviceParts<SD: Comms, D> {
+    /// `device` is an implementation of the `SpiDevice` trait for the
+    /// target device selected by the SPIDriver's chip select signal.
+    pub device: Device<SD, D>,
+
+    /// `device_a` is an implementation of the `SpiDevice` trait for the
+    /// target device selected by driving auxillary output pin A low.
+    pub device_a: Device<SD, D>,
+
+    /// `pin_b` controls the SPIDriver's auxillary output pin "B", as for
+    /// the part of the same name in `Parts`.
+    pub pin_b: PinB<SD>,
+
+    /// `delay` is a copy of the delay that both devices use for any
+    /// `Operation::DelayNs`.
+    pub delay: D,
+}
+
+impl<SD, D> DeviceParts<SD, D>
+where
+    SD: Comms + Clone,
+    D: Clone,
+{
+    /// `new` derives a pair of devices from the given `Comms`
+    /// implementation, after releasing both chip select signals so that
+    /// neither target device is selected until a transaction begins. Both
+    /// devices use a copy of the given delay for any `Operation::DelayNs`.
+    ///
+    /// `SPIDriverHAL::split_devices` calls `new` with a reference to the
+    /// `SPIDriverHAL`.
+    pub fn new(sd: SD, delay: D) -> Result<Self, SD::Error> {
+        ChipSelect::Cs.set(&sd, false)?;
+        ChipSelect::PinA.set(&sd, false)?;
+        Ok(Self {
+            device: Device {
+                sd: sd.clone(),
+                delay: delay.clone(),
+                cs: ChipSelect::Cs,
+            },
+            device_a: Device {
+                sd: sd.clone(),
+                delay: delay.clone(),
+                cs: ChipSelect::PinA,
+            },
+            pin_b: PinB::new(sd),
+            delay,
+        })
+    }
+}
+
+impl<SD, D, E> spi::ErrorType for Device<SD, D>
+where
+    SD: Comms<Error = E>,
+    E: spi::Error,
+{
+    type Error = E;
+}
+
+impl<SD, D, E> spi::SpiDevice<u8> for Device<SD, D>
+where
+    SD: Comms<Error = E>,
+    D: DelayNs,
+    E: spi::Error,
+{
+    fn transaction(&mut self, operations: &mut [Operation<'_, u8>]) -> Result<(), E> {
+        self.sd.begin_transaction()?;
+        let result = self.cs.set(&self.sd, true).and_then(|()| {
+            let result = operations.iter_mut().try_for_each(|op| self.operation(op));
+            // We release the chip select signal even if an operation failed,
+            // but report the operation's error in preference to any error
+            // from releasing it.
+            let released = self.cs.set(&self.sd, false);
+            result.and(released)
+        });
+        self.sd.end_transaction();
+        result
+    }
+}
+
+impl<SD, D, E> Device<SD, D>
+where
+    SD: Comms<Error = E>,
+    D: DelayNs,
+{
+    fn operation(&mut self, op: &mut Operation<'_, u8>) -> Result<(), E> {
+        match op {
+            Operation::Read(buf) => {
+                buf.fill(0xff);
+                self.sd.transfer(buf)?;
+            }
+            Operation::Write(buf) => self.sd.write(buf)?,
+            Operation::Transfer(read, write) => transfer_split(&self.sd, read, write)?,
+            Operation::TransferInPlace(buf) => {
+                self.sd.transfer(buf)?;
+            }
+            Operation::DelayNs(ns) => self.delay.delay_ns(*ns),
+        }
+        Ok(())
+    }
+}
+
+// The SPI part implements the embedded-hal 1.0 SpiBus trait, leaving the
+// chip select signal alone as with the other SPI traits it implements, so
+// that it can be shared between devices using the wrappers from the
+// embedded-hal-bus crate.
+impl<SD, E> spi::ErrorType for SPI<SD>
+where
+    SD: Comms<Error = E>,
+    E: spi::Error,
+{
+    type Error = E;
+}
+
+impl<SD, E> spi::SpiBus<u8> for SPI<SD>
+where
+    SD: Comms<Error = E>,
+    E: spi::Error,
+{
+    /// Implements `SpiBus::read` by transferring 0xff bytes, matching the
+    /// idle state of the MOSI line.
+    fn read(&mut self, words: &mut [u8]) -> Result<(), E> {
+        words.fill(0xff);
+        self.sd.transfer(words)?;
+        Ok(())
+    }
+
+    fn write(&mut self, words: &[u8]) -> Result<(), E> {
+        self.sd.write(words)
+    }
+
+    /// Implements `SpiBus::transfer`, which supports read and write buffers
+    /// of different lengths. The transfer continues for the length of the
+    /// longer buffer, sending 0xff once the write buffer is exhausted and
+    /// discarding the data received once the read buffer is full.
+    fn transfer(&mut self, read: &mut [u8], write: &[u8]) -> Result<(), E> {
+        transfer_split(&self.sd, read, write)
+    }
+
+    fn transfer_in_place(&mut self, words: &mut [u8]) -> Result<(), E> {
+        self.sd.transfer(words)?;
+        Ok(())
+    }
+
+    /// Implements `SpiBus::flush` by waiting until the SPIDriver has
+    /// finished sending all of the data written so far, because writes
+    /// don't otherwise wait for the SPIDriver to respond.
+    ///
+    /// Wrappers such as `ExclusiveDevice` from the embedded-hal-bus crate
+    /// call `flush` before releasing their chip select pin, which may be
+    /// driven by the host rather than the SPIDriver.
+    fn flush(&mut self) -> Result<(), E> {
+        self.sd.flush()
+    }
+}
+
+impl DelayNs for Delay {
+    fn delay_ns(&mut self, ns: u32) {
+        // The clock has only microsecond resolution, so we round up.
+        self.wait_us((ns as u64).div_ceil(1000))
+    }
+}
+
+impl<SD, E> digital::ErrorType for CS<SD>
+where
+    SD: Comms<Error = E>,
+    E: digital::Error,
+{
+    type Error = E;
+}
+
+impl<SD, E> digital::OutputPin for CS<SD>
+where
+    SD: Comms<Error = E>,
+    E: digital::Error,
+{
+    fn set_low(&mut self) -> Result<(), E> {
+        self.0.set_cs(self.state_for(PinState::Low))
+    }
+
+    fn set_high(&mut self) -> Result<(), E> {
+        self.0.set_cs(self.state_for(PinState::High))
+    }
+}
+
+impl<SD, E> digital::StatefulOutputPin for CS<SD>
+where
+    SD: Comms<Error = E>,
+    E: digital::Error,
+{
+    fn is_set_high(&mut self) -> Result<bool, E> {
+        Ok(self.0.cs()? == self.state_for(PinState::High))
+    }
+
+    fn is_set_low(&mut self) -> Result<bool, E> {
+        Ok(self.0.cs()? == self.state_for(PinState::Low))
+    }
+}
+
+impl<SD, E> digital::ErrorType for PinA<SD>
+where
+    SD: Comms<Error = E>,
+    E: digital::Error,
+{
+    type Error = E;
+}
+
+impl<SD, E> digital::OutputPin for PinA<SD>
+where
+    SD: Comms<Error = E>,
+    E: digital::Error,
+{
+    fn set_low(&mut self) -> Result<(), E> {
+        self.0.set_a(PinState::Low)
+    }
+
+    fn set_high(&mut self) -> Result<(), E> {
+        self.0.set_a(PinState::High)
+    }
+}
+
+impl<SD, E> digital::StatefulOutputPin for PinA<SD>
+where
+    SD: Comms<Error = E>,
+    E: digital::Error,
+{
+    fn is_set_high(&mut self) -> Result<bool, E> {
+        Ok(self.0.a()? == PinState::High)
+    }
+
+    fn is_set_low(&mut self) -> Result<bool, E> {
+        Ok(self.0.a()? == PinState::Low)
+    }
+}
+
+impl<SD, E> digital::ErrorType for PinB<SD>
+where
+    SD: Comms<Error = E>,
+    E: digital::Error,
+{
+    type Error = E;
+}
+
+impl<SD, E> digital::OutputPin for PinB<SD>
+where
+    SD: Comms<Error = E>,
+    E: digital::Error,
+{
+    fn set_low(&mut self) -> Result<(), E> {
+        self.0.set_b(PinState::Low)
+    }
+
+    fn set_high(&mut self) -> Result<(), E> {
+        self.0.set_b(PinState::High)
+    }
+}
+
+impl<SD, E> digital::StatefulOutputPin for PinB<SD>
+where
+    SD: Comms<Error = E>,
+    E: digital::Error,
+{
+    fn is_set_high(&mut self) -> Result<bool, E> {
+        Ok(self.0.b()? == PinState::High)
+    }
+
+    fn is_set_low(&mut self) -> Result<bool, E> {
+        Ok(self.0.b()? == PinState::Low)
+    }
+}
+
+// transfer_split implements a transfer with separate read and write buffers
+// of possibly different lengths. The part where the buffers overlap is
+// transferred in place in the read buffer. Any remaining part of the read
+// buffer is then read as with SpiBus::read, or any remaining part of the
+// write buffer is written, discarding what the device sends back.
+fn transfer_split<SD: Comms>(sd: &SD, read: &mut [u8], write: &[u8]) -> Result<(), SD::Error> {
+    let n = read.len().min(write.len());
+    let (read, read_rest) = read.split_at_mut(n);
+    let (write, write_rest) = write.split_at(n);
+    if n > 0 {
+        read.copy_from_slice(write);
+        sd.transfer(read)?;
+    }
+    if !read_rest.is_empty() {
+        read_rest.fill(0xff);
+        sd.transfer(read_rest)?;
+    }
+    if !write_rest.is_empty() {
+        sd.write(write_rest)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use core::cell::RefCell;
+    use embedded_hal_1::spi::SpiDevice;
+    use embedded_hal_bus::spi::{ExclusiveDevice, NoDelay, RefCellDevice};
+    use spidriver::CsState;
+    use std::vec;
+
+    use crate::hal::tests::{Event, Recorder};
+    use crate::hal::Parts;
+
+    #[test]
+    fn exclusive_device_flushes_before_release() {
+        let sd = Recorder::default();
+        let parts = Parts::new(&sd, None);
+        let mut device = ExclusiveDevice::new(parts.spi, parts.cs, NoDelay).unwrap();
+        sd.take_events();
+
+        device.write(&[1, 2, 3]).unwrap();
+        assert_eq!(
+            sd.take_events(),
+            [
+                Event::Cs(CsState::Asserted),
+                Event::Write(vec![1, 2, 3]),
+                Event::Flush,
+                Event::Cs(CsState::Released),
+            ]
+        );
+    }
+
+    #[test]
+    fn refcell_device_flushes_before_release() {
+        let sd = Recorder::default();
+        let parts = Parts::new(&sd, None);
+        let bus = RefCell::new(parts.spi);
+        let mut device = RefCellDevice::new(&bus, parts.cs, NoDelay).unwrap();
+        sd.take_events();
+
+        let mut buf = [4, 5];
+        device.transfer_in_place(&mut buf).unwrap();
+        assert_eq!(buf, [4, 5]);
+        assert_eq!(
+            sd.take_events(),
+            [
+                Event::Cs(CsState::Asserted),
+                Event::Transfer(vec![4, 5]),
+                Event::Flush,
+                Event::Cs(CsState::Released),
+            ]
+        );
+    }
+}
@@ -0,0 +1,157 @@
+use crate::mutex::LockError;
+use spidriver::ErrorKind;
+
+/// `Error` is the error type returned by the HAL objects obtained from an
+/// `SPIDriverHAL`.
+///
+/// When the `eh1` feature is enabled, `Error` implements the embedded-hal
+/// 1.0 SPI and digital I/O `Error` traits, so that the HAL objects satisfy
+/// generic bounds such as `SpiDevice<Error: spi::Error>` and
+/// `OutputPin<Error: digital::Error>`.
+#[derive(Debug)]
+pub enum Error<TXErr, RXErr> {
+    /// `Comms` indicates that communicating with the SPIDriver failed.
+    Comms(spidriver::Error<TXErr, RXErr>),
+
+    /// `Poisoned` indicates that the mutex shared by the HAL objects was
+    /// poisoned by a panic on another thread. See `mutex::LockError`.
+    Poisoned,
+
+    /// `BusBusy` indicates that the SPIDriver was already in use by another
+    /// request or transaction that had not yet completed, such as when a
+    /// HAL object is used from within a callback that runs during an
+    /// operation on another.
+    ///
+    /// When the `std` feature is enabled, a transaction that begins while
+    /// one on another thread is in progress waits for it to end instead.
+    BusBusy,
+
+    /// `Fault` indicates that the operation was refused without
+    /// communicating with the SPIDriver, because an earlier communication
+    /// error left it in a state that doesn't permit the operation.
+    Fault(BusFault),
+}
+
+/// `BusFault` describes how an earlier communication error left the host
+/// and the SPIDriver out of step, as returned by `SPIDriverHAL::fault`.
+///
+/// A fault is shared by all of the HAL objects from an `SPIDriverHAL`, but
+/// refuses only the operations that it could cause to go wrong. It remains
+/// until cleared using `SPIDriverHAL::resync` or
+/// `SPIDriverHAL::clear_fault`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BusFault {
+    /// `Responses` indicates that an operation failed while waiting for a
+    /// response from the SPIDriver, after its command had been completely
+    /// sent, and so the response may still arrive later.
+    ///
+    /// Operations that read a response, which are SPI transfers and flushes
+    /// and requests for the levels of the output pins, fail with
+    /// `Error::Fault`. Operations that only send commands keep working, so
+    /// the output pins can still be set and data can still be written to
+    /// the SPI bus.
+    Responses,
+
+    /// `Commands` indicates that a command may have been only partly sent,
+    /// and so the SPIDriver could interpret whatever is sent next as part of
+    /// it. All operations fail with `Error::Fault`.
+    Commands,
+}
+
+impl BusFault {
+    // for_kind returns the fault that an error of the given kind leaves,
+    // or None if the error occurred before anything was sent.
+    pub(crate) fn for_kind(kind: ErrorKind) -> Option<Self> {
+        match kind {
+            ErrorKind::Request(_) | ErrorKind::Unsupported(_) => None,
+            ErrorKind::Timeout
+            | ErrorKind::Read
+            | ErrorKind::Protocol(_)
+            | ErrorKind::CrcMismatch { .. } => Some(BusFault::Responses),
+            _ => Some(BusFault::Commands),
+        }
+    }
+}
+
+impl<TXErr, RXErr> Error<TXErr, RXErr> {
+    /// `comms_error` returns the underlying `spidriver::Error`, if the
+    /// error is of the `Comms` kind.
+    pub fn comms_error(&self) -> Option<&spidriver::Error<TXErr, RXErr>> {
+        match self {
+            Error::Comms(err) => Some(err),
+            Error::Poisoned | Error::BusBusy | Error::Fault(_) => None,
+        }
+    }
+}
+
+impl<TXErr, RXErr> From<spidriver::Error<TXErr, RXErr>> for Error<TXErr, RXErr> {
+    fn from(err: spidriver::Error<TXErr, RXErr>) -> Self {
+        Error::Comms(err)
+    }
+}
+
+impl<TXErr, RXErr> From<LockError> for Error<TXErr, RXErr> {
+    fn from(err: LockError) -> Self {
+        match err {
+            LockError::Poisoned => Error::Poisoned,
+            LockError::Busy => Error::BusBusy,
+        }
+    }
+}
+
+impl<TXErr, RXErr> core::fmt::Display for Error<TXErr, RXErr> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Error::Comms(err) => err.fmt(f),
+            Error::Poisoned => f.write_str("SPIDriver mutex poisoned by a panic"),
+            Error::BusBusy => f.write_str("SPIDriver is busy with another operation"),
+            Error::Fault(BusFault::Responses) => {
+                f.write_str("SPIDriver responses out of step after an earlier error")
+            }
+            Error::Fault(BusFault::Commands) => {
+                f.write_str("SPIDriver commands out of step after an earlier error")
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<TXErr, RXErr> std::error::Error for Error<TXErr, RXErr>
+where
+    TXErr: std::error::Error + 'static,
+    RXErr: std::error::Error + 'static,
+{
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Comms(err) => Some(err),
+            Error::Poisoned | Error::BusBusy | Error::Fault(_) => None,
+        }
+    }
+}
+
+#[cfg(feature = "eh1")]
+impl<TXErr, RXErr> embedded_hal_1::spi::Error for Error<TXErr, RXErr>
+where
+    TXErr: core::fmt::Debug,
+    RXErr: core::fmt::Debug,
+{
+    fn kind(&self) -> embedded_hal_1::spi::ErrorKind {
+        match self {
+            Error::Comms(err) => embedded_hal_1::spi::Error::kind(err),
+            Error::Poisoned | Error::BusBusy | Error::Fault(_) => {
+                embedded_hal_1::spi::ErrorKind::Other
+            }
+        }
+    }
+}
+
+#[cfg(feature = "eh1")]
+impl<TXErr, RXErr> embedded_hal_1::digital::Error for Error<TXErr, RXErr>
+where
+    TXErr: core::fmt::Debug,
+    RXErr: core::fmt::Debug,
+{
+    fn kind(&self) -> embedded_hal_1::digital::ErrorKind {
+        embedded_hal_1::digital::ErrorKind::Other
+    }
+}
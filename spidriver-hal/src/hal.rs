@@ -1,189 +1,748 @@
-use embedded_hal::blocking::spi;
-use embedded_hal::digital::v2 as gpiov2;
+use spidriver::{Clock, CsState, Endianness, PinState, MAX_FRAME};
 
+/// `Comms` is the interface through which the HAL objects communicate with
+/// an SPIDriver, or with anything else that behaves like one.
+///
+/// `SPIDriverHAL` implements `Comms` in terms of an `SPIDriver`. Other
+/// implementations, such as a simulator for testing drivers or a
+/// multiplexer that shares one SPIDriver between several buses, can pass
+/// themselves to `Parts::new` to obtain HAL objects that implement all of
+/// the same traits.
+///
+/// All of the methods take `&self`, because the HAL objects obtained from
+/// a single `Comms` share it. An implementation therefore needs interior
+/// mutability, such as a `RefCell`, to change its own state.
+///
+/// Each HAL object holds its own handle to the shared implementation, so
+/// `Comms` is also implemented for references to an implementation and,
+/// when the `std` feature is enabled, for `Rc` and `Arc` pointers to one.
+/// Handles that borrow the implementation give HAL objects that borrow it
+/// too, while an `Rc` or `Arc` gives HAL objects that own it jointly.
+///
+/// The HAL objects return the errors from these methods unchanged, so to
+/// satisfy the embedded-hal 1.0 traits when the `eh1` feature is enabled,
+/// `Error` must implement the SPI and digital I/O `Error` traits from
+/// embedded-hal 1.0.
 pub trait Comms {
+    /// `Error` is the type of error returned by all of the methods that
+    /// communicate with the device.
     type Error;
 
-    fn set_cs(&self, active: bool) -> Result<(), Self::Error>;
-    fn set_a(&self, active: bool) -> Result<(), Self::Error>;
-    fn set_b(&self, active: bool) -> Result<(), Self::Error>;
+    /// `set_cs` sets the state of the chip select signal, as seen by the
+    /// target device. An implementation that supports an active-high chip
+    /// select signal inverts the state itself, as reported by
+    /// `cs_active_high`.
+    fn set_cs(&self, state: CsState) -> Result<(), Self::Error>;
+
+    /// `set_a` sets the level of the auxillary output pin "A".
+    fn set_a(&self, state: PinState) -> Result<(), Self::Error>;
+
+    /// `set_b` sets the level of the auxillary output pin "B".
+    fn set_b(&self, state: PinState) -> Result<(), Self::Error>;
+
+    /// `cs` returns the state of the chip select signal most recently set
+    /// using `set_cs`, or the device's current state if it hasn't been set.
+    fn cs(&self) -> Result<CsState, Self::Error>;
+
+    /// `cs_active_high` returns true if the chip select signal is active
+    /// high, in which case the `cs` part drives it high to select the target
+    /// device. The default implementation returns false.
+    fn cs_active_high(&self) -> bool {
+        false
+    }
+
+    /// `a` returns the level of the auxillary output pin "A" most recently
+    /// set using `set_a`, or the device's current level if it hasn't been set.
+    fn a(&self) -> Result<PinState, Self::Error>;
+
+    /// `b` returns the level of the auxillary output pin "B" most recently
+    /// set using `set_b`, or the device's current level if it hasn't been set.
+    fn b(&self) -> Result<PinState, Self::Error>;
+
+    /// `write` sends all of the given data over the SPI bus, discarding the
+    /// data received in return.
     fn write(&self, data: &[u8]) -> Result<(), Self::Error>;
+
+    /// `write_u16s` sends all of the given 16-bit words over the SPI bus,
+    /// with the bytes of each word in the given order, discarding the data
+    /// received in return.
+    ///
+    /// The default implementation packs the words into 64-byte chunks and
+    /// passes each chunk to `write`.
+    fn write_u16s(&self, data: &[u16], order: Endianness) -> Result<(), Self::Error> {
+        let mut frame = [0_u8; MAX_FRAME];
+        for chunk in data.chunks(MAX_FRAME / 2) {
+            let bytes = &mut frame[..chunk.len() * 2];
+            for (v, dst) in chunk.iter().zip(bytes.chunks_mut(2)) {
+                dst.copy_from_slice(&match order {
+                    Endianness::Big => v.to_be_bytes(),
+                    Endianness::Little => v.to_le_bytes(),
+                });
+            }
+            self.write(bytes)?;
+        }
+        Ok(())
+    }
+
+    /// `transfer` sends all of the given data over the SPI bus, replacing
+    /// each byte with the byte received in exchange for it, and returns the
+    /// received data.
     fn transfer<'w>(&self, data: &'w mut [u8]) -> Result<&'w [u8], Self::Error>;
+
+    /// `flush` waits until all of the data passed to `write` and `transfer`
+    /// so far has been sent over the SPI bus.
+    ///
+    /// The default implementation does nothing, which is correct only for
+    /// implementations whose `write` doesn't return until that's true.
+    fn flush(&self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    /// `begin_transaction` is called at the start of each transaction that
+    /// selects a target device, such as those of `SpiDevice`.
+    ///
+    /// An implementation can use them to make sure that transactions never
+    /// interleave. If `begin_transaction` returns an error, the transaction
+    /// fails with that error without performing any of its operations. The
+    /// default implementations do nothing.
+    fn begin_transaction(&self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    /// `end_transaction` is called at the end of each transaction begun
+    /// successfully by `begin_transaction`, even if the transaction failed.
+    fn end_transaction(&self) {}
+}
+
+// forward_comms implements Comms for a pointer type by calling through to the
+// implementation it points to.
+macro_rules! forward_comms {
+    ($($ptr:ty),*) => {$(
+        impl<T: Comms + ?Sized> Comms for $ptr {
+            type Error = T::Error;
+
+            fn set_cs(&self, state: CsState) -> Result<(), Self::Error> {
+                (**self).set_cs(state)
+            }
+            fn set_a(&self, state: PinState) -> Result<(), Self::Error> {
+                (**self).set_a(state)
+            }
+            fn set_b(&self, state: PinState) -> Result<(), Self::Error> {
+                (**self).set_b(state)
+            }
+            fn cs(&self) -> Result<CsState, Self::Error> {
+                (**self).cs()
+            }
+            fn cs_active_high(&self) -> bool {
+                (**self).cs_active_high()
+            }
+            fn a(&self) -> Result<PinState, Self::Error> {
+                (**self).a()
+            }
+            fn b(&self) -> Result<PinState, Self::Error> {
+                (**self).b()
+            }
+            fn write(&self, data: &[u8]) -> Result<(), Self::Error> {
+                (**self).write(data)
+            }
+            fn write_u16s(&self, data: &[u16], order: Endianness) -> Result<(), Self::Error> {
+                (**self).write_u16s(data, order)
+            }
+            fn transfer<'w>(&self, data: &'w mut [u8]) -> Result<&'w [u8], Self::Error> {
+                (**self).transfer(data)
+            }
+            fn flush(&self) -> Result<(), Self::Error> {
+                (**self).flush()
+            }
+            fn begin_transaction(&self) -> Result<(), Self::Error> {
+                (**self).begin_transaction()
+            }
+            fn end_transaction(&self) {
+                (**self).end_transaction()
+            }
+        }
+    )*};
+}
+
+forward_comms!(&T);
+#[cfg(feature = "std")]
+forward_comms!(std::rc::Rc<T>, std::sync::Arc<T>);
+
+/// `Unsynchronized` is a wrapper that makes a shared reference to a `Comms`
+/// implementation `Send` regardless of whether the implementation is
+/// `Sync`, for hosts where the program itself guarantees that the HAL
+/// objects sharing an SPIDriver never run concurrently.
+///
+/// Only references can be wrapped, because copying or dropping a reference
+/// never touches the value it refers to. Handles such as `Rc` update a
+/// shared count when cloned or dropped, which would race if the copies were
+/// on different threads.
+///
+/// RTIC tasks of the same priority never preempt one another, for example,
+/// and so the HAL objects from `SPIDriverHAL::split_unsynchronized` can be
+/// moved into the local resources of several such tasks and used there
+/// without entering a critical section.
+///
+/// `Unsynchronized` changes only where the handle may be sent, not how it
+/// accesses the SPIDriver: each request still goes through the wrapped
+/// handle, which for an `SPIDriverHAL` means through its mutex. With the
+/// default `RefCell` that costs only a check of the borrow flag, which
+/// still reports reentrant use as `Error::BusBusy`.
+pub struct Unsynchronized<SD>(SD);
+
+impl<'a, T> Unsynchronized<&'a T>
+where
+    T: Comms + ?Sized,
+{
+    /// `new` wraps the given reference.
+    ///
+    /// # Safety
+    ///
+    /// The caller must ensure that, for as long as any copy of the result
+    /// exists, no two calls into the `Comms` implementation that `sd` refers
+    /// to ever overlap, even if the HAL objects holding the copies are moved
+    /// to other threads or execution contexts. That implementation,
+    /// including the serial transport of an `SPIDriverHAL`, must also be
+    /// safe to use from each of those contexts in turn.
+    pub unsafe fn new(sd: &'a T) -> Self {
+        Self(sd)
+    }
+
+    /// `into_inner` returns the wrapped reference.
+    pub fn into_inner(self) -> &'a T {
+        self.0
+    }
+}
+
+impl<SD: Clone> Clone for Unsynchronized<SD> {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+// Safety: Unsynchronized::new requires the caller to uphold the guarantees
+// that Send would otherwise provide for the calls through the reference,
+// and copying or dropping the reference itself has no effect on T.
+unsafe impl<T: ?Sized> Send for Unsynchronized<&T> {}
+
+impl<SD: Comms> Comms for Unsynchronized<SD> {
+    type Error = SD::Error;
+
+    fn set_cs(&self, state: CsState) -> Result<(), Self::Error> {
+        self.0.set_cs(state)
+    }
+    fn set_a(&self, state: PinState) -> Result<(), Self::Error> {
+        self.0.set_a(state)
+    }
+    fn set_b(&self, state: PinState) -> Result<(), Self::Error> {
+        self.0.set_b(state)
+    }
+    fn cs(&self) -> Result<CsState, Self::Error> {
+        self.0.cs()
+    }
+    fn cs_active_high(&self) -> bool {
+        self.0.cs_active_high()
+    }
+    fn a(&self) -> Result<PinState, Self::Error> {
+        self.0.a()
+    }
+    fn b(&self) -> Result<PinState, Self::Error> {
+        self.0.b()
+    }
+    fn write(&self, data: &[u8]) -> Result<(), Self::Error> {
+        self.0.write(data)
+    }
+    fn write_u16s(&self, data: &[u16], order: Endianness) -> Result<(), Self::Error> {
+        self.0.write_u16s(data, order)
+    }
+    fn transfer<'w>(&self, data: &'w mut [u8]) -> Result<&'w [u8], Self::Error> {
+        self.0.transfer(data)
+    }
+    fn flush(&self) -> Result<(), Self::Error> {
+        self.0.flush()
+    }
+    fn begin_transaction(&self) -> Result<(), Self::Error> {
+        self.0.begin_transaction()
+    }
+    fn end_transaction(&self) {
+        self.0.end_transaction()
+    }
 }
 
 /// `Parts` is a container for the various parts of a SPIDriver that can be
 /// used separately via distinct HAL traits.
 ///
 /// The HAL objects inside a particular `Parts` all share a single underlying
-/// communications channel, so it is not possible to access them concurrently
-/// on multiple threads. Instead, coordinate all interactions with a single
-/// SPIDriver on a single thread.
-pub struct Parts<'a, SD: 'a>
+/// communications channel, each through its own copy of the `Comms` handle
+/// `SD`. When that handle is a reference, as with `SPIDriverHAL::split`, the
+/// HAL objects borrow the channel. When it is an `Rc`, as with
+/// `SPIDriverHAL::into_parts`, they own it jointly and so can be stored
+/// anywhere without lifetime constraints.
+///
+/// The HAL objects are `Send` only if `SD` is, which requires a `Comms`
+/// implementation that synchronizes access to the device. For an
+/// `SPIDriverHAL`, that depends on its mutex, as described in the `mutex`
+/// module. Alternatively, a program that guarantees that the HAL objects
+/// never run concurrently can use an `Unsynchronized` handle.
+///
+/// The `StatefulOutputPin` implementations report the level most recently
+/// set through any of the HAL objects. Until a pin's level has been set,
+/// they learn it by requesting a status report from the SPIDriver.
+pub struct Parts<SD>
 where
     SD: Comms,
 {
-    /// `spi` is an implementation of the blocking SPI `Write` and `Transfer`
-    /// traits with an 8-bit word size.
-    pub spi: SPI<'a, SD>,
+    /// `spi` is an implementation of the SPI traits. When the `eh0_2`
+    /// feature is enabled, those are the blocking SPI `Write`, `WriteIter`,
+    /// `Transfer`, and `Transactional` traits and the non-blocking SPI
+    /// `FullDuplex` trait, all with an 8-bit word size, and the blocking
+    /// SPI `Write` and `Transfer` traits with a 16-bit word size. When the
+    /// `eh1` feature is enabled, it also implements the embedded-hal 1.0
+    /// `SpiBus` trait.
+    ///
+    /// Words sent using `FullDuplex` are transferred only once they are
+    /// read, so don't mix `FullDuplex` with the blocking traits while any
+    /// responses remain unread.
+    pub spi: SPI<SD>,
 
-    /// `cs` is an implementation of the digital I/O `OutputPin` trait that
-    /// controls the SPIDriver's Chip Select pin.
+    /// `cs` is an implementation of the digital I/O `OutputPin`,
+    /// `StatefulOutputPin`, and `ToggleableOutputPin` traits that controls
+    /// the SPIDriver's Chip Select pin.
+    ///
+    /// When the `eh1` feature is enabled, it also implements the
+    /// embedded-hal 1.0 `OutputPin` and `StatefulOutputPin` traits.
     ///
     /// Setting this pin to low is implemented as "select" on the SPIDriver and
     /// setting it to high is implemented as "unselect", for consistency with
-    /// the way driver crates tend to expect a CS pin to behave.
-    pub cs: CS<'a, SD>,
+    /// the way driver crates tend to expect a CS pin to behave. If the chip
+    /// select signal is treated as active high, using
+    /// `SPIDriverHAL::set_cs_active_high`, then setting this pin to high
+    /// selects the target device instead.
+    pub cs: CS<SD>,
+
+    /// `pin_a` is an implementation of the digital I/O `OutputPin`,
+    /// `StatefulOutputPin`, and `ToggleableOutputPin` traits that controls
+    /// the SPIDriver's auxillary output pin "A".
+    ///
+    /// When the `eh1` feature is enabled, it also implements the
+    /// embedded-hal 1.0 `OutputPin` and `StatefulOutputPin` traits.
+    pub pin_a: PinA<SD>,
 
-    /// `pin_a` is an implementation of the digital I/O `OutputPin` trait that
-    /// controls the SPIDriver's auxillary output pin "A".
-    pub pin_a: PinA<'a, SD>,
+    /// `pin_b` is an implementation of the digital I/O `OutputPin`,
+    /// `StatefulOutputPin`, and `ToggleableOutputPin` traits that controls
+    /// the SPIDriver's auxillary output pin "B".
+    ///
+    /// When the `eh1` feature is enabled, it also implements the
+    /// embedded-hal 1.0 `OutputPin` and `StatefulOutputPin` traits.
+    pub pin_b: PinB<SD>,
 
-    /// `pin_a` is an implementation of the digital I/O `OutputPin` trait that
-    /// controls the SPIDriver's auxillary output pin "B".
-    pub pin_b: PinB<'a, SD>,
+    /// `delay` is an implementation of the delay traits, for drivers that
+    /// need to wait between operations: the blocking delay `DelayMs` and
+    /// `DelayUs` traits when the `eh0_2` feature is enabled, and the
+    /// embedded-hal 1.0 `DelayNs` trait when the `eh1` feature is enabled.
+    ///
+    /// The delay is measured on the host, and so is only a lower bound on
+    /// the delay between the operations on the SPI bus.
+    ///
+    /// `delay` is `None` if no clock was set using
+    /// `SPIDriverHAL::set_clock` and the `std` feature is disabled, because
+    /// there is then no way to measure time.
+    pub delay: Option<Delay>,
 }
 
-impl<'a, SD: 'a> Parts<'a, SD>
+impl<SD> Parts<SD>
 where
-    SD: Comms,
+    SD: Comms + Clone,
 {
-    pub(crate) fn new(sd: &'a SD) -> Self {
+    /// `new` derives a set of distinct HAL objects from the given `Comms`
+    /// implementation, using the given delay, if any, as the `delay` part.
+    ///
+    /// `SPIDriverHAL::split` calls `new` with a reference to the
+    /// `SPIDriverHAL`. Other implementations of `Comms` can call it directly.
+    pub fn new(sd: SD, delay: Option<Delay>) -> Self {
         Self {
-            spi: SPI::new(&sd),
-            cs: CS::new(&sd),
-            pin_a: PinA::new(&sd),
-            pin_b: PinB::new(&sd),
+            spi: SPI::new(sd.clone()),
+            cs: CS::new(sd.clone()),
+            pin_a: PinA::new(sd.clone()),
+            pin_b: PinB::new(sd),
+            delay,
         }
     }
 }
 
 /// `SPI` implements some of the SPI-related traits from `embedded-hal` in terms
 /// of an SPIDriver device.
-pub struct SPI<'a, SD: Comms>(&'a SD);
+///
+/// The traits are implemented for both 8-bit and 16-bit words. The bytes of
+/// each 16-bit word are sent most significant first unless changed using
+/// `set_word_order`.
+///
+/// `SPI` can be cloned whenever its `Comms` handle can, which is cheap for
+/// the handles returned by `SPIDriverHAL::split` and `into_parts`. That
+/// allows several drivers, each selecting its target device in its own way,
+/// to share the SPI bus without sharing a single `SPI` object. Each clone
+/// starts with the word order of the original but can then change its own,
+/// and starts with an empty `FullDuplex` queue. The clones' operations
+/// interleave in the order they are called, so drivers sharing the bus from
+/// different threads should use `SpiDevice` implementations instead, whose
+/// transactions never interleave.
+pub struct SPI<SD: Comms> {
+    pub(crate) sd: SD,
+    #[cfg(feature = "eh0_2")]
+    pub(crate) queue: crate::eh0_2::DuplexQueue,
+    pub(crate) order: Endianness,
+}
 
-impl<'a, SD: 'a> SPI<'a, SD>
+impl<SD> SPI<SD>
 where
     SD: Comms,
 {
-    fn new(sd: &'a SD) -> Self {
-        Self(sd)
+    fn new(sd: SD) -> Self {
+        Self {
+            sd,
+            #[cfg(feature = "eh0_2")]
+            queue: crate::eh0_2::DuplexQueue::new(),
+            order: Endianness::Big,
+        }
     }
-}
 
-impl<'a, SD: 'a, E> spi::Transfer<u8> for SPI<'a, SD>
-where
-    SD: Comms<Error = E>,
-{
-    type Error = E;
-
-    /// Implements blocking SPI `Transfer` by passing the given data to the
-    /// SPIDriver in chunks of up to 64 bytes each.
-    ///
-    /// Because of the chunking behavior, larger messages may have inconsistent
-    /// timing at the chunk boundaries, which may affect devices with particularly
-    /// sensitive clock timing constraints.
-    fn transfer<'w>(&mut self, data: &'w mut [u8]) -> Result<&'w [u8], E> {
-        self.0.transfer(data)
+    /// `set_word_order` selects the order in which the bytes of each 16-bit
+    /// word are sent and received.
+    pub fn set_word_order(&mut self, order: Endianness) {
+        self.order = order;
     }
 }
 
-impl<'a, SD: 'a, E> spi::Write<u8> for SPI<'a, SD>
+impl<SD> Clone for SPI<SD>
 where
-    SD: Comms<Error = E>,
+    SD: Comms + Clone,
 {
-    type Error = E;
-
-    /// Implements blocking SPI `Write` by passing the given data to the
-    /// SPIDriver in chunks of up to 64 bytes each.
-    ///
-    /// Because of the chunking behavior, larger messages may have inconsistent
-    /// timing at the chunk boundaries, which may affect devices with particularly
-    /// sensitive clock timing constraints.
-    fn write(&mut self, data: &[u8]) -> Result<(), E> {
-        self.0.write(data)
+    fn clone(&self) -> Self {
+        Self {
+            order: self.order,
+            ..Self::new(self.sd.clone())
+        }
     }
 }
 
 /// `CS` implements some of the digital IO traits from `embedded-hal` in
 /// terms of an SPIDriver device's Chip Select pin.
-pub struct CS<'a, SD: Comms>(&'a SD);
+pub struct CS<SD: Comms>(pub(crate) SD);
 
-impl<'a, SD: 'a> CS<'a, SD>
+impl<SD> CS<SD>
 where
     SD: Comms,
 {
-    fn new(sd: &'a SD) -> Self {
+    fn new(sd: SD) -> Self {
         Self(sd)
     }
-}
-
-impl<'a, SD: 'a, E> gpiov2::OutputPin for CS<'a, SD>
-where
-    SD: Comms<Error = E>,
-{
-    type Error = E;
-
-    fn set_low(&mut self) -> Result<(), E> {
-        self.0.set_cs(false)
-    }
 
-    fn set_high(&mut self) -> Result<(), E> {
-        self.0.set_cs(true)
+    // state_for returns the state of the chip select signal that corresponds
+    // to the given level of the pin, given the signal's polarity.
+    pub(crate) fn state_for(&self, level: PinState) -> CsState {
+        CsState::from((level == PinState::High) == self.0.cs_active_high())
     }
 }
 
 /// `PinA` implements some of the digital IO traits from `embedded-hal` in
 /// terms of an SPIDriver device's auxillary output pin A.
-pub struct PinA<'a, SD: Comms>(&'a SD);
+pub struct PinA<SD: Comms>(pub(crate) SD);
 
-impl<'a, SD: 'a> PinA<'a, SD>
+impl<SD> PinA<SD>
 where
     SD: Comms,
 {
-    fn new(sd: &'a SD) -> Self {
+    fn new(sd: SD) -> Self {
         Self(sd)
     }
 }
 
-impl<'a, SD: 'a, E> gpiov2::OutputPin for PinA<'a, SD>
+/// `PinB` implements some of the digital IO traits from `embedded-hal` in
+/// terms of an SPIDriver device's auxillary output pin B.
+pub struct PinB<SD: Comms>(pub(crate) SD);
+
+impl<SD> PinB<SD>
 where
-    SD: Comms<Error = E>,
+    SD: Comms,
 {
-    type Error = E;
+    pub(crate) fn new(sd: SD) -> Self {
+        Self(sd)
+    }
+}
 
-    fn set_low(&mut self) -> Result<(), E> {
-        self.0.set_a(false)
+/// `LegacyPin` adapts one of the output pin parts, such as `CS`, to the
+/// deprecated v1 digital I/O `OutputPin` trait from embedded-hal 0.2, for
+/// use with older drivers that still require it.
+///
+/// The v1 trait has no way to report errors, so if setting the level of the
+/// pin fails then `LegacyPin` panics or, when the `log` feature is enabled,
+/// logs the error and carries on.
+///
+/// This type is available only when the `digital-v1` feature is enabled.
+#[cfg(feature = "digital-v1")]
+#[derive(Debug)]
+pub struct LegacyPin<P>(pub(crate) P);
+
+#[cfg(feature = "digital-v1")]
+impl<P> LegacyPin<P> {
+    /// `new` wraps the given pin.
+    pub fn new(pin: P) -> Self {
+        Self(pin)
     }
 
-    fn set_high(&mut self) -> Result<(), E> {
-        self.0.set_a(true)
+    /// `into_inner` returns the wrapped pin.
+    pub fn into_inner(self) -> P {
+        self.0
     }
 }
 
-/// `PinB` implements some of the digital IO traits from `embedded-hal` in
-/// terms of an SPIDriver device's auxillary output pin B.
-pub struct PinB<'a, SD: Comms>(&'a SD);
+/// `Delay` implements the blocking delay traits from `embedded-hal` using a
+/// clock on the host.
+///
+/// A `Delay` created with `new` polls the given clock until the delay has
+/// elapsed, while one created with `sleeping` puts the current thread to
+/// sleep. Without the standard library, a `Delay` can only be created from
+/// a clock, because there is otherwise no way to measure time.
+#[derive(Clone, Copy)]
+pub struct Delay {
+    source: DelaySource,
+}
 
-impl<'a, SD: 'a> PinB<'a, SD>
-where
-    SD: Comms,
-{
-    fn new(sd: &'a SD) -> Self {
-        Self(sd)
+// DelaySource is how a Delay measures time.
+#[derive(Clone, Copy)]
+enum DelaySource {
+    Clock(&'static (dyn Clock + Sync)),
+    #[cfg(feature = "std")]
+    Sleep,
+}
+
+impl Delay {
+    /// `new` creates a delay that polls the given clock.
+    pub fn new(clock: &'static (dyn Clock + Sync)) -> Self {
+        Self {
+            source: DelaySource::Clock(clock),
+        }
+    }
+
+    /// `sleeping` creates a delay that puts the current thread to sleep
+    /// using the standard library.
+    ///
+    /// This method is available only when the `std` feature is enabled.
+    #[cfg(feature = "std")]
+    pub fn sleeping() -> Self {
+        Self {
+            source: DelaySource::Sleep,
+        }
+    }
+
+    // for_clock returns the delay that the HAL objects use given the clock
+    // set using SPIDriverHAL::set_clock, if any, or None if there's no way
+    // to measure time.
+    pub(crate) fn for_clock(clock: Option<&'static (dyn Clock + Sync)>) -> Option<Self> {
+        match clock {
+            Some(clock) => Some(Self::new(clock)),
+            #[cfg(feature = "std")]
+            None => Some(Self::sleeping()),
+            #[cfg(not(feature = "std"))]
+            None => None,
+        }
+    }
+
+    // wait_us waits for at least the given number of microseconds.
+    pub(crate) fn wait_us(&self, us: u64) {
+        match self.source {
+            DelaySource::Clock(clock) => {
+                let start = clock.now_micros();
+                while clock.now_micros().wrapping_sub(start) < us {
+                    core::hint::spin_loop();
+                }
+            }
+            #[cfg(feature = "std")]
+            DelaySource::Sleep => std::thread::sleep(core::time::Duration::from_micros(us)),
+        }
     }
 }
 
-impl<'a, SD: 'a, E> gpiov2::OutputPin for PinB<'a, SD>
-where
-    SD: Comms<Error = E>,
-{
-    type Error = E;
+impl core::fmt::Debug for Delay {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("Delay")
+            .field("clock", &matches!(self.source, DelaySource::Clock(_)))
+            .finish()
+    }
+}
+
+#[cfg(all(test, any(feature = "eh0_2", feature = "eh1")))]
+pub(crate) mod tests {
+    extern crate std;
+
+    use super::*;
+    use core::cell::RefCell;
+    use core::convert::Infallible;
+    #[cfg(feature = "eh0_2")]
+    use embedded_hal::digital::v2::OutputPin;
+    #[cfg(not(feature = "eh0_2"))]
+    use embedded_hal_1::digital::OutputPin;
+    use std::vec::Vec;
+
+    // Event is a request made of a Recorder.
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub(crate) enum Event {
+        Cs(CsState),
+        Write(Vec<u8>),
+        Transfer(Vec<u8>),
+        Flush,
+    }
+
+    // Recorder is a Comms implementation that records each request made of
+    // it, in order, rather than communicating with a device. Transfers
+    // return the data that was sent.
+    #[derive(Default)]
+    pub(crate) struct Recorder {
+        pub(crate) active_high: bool,
+        events: RefCell<Vec<Event>>,
+    }
+
+    impl Recorder {
+        pub(crate) fn take_events(&self) -> Vec<Event> {
+            self.events.take()
+        }
+
+        fn record(&self, event: Event) {
+            self.events.borrow_mut().push(event);
+        }
+    }
+
+    impl Comms for Recorder {
+        type Error = Infallible;
+
+        fn set_cs(&self, state: CsState) -> Result<(), Infallible> {
+            self.record(Event::Cs(state));
+            Ok(())
+        }
+
+        fn set_a(&self, _: PinState) -> Result<(), Infallible> {
+            Ok(())
+        }
+
+        fn set_b(&self, _: PinState) -> Result<(), Infallible> {
+            Ok(())
+        }
+
+        fn cs(&self) -> Result<CsState, Infallible> {
+            let events = self.events.borrow();
+            let last = events.iter().rev().find_map(|event| match event {
+                Event::Cs(state) => Some(*state),
+                _ => None,
+            });
+            Ok(last.unwrap_or(CsState::Released))
+        }
 
-    fn set_low(&mut self) -> Result<(), E> {
-        self.0.set_b(false)
+        fn cs_active_high(&self) -> bool {
+            self.active_high
+        }
+
+        fn a(&self) -> Result<PinState, Infallible> {
+            Ok(PinState::High)
+        }
+
+        fn b(&self) -> Result<PinState, Infallible> {
+            Ok(PinState::High)
+        }
+
+        fn write(&self, data: &[u8]) -> Result<(), Infallible> {
+            self.record(Event::Write(data.to_vec()));
+            Ok(())
+        }
+
+        fn transfer<'w>(&self, data: &'w mut [u8]) -> Result<&'w [u8], Infallible> {
+            self.record(Event::Transfer(data.to_vec()));
+            Ok(data)
+        }
+
+        fn flush(&self) -> Result<(), Infallible> {
+            self.record(Event::Flush);
+            Ok(())
+        }
     }
 
-    fn set_high(&mut self) -> Result<(), E> {
-        self.0.set_b(true)
+    #[test]
+    fn cs_low_selects() {
+        let sd = Recorder::default();
+        let mut parts = Parts::new(&sd, None);
+        parts.cs.set_low().unwrap();
+        parts.cs.set_high().unwrap();
+        assert_eq!(
+            sd.take_events(),
+            [Event::Cs(CsState::Asserted), Event::Cs(CsState::Released)]
+        );
+    }
+
+    #[test]
+    fn cs_high_selects_when_active_high() {
+        let sd = Recorder {
+            active_high: true,
+            ..Recorder::default()
+        };
+        let mut parts = Parts::new(&sd, None);
+        parts.cs.set_high().unwrap();
+        parts.cs.set_low().unwrap();
+        assert_eq!(
+            sd.take_events(),
+            [Event::Cs(CsState::Asserted), Event::Cs(CsState::Released)]
+        );
+    }
+
+    // Recorder transfers return the data that was sent, so a split transfer
+    // should read back the write buffer and then the 0xff padding.
+    #[cfg(feature = "eh1")]
+    #[test]
+    fn transfer_read_longer_than_write() {
+        use embedded_hal_1::spi::SpiBus;
+
+        let sd = Recorder::default();
+        let mut parts = Parts::new(&sd, None);
+        let write: Vec<u8> = (0..40).collect();
+        let mut read = [0_u8; 100];
+        parts.spi.transfer(&mut read, &write).unwrap();
+        assert_eq!(read[..40], write[..]);
+        assert!(read[40..].iter().all(|b| *b == 0xff));
+        assert_eq!(
+            sd.take_events(),
+            [Event::Transfer(write), Event::Transfer([0xff; 60].to_vec())]
+        );
+    }
+
+    #[cfg(feature = "eh1")]
+    #[test]
+    fn transfer_write_longer_than_read() {
+        use embedded_hal_1::spi::SpiBus;
+
+        let sd = Recorder::default();
+        let mut parts = Parts::new(&sd, None);
+        let write: Vec<u8> = (0..100).collect();
+        let mut read = [0_u8; 40];
+        parts.spi.transfer(&mut read, &write).unwrap();
+        assert_eq!(read[..], write[..40]);
+        assert_eq!(
+            sd.take_events(),
+            [
+                Event::Transfer(write[..40].to_vec()),
+                Event::Write(write[40..].to_vec())
+            ]
+        );
+    }
+
+    #[cfg(feature = "eh1")]
+    #[test]
+    fn transfer_same_length() {
+        use embedded_hal_1::spi::SpiBus;
+
+        let sd = Recorder::default();
+        let mut parts = Parts::new(&sd, None);
+        let write: Vec<u8> = (0..100).collect();
+        let mut read = [0_u8; 100];
+        parts.spi.transfer(&mut read, &write).unwrap();
+        assert_eq!(read[..], write[..]);
+        assert_eq!(sd.take_events(), [Event::Transfer(write)]);
     }
 }
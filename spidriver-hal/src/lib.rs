@@ -5,38 +5,159 @@
 //! driver crates that are written in terms of those traits to control their
 //! corresponding devices via an SPIDriver module.
 //!
-//! Specifically, this library provides:
-//! - Implementations of the blocking SPI `Write` and `Transfer` traits that
-//!   transmit data via the SPIDriver.
-//! - An implementation of the v2 Digital IO `OutputPin` trait for the chip
-//!   select output of the SPIDriver.
-//! - Implementations of the v2 Digital IO `OutputPin` trait for the auxillary
-//!   output pins A and B on the SPIDriver.
+//! Specifically, when the `eh0_2` feature is enabled, as it is by default,
+//! this library provides:
+//! - Implementations of the blocking SPI `Write`, `WriteIter`, `Transfer`,
+//!   and `Transactional` traits that transmit data via the SPIDriver, and
+//!   of the non-blocking SPI `FullDuplex` trait. `Write` and `Transfer` are
+//!   also implemented for 16-bit words, in either byte order.
+//! - Implementations of the v2 Digital IO `OutputPin`, `StatefulOutputPin`,
+//!   and `ToggleableOutputPin` traits for the chip select output of the
+//!   SPIDriver and for the auxillary output pins A and B.
+//! - Implementations of the blocking `DelayMs` and `DelayUs` traits,
+//!   measured on the host using a `Clock` or, when the `std` feature is
+//!   enabled, the standard library.
+//! - When the `digital-v1` feature is also enabled, `hal::LegacyPin`, which
+//!   adapts the output pins to the deprecated v1 `OutputPin` trait.
+//!
+//! When the `eh1` feature is enabled, this library also provides:
+//! - An implementation of the embedded-hal 1.0 `SpiBus` trait on the SPI
+//!   part, and of the `SpiDevice` trait using the SPIDriver's own chip
+//!   select signal, obtained using `SPIDriverHAL::device`.
+//! - A pair of `SpiDevice` implementations for two target devices sharing
+//!   the SPI bus, one selected by the SPIDriver's chip select signal and
+//!   the other by auxillary output pin A, obtained using
+//!   `SPIDriverHAL::split_devices`.
+//! - Implementations of the digital `OutputPin` and `StatefulOutputPin`
+//!   traits for the chip select output and the auxillary output pins.
+//! - An implementation of the `DelayNs` trait on the delay part.
+//!
+//! When the `async` feature is enabled, the `asynch` module also provides
+//! implementations of the `embedded-hal-async` `SpiDevice` trait for target
+//! devices sharing an `AsyncSPIDriver` from the `spidriver` crate, through
+//! an asynchronous mutex from either the `embassy-sync` or `tokio` crates
+//! when the feature of the same name is enabled.
+//!
+//! The two features can be enabled together, in which case the same HAL
+//! objects implement both generations of the traits. That allows a program
+//! to use drivers written for either generation with the same SPIDriver,
+//! such as while those drivers are migrated from one to the other.
 //!
 //! To use it, first instantiate and configure an `SPIDriver` object from the
 //! `spidriver` crate, and then pass it to `SPIDriverHAL::new` before calling
 //! `split` to obtain the individual interface objects:
 //!
-//! ```rust
-//! let sd = SPIDriver::new(rx, tx); // rx and tx obtained from some underlying platform crate
-//! let parts = SPIDriverHAL::new(sd).split();
+//! ```rust,no_run
+//! # use spidriver::{CsState, PinState, SPIDriver};
+//! # use spidriver_hal::SPIDriverHAL;
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! # use serial_embedded_hal::{PortSettings, Serial};
+//! # let port = Serial::new(
+//! #     "/dev/ttyUSB0",
+//! #     &PortSettings {
+//! #         baud_rate: serial_embedded_hal::BaudRate::BaudOther(460800),
+//! #         char_size: serial_embedded_hal::CharSize::Bits8,
+//! #         parity: serial_embedded_hal::Parity::ParityNone,
+//! #         stop_bits: serial_embedded_hal::StopBits::Stop1,
+//! #         flow_control: serial_embedded_hal::FlowControl::FlowNone,
+//! #     },
+//! # )?;
+//! # let (tx, rx) = port.split();
+//! let sd = SPIDriver::new(tx, rx); // tx and rx obtained from some underlying platform crate
+//! let sdh = SPIDriverHAL::new(sd);
+//! let parts = sdh.split();
+//! # Ok(())
+//! # }
 //! ```
+//!
+//! The HAL objects communicate with the SPIDriver through the `hal::Comms`
+//! trait, which `SPIDriverHAL` implements. To use the HAL objects with
+//! some other transport, such as a simulator, implement `hal::Comms` for it
+//! and then pass it to `hal::Parts::new`.
+//!
+//! If communication with the SPIDriver fails, the HAL objects from an
+//! `SPIDriverHAL` share a record of how the failure left the host and the
+//! SPIDriver out of step, and refuse only the operations it could cause to
+//! go wrong, as described for `BusFault`. Use `SPIDriverHAL::fault` to
+//! query it and `SPIDriverHAL::resync` to recover.
+//!
+//! The HAL objects obtained from an `SPIDriverHAL` share the wrapped
+//! `SPIDriver` through a mutex, which is a `RefCell` by default. The
+//! `mutex` module describes how to select another kind of mutex, such as
+//! `std::sync::Mutex` to share the HAL objects between threads when the
+//! `std` feature is enabled, or one based on the `critical-section` crate
+//! when the `critical-section` feature is enabled.
+//!
+//! The SPI part implements the traits that the proxies from the
+//! `shared-bus` crate require of the bus they share, and so several drivers
+//! can share it through a bus manager while using the other parts as their
+//! chip select pins. For example, to share an SPIDriver between an SSD1322
+//! display controller selected by the SPIDriver's chip select signal, with
+//! pin A as its data/command signal, and an SPI flash memory selected by
+//! pin B, where `SSD1322` and `SpiFlash` stand for their drivers:
+//!
+//! ```rust,ignore
+//! let parts = sdh.split();
+//! let bus = shared_bus::BusManagerSimple::new(parts.spi);
+//! let mut display = SSD1322::new(bus.acquire_spi(), parts.cs, parts.pin_a)?;
+//! let mut flash = SpiFlash::new(bus.acquire_spi(), parts.pin_b)?;
+//! ```
+//!
+//! To share the bus between threads using `shared_bus::BusManagerStd`
+//! instead, use `std::sync::Mutex` as described above and obtain the parts
+//! using `SPIDriverHAL::into_shared_parts`, whose SPI part is then `Send`
+//! whenever the serial implementations are. The `shared-bus` example
+//! contains the code above along with minimal versions of both drivers,
+//! and also checks at compile time that the SPI part meets the
+//! requirements of both bus managers.
 
-#![no_std]
+#![cfg_attr(not(feature = "std"), no_std)]
 
 extern crate embedded_hal;
 
+#[cfg(not(any(feature = "eh0_2", feature = "eh1")))]
+compile_error!("spidriver-hal requires at least one of the `eh0_2` and `eh1` features");
+
+#[cfg(feature = "async")]
+pub mod asynch;
+#[cfg(feature = "eh0_2")]
+mod eh0_2;
+#[cfg(feature = "eh1")]
+pub mod eh1;
+mod error;
 pub mod hal;
+pub mod mutex;
+pub mod prelude;
 
-use spidriver::SPIDriver;
+use core::cell::RefCell;
+use core::marker::PhantomData;
+use spidriver::{Clock, CsState, DeviceStatus, Endianness, PinState, SPIDriver};
 
-use hal::{Comms, Parts};
+use hal::{Comms, Delay, Parts, Unsynchronized};
+use mutex::BusMutex;
+
+pub use error::{BusFault, Error};
 
 /// `SPIDriverHAL` is the entry point for this library.
+///
+/// The HAL objects share the wrapped `SPIDriver`, protected by a mutex of
+/// type `M`. The default `RefCell` allows them to be used only on a single
+/// thread; use `with_mutex` to select another kind of mutex.
+///
+/// `SPIDriverHAL` is `Send` whenever the serial implementations it wraps
+/// are, and so can be moved into a worker thread before being split there.
+/// With the default mutex, the HAL objects obtained from it share it without
+/// synchronization, and so aren't `Send` themselves.
 pub struct SPIDriverHAL<
     UARTTX: embedded_hal::serial::Write<u8>,
     UARTRX: embedded_hal::serial::Read<u8>,
->(core::cell::RefCell<SD<UARTTX, UARTRX>>);
+    M = RefCell<Bus<UARTTX, UARTRX>>,
+> {
+    bus: M,
+    clock: Option<&'static (dyn Clock + Sync)>,
+    cs_active_high: bool,
+    serial: PhantomData<fn() -> (UARTTX, UARTRX)>,
+}
 
 impl<TX, RX> SPIDriverHAL<TX, RX>
 where
@@ -46,81 +167,715 @@ where
     /// `new` consumes an `SPIDriver` object and binds it to a HAL container.
     ///
     /// The variable recieving the result of `new` establishes the lifetime
-    /// for all of the individual HAL objects derived from it.
+    /// for all of the individual HAL objects derived from it using `split`.
     ///
     /// The next step after calling `new` and saving its result in a variable
-    /// is to call` split` on that stored result.
+    /// is to call` split` on that stored result, or to call `into_parts`
+    /// to obtain HAL objects that own it.
     pub fn new(sd: SPIDriver<TX, RX>) -> Self {
-        let dev = SD(sd);
-        Self(core::cell::RefCell::new(dev))
+        Self::with_mutex(sd)
+    }
+}
+
+impl<TX, RX, M> SPIDriverHAL<TX, RX, M>
+where
+    TX: embedded_hal::serial::Write<u8>,
+    RX: embedded_hal::serial::Read<u8>,
+    M: BusMutex<Bus = Bus<TX, RX>>,
+{
+    /// `with_mutex` is a variant of `new` that protects the wrapped
+    /// `SPIDriver` using a mutex of type `M`, which is usually specified
+    /// with a turbofish:
+    ///
+    /// ```rust,ignore
+    /// let sdh = SPIDriverHAL::<_, _, SomeMutex<_>>::with_mutex(sd);
+    /// ```
+    pub fn with_mutex(sd: SPIDriver<TX, RX>) -> Self {
+        let bus = Bus {
+            sd,
+            levels: Levels::default(),
+            busy: false,
+            #[cfg(feature = "std")]
+            owner: None,
+            #[cfg(feature = "std")]
+            waiting: Vec::new(),
+            fault: None,
+        };
+        Self {
+            bus: M::create(bus),
+            clock: None,
+            cs_active_high: false,
+            serial: PhantomData,
+        }
+    }
+
+    /// `set_clock` sets the clock that the `delay` part returned by `split`
+    /// uses to measure its delays, replacing any clock set previously.
+    ///
+    /// Without a clock, the `delay` part sleeps using the standard library
+    /// if the `std` feature is enabled, and is otherwise absent.
+    pub fn set_clock(&mut self, clock: &'static (dyn Clock + Sync)) {
+        self.clock = Some(clock);
+    }
+
+    /// `set_cs_active_high` selects whether the chip select signal is treated
+    /// as active high, for target devices that are selected by driving it
+    /// high rather than low.
+    ///
+    /// When enabled, the HAL objects drive the SPIDriver's chip select
+    /// signal high to select the target device, and so drivers can use them
+    /// without inverting the signal themselves. The `cs` part's
+    /// `OutputPin` implementation always sets the level it is asked to,
+    /// which then selects the target device when set high.
+    pub fn set_cs_active_high(&mut self, active_high: bool) {
+        self.cs_active_high = active_high;
     }
 
     /// `split` derives a set of distinct HAL objects representing different
     /// functions of the wrapped `SPIDriver`.
-    pub fn split<'a>(&'a self) -> Parts<'a, Self> {
-        Parts::new(&self)
+    ///
+    /// The HAL objects borrow the `SPIDriverHAL`. Use `into_parts` instead
+    /// to obtain HAL objects that can outlive the variable it is stored in.
+    pub fn split(&self) -> Parts<&Self> {
+        Parts::new(self, Delay::for_clock(self.clock))
+    }
+
+    /// `split_unsynchronized` is a variant of `split` that returns HAL
+    /// objects that are `Send` even with the default `RefCell` mutex, for
+    /// sharing an SPIDriver between execution contexts that never run
+    /// concurrently, such as RTIC tasks of the same priority. The HAL objects
+    /// still access the SPIDriver through its mutex, which for the default
+    /// `RefCell` is only a check of its borrow flag rather than a critical
+    /// section.
+    ///
+    /// The HAL objects borrow the `SPIDriverHAL` in the same way as with
+    /// `split`. Moving them into tasks usually requires them to live for the
+    /// rest of the program, which such programs arrange by storing the
+    /// `SPIDriverHAL` in a `static` cell during initialization.
+    ///
+    /// # Safety
+    ///
+    /// The caller must uphold the requirements of `hal::Unsynchronized::new`
+    /// for the handle to this `SPIDriverHAL`.
+    pub unsafe fn split_unsynchronized(&self) -> Parts<Unsynchronized<&Self>> {
+        Parts::new(Unsynchronized::new(self), Delay::for_clock(self.clock))
     }
 
-    pub(crate) fn with_mut_sd<R>(&self, f: impl FnOnce(&mut SD<TX, RX>) -> R) -> R {
-        let mut sd = self.0.borrow_mut();
-        f(&mut *sd)
+    /// `into_parts` is a variant of `split` that consumes the
+    /// `SPIDriverHAL`, returning HAL objects that own it jointly through
+    /// an `Rc`.
+    ///
+    /// The resulting HAL objects have no lifetime constraints, so they can
+    /// be moved into the structs of driver crates and kept for as long as
+    /// necessary. The `SPIDriverHAL` is dropped along with the last of them.
+    ///
+    /// This method is available only when the `std` feature is enabled.
+    #[cfg(feature = "std")]
+    pub fn into_parts(self) -> Parts<std::rc::Rc<Self>> {
+        let delay = Delay::for_clock(self.clock);
+        Parts::new(std::rc::Rc::new(self), delay)
+    }
+
+    /// `into_shared_parts` is a variant of `into_parts` that returns HAL
+    /// objects that own the `SPIDriverHAL` jointly through an `Arc`.
+    ///
+    /// When the `SPIDriverHAL` uses a mutex that allows sharing between
+    /// threads, such as `std::sync::Mutex`, the resulting HAL objects can
+    /// be moved into different threads.
+    ///
+    /// This method is available only when the `std` feature is enabled.
+    #[cfg(feature = "std")]
+    pub fn into_shared_parts(self) -> Parts<std::sync::Arc<Self>> {
+        let delay = Delay::for_clock(self.clock);
+        Parts::new(std::sync::Arc::new(self), delay)
+    }
+
+    /// `device` returns an implementation of the embedded-hal 1.0
+    /// `SpiDevice` trait, which asserts the SPIDriver's chip select signal
+    /// for the duration of each transaction.
+    ///
+    /// `delay` implements any `Operation::DelayNs` in a transaction, and
+    /// can be the `delay` part returned by `split`. The device shares the
+    /// underlying `SPIDriver` with the objects returned by `split`, so don't
+    /// use its `cs` part to control the chip select signal at the same time.
+    ///
+    /// This method is available only when the `eh1` feature is enabled.
+    #[cfg(feature = "eh1")]
+    pub fn device<D>(&self, delay: D) -> eh1::Device<&Self, D> {
+        eh1::Device::new(self, delay)
+    }
+
+    /// `split_devices` is a variant of `split` for an SPI bus shared by two
+    /// target devices, one selected by the SPIDriver's chip select signal
+    /// and the other selected by auxillary output pin A.
+    ///
+    /// It returns an implementation of the embedded-hal 1.0 `SpiDevice`
+    /// trait for each target device, after releasing both chip select
+    /// signals. The transactions of the two devices never interleave.
+    /// Each device uses a copy of `delay` to implement any
+    /// `Operation::DelayNs`, as for `device`.
+    ///
+    /// Don't use the objects returned by `split` or `device` at the same
+    /// time as those returned by `split_devices`, because they could
+    /// change the levels of the chip select signals.
+    ///
+    /// This method is available only when the `eh1` feature is enabled.
+    #[cfg(feature = "eh1")]
+    #[allow(clippy::type_complexity)]
+    pub fn split_devices<D>(
+        &self,
+        delay: D,
+    ) -> Result<eh1::DeviceParts<&Self, D>, Error<TX::Error, RX::Error>>
+    where
+        D: Clone,
+    {
+        eh1::DeviceParts::new(self, delay)
+    }
+
+    /// `fault` returns the fault left by an earlier communication error, if
+    /// any, as described for `BusFault`.
+    pub fn fault(&self) -> Result<Option<BusFault>, Error<TX::Error, RX::Error>> {
+        self.with_bus(|bus| Ok(bus.fault))
+    }
+
+    /// `resync` attempts to bring the host and the SPIDriver back into step
+    /// after a fault, using `SPIDriver::resync`, and clears the fault if it
+    /// succeeds.
+    ///
+    /// Resynchronizing may change the levels of the output pins if a command
+    /// to set one was only partly sent, so the HAL objects afterwards learn
+    /// their levels again by requesting a status report from the SPIDriver.
+    pub fn resync(&self) -> Result<(), Error<TX::Error, RX::Error>> {
+        self.with_bus(|bus| {
+            bus.levels = Levels::default();
+            bus.fault = None;
+            let result = bus.sd.resync();
+            bus.record(result)
+        })
+    }
+
+    /// `clear_fault` clears any fault without communicating with the
+    /// SPIDriver, for when the caller knows that the host and the SPIDriver
+    /// are still in step despite the error, such as after recovering by
+    /// some other means. Otherwise, prefer `resync`.
+    pub fn clear_fault(&self) -> Result<(), Error<TX::Error, RX::Error>> {
+        self.with_bus(|bus| {
+            bus.sd.reset_state();
+            bus.fault = None;
+            Ok(())
+        })
+    }
+
+    pub(crate) fn with_bus<R>(
+        &self,
+        f: impl FnOnce(&mut Bus<TX, RX>) -> Result<R, Error<TX::Error, RX::Error>>,
+    ) -> Result<R, Error<TX::Error, RX::Error>> {
+        self.bus.lock(f)?
+    }
+
+    // device_cs converts between the state of the chip select signal as
+    // requested through the HAL objects and the state of the SPIDriver's own
+    // active-low chip select signal, which are opposites when the signal is
+    // treated as active high.
+    fn device_cs(&self, state: CsState) -> CsState {
+        match (self.cs_active_high, state) {
+            (false, state) => state,
+            (true, CsState::Asserted) => CsState::Released,
+            (true, CsState::Released) => CsState::Asserted,
+        }
     }
 }
 
-pub(crate) struct SD<
-    UARTTX: embedded_hal::serial::Write<u8>,
-    UARTRX: embedded_hal::serial::Read<u8>,
->(SPIDriver<UARTTX, UARTRX>);
+/// `Bus` is the state shared by the HAL objects obtained from an
+/// `SPIDriverHAL`, which a `BusMutex` protects. Its contents are private.
+pub struct Bus<UARTTX: embedded_hal::serial::Write<u8>, UARTRX: embedded_hal::serial::Read<u8>> {
+    sd: SPIDriver<UARTTX, UARTRX>,
+    levels: Levels,
 
-impl<TX, RX, TXErr, RXErr> Comms for SPIDriverHAL<TX, RX>
+    // Whether a transaction is in progress. See Comms::begin_transaction.
+    busy: bool,
+
+    // The thread running the transaction in progress, if any, and the
+    // threads waiting to begin their own transactions once it ends.
+    #[cfg(feature = "std")]
+    owner: Option<std::thread::ThreadId>,
+    #[cfg(feature = "std")]
+    waiting: Vec<std::thread::Thread>,
+
+    // The fault left by the most severe communication error since the
+    // fault was last cleared, if any. See Bus::run.
+    fault: Option<BusFault>,
+}
+
+// Levels records the level most recently set for each of the SPIDriver's
+// output pins, or None if it isn't yet known. The chip select state is as
+// seen by the SPIDriver, regardless of the signal's polarity.
+#[derive(Default)]
+struct Levels {
+    cs: Option<CsState>,
+    a: Option<PinState>,
+    b: Option<PinState>,
+}
+
+impl<TX, RX, TXErr, RXErr> Bus<TX, RX>
 where
     TX: embedded_hal::serial::Write<u8, Error = TXErr>,
     RX: embedded_hal::serial::Read<u8, Error = RXErr>,
 {
-    type Error = spidriver::Error<TXErr, RXErr>;
-
-    fn set_cs(&self, high: bool) -> Result<(), Self::Error> {
-        self.with_mut_sd(|sd| {
-            if high {
-                sd.0.unselect() // SPI is active low, so high means unselected
-            } else {
-                sd.0.select()
+    // learn_levels requests a status report from the device and records
+    // the level of each output pin whose level isn't already known.
+    fn learn_levels(&mut self) -> Result<DeviceStatus, Error<TXErr, RXErr>> {
+        let status = self.run(true, |sd| sd.status())?;
+        self.levels.cs.get_or_insert(status.cs);
+        self.levels.a.get_or_insert(status.a);
+        self.levels.b.get_or_insert(status.b);
+        Ok(status)
+    }
+
+    // run performs an operation on the SPIDriver unless the current fault
+    // forbids it, and records the fault left by the operation if it fails.
+    // Operations that read a response from the SPIDriver must pass true for
+    // needs_response.
+    fn run<T>(
+        &mut self,
+        needs_response: bool,
+        op: impl FnOnce(&mut SPIDriver<TX, RX>) -> Result<T, spidriver::Error<TXErr, RXErr>>,
+    ) -> Result<T, Error<TXErr, RXErr>> {
+        match self.fault {
+            Some(fault @ BusFault::Commands) => return Err(Error::Fault(fault)),
+            Some(fault @ BusFault::Responses) if needs_response => return Err(Error::Fault(fault)),
+            _ => {}
+        }
+        let result = op(&mut self.sd);
+        self.record(result)
+    }
+
+    // begin_transaction marks a transaction as in progress, or returns
+    // false if the caller must wait for the one in progress on another
+    // thread to end, in which case it will be unparked when it does.
+    fn begin_transaction(&mut self) -> Result<bool, Error<TXErr, RXErr>> {
+        if !self.busy {
+            self.busy = true;
+            #[cfg(feature = "std")]
+            {
+                self.owner = Some(std::thread::current().id());
             }
+            return Ok(true);
+        }
+
+        #[cfg(feature = "std")]
+        if self.owner != Some(std::thread::current().id()) {
+            self.waiting.push(std::thread::current());
+            return Ok(false);
+        }
+
+        // Each transaction runs to completion within a single call, so one
+        // can already be in progress on the same thread, or without std on
+        // a different execution context that we can't wait for, only if
+        // one of its operations has re-entered the HAL.
+        Err(Error::BusBusy)
+    }
+
+    fn end_transaction(&mut self) {
+        self.busy = false;
+        #[cfg(feature = "std")]
+        {
+            self.owner = None;
+            for thread in self.waiting.drain(..) {
+                thread.unpark();
+            }
+        }
+    }
+
+    // record updates the fault to reflect the outcome of an operation.
+    fn record<T>(
+        &mut self,
+        result: Result<T, spidriver::Error<TXErr, RXErr>>,
+    ) -> Result<T, Error<TXErr, RXErr>> {
+        let err = match result {
+            Ok(value) => return Ok(value),
+            Err(err) => err,
+        };
+        match BusFault::for_kind(err.kind()) {
+            Some(BusFault::Commands) => self.fault = Some(BusFault::Commands),
+            Some(BusFault::Responses) if self.fault != Some(BusFault::Commands) => {
+                self.fault = Some(BusFault::Responses);
+                // The SPIDriver refuses all operations after an error, but
+                // the HAL objects can still send the commands that expect
+                // no response.
+                self.sd.reset_state();
+            }
+            Some(BusFault::Responses) => {}
+            None => {}
+        }
+        Err(Error::Comms(err))
+    }
+}
+
+impl<TX, RX, M, TXErr, RXErr> Comms for SPIDriverHAL<TX, RX, M>
+where
+    TX: embedded_hal::serial::Write<u8, Error = TXErr>,
+    RX: embedded_hal::serial::Read<u8, Error = RXErr>,
+    M: BusMutex<Bus = Bus<TX, RX>>,
+{
+    type Error = Error<TXErr, RXErr>;
+
+    fn set_cs(&self, state: CsState) -> Result<(), Self::Error> {
+        let state = self.device_cs(state);
+        self.with_bus(|bus| {
+            // If setting the level fails then we can't be sure what level
+            // the pin was left at.
+            bus.levels.cs = None;
+            bus.run(false, |sd| sd.set_cs(state))?;
+            bus.levels.cs = Some(state);
+            Ok(())
+        })
+    }
+
+    fn set_a(&self, state: PinState) -> Result<(), Self::Error> {
+        self.with_bus(|bus| {
+            bus.levels.a = None;
+            bus.run(false, |sd| sd.set_a(state))?;
+            bus.levels.a = Some(state);
+            Ok(())
+        })
+    }
+
+    fn set_b(&self, state: PinState) -> Result<(), Self::Error> {
+        self.with_bus(|bus| {
+            bus.levels.b = None;
+            bus.run(false, |sd| sd.set_b(state))?;
+            bus.levels.b = Some(state);
+            Ok(())
         })
     }
 
-    fn set_a(&self, high: bool) -> Result<(), Self::Error> {
-        self.with_mut_sd(|sd| sd.0.set_a(high))
+    fn cs(&self) -> Result<CsState, Self::Error> {
+        let state = self.with_bus(|bus| match bus.levels.cs {
+            Some(state) => Ok(state),
+            None => Ok(bus.learn_levels()?.cs),
+        })?;
+        Ok(self.device_cs(state))
     }
 
-    fn set_b(&self, high: bool) -> Result<(), Self::Error> {
-        self.with_mut_sd(|sd| sd.0.set_b(high))
+    fn cs_active_high(&self) -> bool {
+        self.cs_active_high
+    }
+
+    fn a(&self) -> Result<PinState, Self::Error> {
+        self.with_bus(|bus| match bus.levels.a {
+            Some(state) => Ok(state),
+            None => Ok(bus.learn_levels()?.a),
+        })
+    }
+
+    fn b(&self) -> Result<PinState, Self::Error> {
+        self.with_bus(|bus| match bus.levels.b {
+            Some(state) => Ok(state),
+            None => Ok(bus.learn_levels()?.b),
+        })
     }
 
     fn write(&self, data: &[u8]) -> Result<(), Self::Error> {
-        self.with_mut_sd(|sd| {
-            let mut remain = data;
-            while remain.len() > 0 {
-                let len: usize = if remain.len() > 64 { 64 } else { remain.len() };
-                let (this, next) = remain.split_at(len);
-                sd.0.write(this)?;
-                remain = next;
+        self.with_bus(|bus| bus.run(false, |sd| sd.write_all(data)))
+    }
+
+    fn write_u16s(&self, data: &[u16], order: Endianness) -> Result<(), Self::Error> {
+        self.with_bus(|bus| bus.run(false, |sd| sd.write_u16s(data, order)))
+    }
+
+    fn transfer<'w>(&self, data: &'w mut [u8]) -> Result<&'w [u8], Self::Error> {
+        self.with_bus(move |bus| bus.run(true, move |sd| sd.transfer_all(data)))
+    }
+
+    fn flush(&self) -> Result<(), Self::Error> {
+        self.with_bus(|bus| bus.run(true, |sd| sd.sync()))
+    }
+
+    fn begin_transaction(&self) -> Result<(), Self::Error> {
+        while !self.with_bus(|bus| bus.begin_transaction())? {
+            // park can return spuriously, in which case we'll just check
+            // again and go back to waiting.
+            #[cfg(feature = "std")]
+            std::thread::park();
+        }
+        Ok(())
+    }
+
+    fn end_transaction(&self) {
+        // If the mutex has been poisoned then there's no transaction state
+        // to clean up, because every later request will fail anyway.
+        let _ = self.with_bus(|bus| {
+            bus.end_transaction();
+            Ok(())
+        });
+    }
+}
+
+// SPIDriverHAL, its errors, and the HAL objects derived from a Comms handle
+// are Send whenever the things they contain are. This function is never
+// called, but it fails to compile if a change to any of those types breaks
+// that.
+#[allow(dead_code)]
+fn assert_send<TX, RX, C>()
+where
+    TX: embedded_hal::serial::Write<u8> + Send,
+    RX: embedded_hal::serial::Read<u8> + Send,
+    TX::Error: Send,
+    RX::Error: Send,
+    C: Comms + Send,
+{
+    fn check<T: Send>() {}
+    check::<SPIDriverHAL<TX, RX>>();
+    check::<Error<TX::Error, RX::Error>>();
+    check::<Parts<C>>();
+    check::<Parts<Unsynchronized<&SPIDriverHAL<TX, RX>>>>();
+    #[cfg(feature = "critical-section")]
+    check::<Parts<&SPIDriverHAL<TX, RX, mutex::CriticalSectionMutex<Bus<TX, RX>>>>>();
+    #[cfg(feature = "std")]
+    check::<Parts<std::sync::Arc<SPIDriverHAL<TX, RX, std::sync::Mutex<Bus<TX, RX>>>>>>();
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use super::*;
+    use spidriver::{Capability, ErrorKind, ProtocolFault, RequestError};
+    use std::collections::VecDeque;
+    use std::sync::{Arc, Mutex};
+    use std::vec::Vec;
+
+    // Device simulates just enough of an SPIDriver for these tests: it
+    // echoes back the argument of each echo command, and its SPI bus is
+    // looped back so that each transfer returns the data that was sent. All
+    // other commands are accepted and ignored. Setting fail_writes or
+    // fail_reads makes the serial writer or reader return errors.
+    #[derive(Default)]
+    struct Device {
+        command: Vec<u8>,
+        responses: VecDeque<u8>,
+        fail_writes: bool,
+        fail_reads: bool,
+    }
+
+    impl Device {
+        fn receive(&mut self, c: u8) {
+            self.command.push(c);
+            let opcode = self.command[0];
+            let arg_len = match opcode {
+                b'e' | b'a' | b'b' => 1,
+                0x80..=0xbf => (opcode - 0x80) as usize + 1,
+                0xc0..=0xff => (opcode - 0xc0) as usize + 1,
+                _ => 0,
+            };
+            if self.command.len() < 1 + arg_len {
+                return;
+            }
+            if let b'e' | 0x80..=0xbf = opcode {
+                self.responses.extend(&self.command[1..]);
             }
+            self.command.clear();
+        }
+    }
+
+    struct Tx(Arc<Mutex<Device>>);
+
+    struct Rx(Arc<Mutex<Device>>);
+
+    impl embedded_hal::serial::Write<u8> for Tx {
+        type Error = ();
+
+        fn write(&mut self, c: u8) -> nb::Result<(), ()> {
+            let mut device = self.0.lock().unwrap();
+            if device.fail_writes {
+                return Err(nb::Error::Other(()));
+            }
+            device.receive(c);
             Ok(())
-        })
+        }
+
+        fn flush(&mut self) -> nb::Result<(), ()> {
+            Ok(())
+        }
     }
 
-    fn transfer<'w>(&self, data: &'w mut [u8]) -> Result<&'w [u8], Self::Error> {
-        self.with_mut_sd(|sd| {
-            let mut remain = &mut data[..];
-            while remain.len() > 0 {
-                let len: usize = if remain.len() > 64 { 64 } else { remain.len() };
-                let (this, next) = remain.split_at_mut(len);
-                sd.0.transfer(this)?;
-                remain = next;
+    impl embedded_hal::serial::Read<u8> for Rx {
+        type Error = ();
+
+        fn read(&mut self) -> nb::Result<u8, ()> {
+            let mut device = self.0.lock().unwrap();
+            if device.fail_reads {
+                return Err(nb::Error::Other(()));
+            }
+            device.responses.pop_front().ok_or(nb::Error::WouldBlock)
+        }
+    }
+
+    fn new_driver() -> SPIDriver<Tx, Rx> {
+        new_driver_and_device().0
+    }
+
+    fn new_driver_and_device() -> (SPIDriver<Tx, Rx>, Arc<Mutex<Device>>) {
+        let device = Arc::new(Mutex::new(Device::default()));
+        let sd = SPIDriver::new(Tx(device.clone()), Rx(device.clone()));
+        (sd, device)
+    }
+
+    // new_faulted returns an SPIDriverHAL that has just recorded the fault
+    // left by a failed read, for Responses, or a failed write, for
+    // Commands, along with its simulated device, which no longer fails.
+    fn new_faulted(fault: BusFault) -> (SPIDriverHAL<Tx, Rx>, Arc<Mutex<Device>>) {
+        let (sd, device) = new_driver_and_device();
+        let sdh = SPIDriverHAL::new(sd);
+        let kind = match fault {
+            BusFault::Responses => {
+                device.lock().unwrap().fail_reads = true;
+                sdh.transfer(&mut [1, 2]).map(|_| ())
+            }
+            BusFault::Commands => {
+                device.lock().unwrap().fail_writes = true;
+                sdh.write(&[1, 2])
             }
+        }
+        .unwrap_err()
+        .comms_error()
+        .map(|err| err.kind());
+        let expected = match fault {
+            BusFault::Responses => ErrorKind::Read,
+            BusFault::Commands => ErrorKind::Write,
+        };
+        assert_eq!(kind, Some(expected));
+        assert_eq!(sdh.fault().unwrap(), Some(fault));
+        *device.lock().unwrap() = Device::default();
+        (sdh, device)
+    }
+
+    fn assert_refused<T: core::fmt::Debug>(result: Result<T, Error<(), ()>>, fault: BusFault) {
+        match result {
+            Err(Error::Fault(got)) => assert_eq!(got, fault),
+            other => panic!("expected {:?} fault, got {:?}", fault, other),
+        }
+    }
+
+    #[test]
+    fn fault_for_kind() {
+        let cases = [
+            (ErrorKind::Request(RequestError::Empty), None),
+            (ErrorKind::Unsupported(Capability::ModeSelect), None),
+            (ErrorKind::Timeout, Some(BusFault::Responses)),
+            (ErrorKind::Read, Some(BusFault::Responses)),
+            (
+                ErrorKind::Protocol(ProtocolFault::UnexpectedResponse),
+                Some(BusFault::Responses),
+            ),
+            (
+                ErrorKind::CrcMismatch {
+                    expected: 0,
+                    actual: 1,
+                },
+                Some(BusFault::Responses),
+            ),
+            (ErrorKind::Write, Some(BusFault::Commands)),
+            (ErrorKind::Poisoned, Some(BusFault::Commands)),
+            (ErrorKind::DeviceUnresponsive, Some(BusFault::Commands)),
+        ];
+        for (kind, want) in cases {
+            assert_eq!(BusFault::for_kind(kind), want, "{:?}", kind);
+        }
+    }
+
+    #[test]
+    fn responses_fault_allows_commands() {
+        let (sdh, _) = new_faulted(BusFault::Responses);
+        sdh.write(&[1, 2]).unwrap();
+        sdh.set_cs(CsState::Asserted).unwrap();
+        sdh.set_a(PinState::Low).unwrap();
+        sdh.set_b(PinState::Low).unwrap();
+        assert_refused(sdh.transfer(&mut [1, 2]), BusFault::Responses);
+        assert_refused(sdh.flush(), BusFault::Responses);
+        assert_eq!(sdh.fault().unwrap(), Some(BusFault::Responses));
+    }
+
+    #[test]
+    fn commands_fault_blocks_everything() {
+        let (sdh, _) = new_faulted(BusFault::Commands);
+        assert_refused(sdh.write(&[1, 2]), BusFault::Commands);
+        assert_refused(sdh.set_cs(CsState::Asserted), BusFault::Commands);
+        assert_refused(sdh.set_a(PinState::Low), BusFault::Commands);
+        assert_refused(sdh.set_b(PinState::Low), BusFault::Commands);
+        assert_refused(sdh.transfer(&mut [1, 2]), BusFault::Commands);
+        assert_refused(sdh.flush(), BusFault::Commands);
+    }
+
+    #[test]
+    fn responses_fault_keeps_commands_fault() {
+        let (sdh, device) = new_faulted(BusFault::Commands);
+        device.lock().unwrap().fail_reads = true;
+        sdh.with_bus(|bus| {
+            // The HAL objects refuse everything after a Commands fault, so
+            // we use the SPIDriver directly to get a read error to record.
+            bus.sd.reset_state();
+            let result = bus.sd.transfer(&mut [1, 2]).map(|_| ());
+            assert_eq!(result.as_ref().unwrap_err().kind(), ErrorKind::Read);
+            assert!(bus.record(result).is_err());
             Ok(())
-        })?;
-        Ok(data)
+        })
+        .unwrap();
+        assert_eq!(sdh.fault().unwrap(), Some(BusFault::Commands));
+    }
+
+    #[test]
+    fn resync_clears_fault() {
+        for fault in [BusFault::Responses, BusFault::Commands] {
+            let (sdh, _) = new_faulted(fault);
+            sdh.resync().unwrap();
+            assert_eq!(sdh.fault().unwrap(), None);
+            assert_eq!(sdh.transfer(&mut [1, 2]).unwrap(), [1, 2]);
+        }
+    }
+
+    #[test]
+    fn clear_fault_clears_fault() {
+        for fault in [BusFault::Responses, BusFault::Commands] {
+            let (sdh, _) = new_faulted(fault);
+            sdh.clear_fault().unwrap();
+            assert_eq!(sdh.fault().unwrap(), None);
+            assert_eq!(sdh.transfer(&mut [1, 2]).unwrap(), [1, 2]);
+        }
+    }
+
+    #[test]
+    fn reentrant_transaction_is_busy() {
+        let sdh = SPIDriverHAL::new(new_driver());
+        sdh.begin_transaction().unwrap();
+        assert!(matches!(sdh.begin_transaction(), Err(Error::BusBusy)));
+        sdh.end_transaction();
+        sdh.begin_transaction().unwrap();
+        sdh.end_transaction();
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn transaction_waits_for_other_thread() {
+        use std::sync::atomic::{AtomicBool, Ordering};
+        use std::time::Duration;
+
+        let sdh = SPIDriverHAL::<_, _, Mutex<_>>::with_mutex(new_driver());
+        let began = AtomicBool::new(false);
+        sdh.begin_transaction().unwrap();
+        std::thread::scope(|s| {
+            let other = s.spawn(|| {
+                sdh.begin_transaction()?;
+                began.store(true, Ordering::SeqCst);
+                sdh.end_transaction();
+                Ok::<_, Error<(), ()>>(())
+            });
+            std::thread::sleep(Duration::from_millis(50));
+            assert!(!began.load(Ordering::SeqCst));
+            sdh.end_transaction();
+            other.join().unwrap().unwrap();
+        });
+        assert!(began.load(Ordering::SeqCst));
     }
 }
@@ -0,0 +1,218 @@
+//! Mutexes that protect the state shared by the HAL objects obtained from
+//! an `SPIDriverHAL`.
+//!
+//! Each HAL object communicates with the SPIDriver by locking the shared
+//! state for the duration of a single request. The type of mutex therefore
+//! determines where the HAL objects can be used: the default `RefCell`
+//! allows them to be used only on a single thread, `std::sync::Mutex`
+//! allows them to be shared between threads, and `CriticalSectionMutex`
+//! allows them to be shared with interrupt handlers.
+//!
+//! When the `async` feature is enabled, `AsyncBusMutex` similarly protects
+//! an `AsyncSPIDriver` shared by the devices in the `asynch` module. Those
+//! devices hold the lock for the whole of each transaction, across its
+//! await points, and so need a mutex that tasks can wait for without
+//! blocking the executor.
+
+/// `BusMutex` is implemented by the types that can protect the state shared
+/// by the HAL objects obtained from an `SPIDriverHAL`.
+///
+/// To use a mutex other than the default, create the `SPIDriverHAL` using
+/// `SPIDriverHAL::with_mutex`.
+pub trait BusMutex {
+    /// `Bus` is the type of the state that the mutex protects.
+    type Bus;
+
+    /// `create` returns a new mutex protecting the given state.
+    fn create(bus: Self::Bus) -> Self;
+
+    /// `lock` calls the given function with exclusive access to the
+    /// protected state, and returns its result, or returns an error without
+    /// calling it if the state can't be accessed.
+    fn lock<R, F: FnOnce(&mut Self::Bus) -> R>(&self, f: F) -> Result<R, LockError>;
+}
+
+/// `LockError` describes why a `BusMutex` couldn't provide access to the
+/// state it protects. The HAL objects report it as an `Error`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LockError {
+    /// `Poisoned` indicates that a thread panicked while it held the lock,
+    /// and so the protected state may be inconsistent.
+    Poisoned,
+
+    /// `Busy` indicates that the lock is already held by the same thread,
+    /// such as when a HAL object is used from within a callback that runs
+    /// during a request by another.
+    Busy,
+}
+
+/// `RefCell` is the default mutex, which allows the HAL objects to be used
+/// only on a single thread. Because each HAL object holds the lock only
+/// during a call to one of its own methods, the lock is contended only if a
+/// HAL object is used reentrantly, in which case the request fails with
+/// `Error::BusBusy`.
+///
+/// A program that guarantees that the HAL objects never run concurrently,
+/// such as one sharing them between RTIC tasks of the same priority, can
+/// instead obtain them using `SPIDriverHAL::split_unsynchronized` to move
+/// them between execution contexts while keeping this mutex.
+impl<T> BusMutex for core::cell::RefCell<T> {
+    type Bus = T;
+
+    fn create(bus: T) -> Self {
+        core::cell::RefCell::new(bus)
+    }
+
+    fn lock<R, F: FnOnce(&mut T) -> R>(&self, f: F) -> Result<R, LockError> {
+        let mut bus = self.try_borrow_mut().map_err(|_| LockError::Busy)?;
+        Ok(f(&mut bus))
+    }
+}
+
+/// `std::sync::Mutex` allows the HAL objects to be shared between threads,
+/// such as to drive the SPI bus from one thread while another controls the
+/// auxillary output pins.
+///
+/// An `SPIDriverHAL` using this mutex is `Sync` whenever the serial
+/// implementations it wraps are `Send`, and so the HAL objects obtained by
+/// calling `into_shared_parts`, or by calling `split` within a scope that
+/// outlives the threads, can be moved into other threads. Each request to
+/// the SPIDriver holds the lock until the SPIDriver has responded, and
+/// requests from different threads are interleaved between those.
+///
+/// A panic on another thread while it holds the lock poisons the mutex, after
+/// which every request through the HAL objects returns `Error::Poisoned`.
+///
+/// `SpiDevice` transactions must still not overlap, so a transaction that
+/// begins while one on another thread is in progress waits for it to end,
+/// without holding the lock in the meantime. A transaction that begins
+/// while one on the same thread is in progress, which can happen only if a
+/// HAL object is used reentrantly, fails with `Error::BusBusy` instead.
+///
+/// This implementation is available only when the `std` feature is enabled.
+#[cfg(feature = "std")]
+impl<T> BusMutex for std::sync::Mutex<T> {
+    type Bus = T;
+
+    fn create(bus: T) -> Self {
+        std::sync::Mutex::new(bus)
+    }
+
+    fn lock<R, F: FnOnce(&mut T) -> R>(&self, f: F) -> Result<R, LockError> {
+        let mut bus = std::sync::Mutex::lock(self).map_err(|_| LockError::Poisoned)?;
+        Ok(f(&mut bus))
+    }
+}
+
+/// `CriticalSectionMutex` is a mutex that locks the shared state by entering
+/// a critical section using the `critical-section` crate, for when the host
+/// is itself a microcontroller and the HAL objects must be shared with
+/// interrupt handlers.
+///
+/// An `SPIDriverHAL` using this mutex is `Sync` whenever the serial
+/// implementations it wraps are `Send`, and so the HAL objects obtained by
+/// calling `split` on one stored in a `static` can be moved into interrupt
+/// handlers. Each request to the SPIDriver happens entirely within a
+/// critical section, which on most platforms means that interrupts remain
+/// disabled until the SPIDriver has responded.
+///
+/// This type is available only when the `critical-section` feature is
+/// enabled.
+#[cfg(feature = "critical-section")]
+pub type CriticalSectionMutex<T> = critical_section::Mutex<core::cell::RefCell<T>>;
+
+#[cfg(feature = "critical-section")]
+impl<T> BusMutex for CriticalSectionMutex<T> {
+    type Bus = T;
+
+    fn create(bus: T) -> Self {
+        critical_section::Mutex::new(core::cell::RefCell::new(bus))
+    }
+
+    fn lock<R, F: FnOnce(&mut T) -> R>(&self, f: F) -> Result<R, LockError> {
+        critical_section::with(|cs| {
+            let mut bus = self
+                .borrow(cs)
+                .try_borrow_mut()
+                .map_err(|_| LockError::Busy)?;
+            Ok(f(&mut bus))
+        })
+    }
+}
+
+/// `AsyncBusMutex` is implemented by the asynchronous mutexes that can
+/// protect an `AsyncSPIDriver` shared by the devices in the `asynch` module.
+///
+/// This crate implements it for `embassy_sync::mutex::Mutex` when the
+/// `embassy-sync` feature is enabled, and for `tokio::sync::Mutex` when the
+/// `tokio` feature is enabled.
+///
+/// This trait is available only when the `async` feature is enabled.
+#[cfg(feature = "async")]
+pub trait AsyncBusMutex {
+    /// `Bus` is the type of the state that the mutex protects.
+    type Bus;
+
+    /// `Guard` provides exclusive access to the protected state until it is
+    /// dropped.
+    type Guard<'a>: core::ops::DerefMut<Target = Self::Bus>
+    where
+        Self: 'a;
+
+    /// `create` returns a new mutex protecting the given state.
+    fn create(bus: Self::Bus) -> Self;
+
+    /// `lock` waits until no other task holds the lock, and then returns a
+    /// guard providing exclusive access to the protected state.
+    fn lock(&self) -> impl core::future::Future<Output = Self::Guard<'_>>;
+}
+
+/// `embassy_sync::mutex::Mutex` allows the devices in the `asynch` module
+/// to be shared between embassy tasks. Its raw mutex type `M` determines
+/// whether those tasks can run on different executors or in interrupt
+/// handlers, as described in the `embassy-sync` documentation.
+///
+/// This implementation is available only when the `embassy-sync` feature is
+/// enabled.
+#[cfg(feature = "embassy-sync")]
+impl<M, T> AsyncBusMutex for embassy_sync::mutex::Mutex<M, T>
+where
+    M: embassy_sync::blocking_mutex::raw::RawMutex,
+{
+    type Bus = T;
+    type Guard<'a>
+        = embassy_sync::mutex::MutexGuard<'a, M, T>
+    where
+        Self: 'a;
+
+    fn create(bus: T) -> Self {
+        embassy_sync::mutex::Mutex::new(bus)
+    }
+
+    fn lock(&self) -> impl core::future::Future<Output = Self::Guard<'_>> {
+        embassy_sync::mutex::Mutex::lock(self)
+    }
+}
+
+/// `tokio::sync::Mutex` allows the devices in the `asynch` module to be
+/// shared between tokio tasks, including those running on different threads
+/// of a multi-threaded runtime.
+///
+/// This implementation is available only when the `tokio` feature is
+/// enabled.
+#[cfg(feature = "tokio")]
+impl<T> AsyncBusMutex for tokio::sync::Mutex<T> {
+    type Bus = T;
+    type Guard<'a>
+        = tokio::sync::MutexGuard<'a, T>
+    where
+        Self: 'a;
+
+    fn create(bus: T) -> Self {
+        tokio::sync::Mutex::new(bus)
+    }
+
+    fn lock(&self) -> impl core::future::Future<Output = Self::Guard<'_>> {
+        tokio::sync::Mutex::lock(self)
+    }
+}
@@ -0,0 +1,27 @@
+//! Re-exports of the most commonly-used items in this crate and in the
+//! `spidriver` crate it builds on.
+//!
+//! The `embedded-hal` traits implemented by the HAL objects are imported
+//! anonymously, so that their methods are callable without the trait names
+//! conflicting with anything in the importing module. The embedded-hal 0.2
+//! traits are included only when the `eh0_2` feature is enabled.
+//!
+//! ```rust
+//! use spidriver_hal::prelude::*;
+//! ```
+
+pub use crate::hal::Parts;
+pub use crate::SPIDriverHAL;
+#[cfg(feature = "eh0_2")]
+pub use embedded_hal::blocking::delay::{DelayMs as _, DelayUs as _};
+#[cfg(feature = "eh0_2")]
+pub use embedded_hal::blocking::spi::{
+    Transactional as _, Transfer as _, Write as _, WriteIter as _,
+};
+#[cfg(feature = "eh0_2")]
+pub use embedded_hal::digital::v2::{
+    OutputPin as _, StatefulOutputPin as _, ToggleableOutputPin as _,
+};
+#[cfg(feature = "eh0_2")]
+pub use embedded_hal::spi::FullDuplex as _;
+pub use spidriver::prelude::*;
@@ -0,0 +1,83 @@
+// A simulated SPIDriver, for measuring the overhead of the library itself
+// without the latency of a real serial port.
+//
+// The simulated device understands just enough of the protocol for the
+// benchmarks: it echoes back the argument of each echo command, and its SPI
+// bus is looped back so that each transfer returns the data that was sent.
+// All other commands are accepted and ignored.
+
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::convert::Infallible;
+use std::rc::Rc;
+
+use embedded_hal::serial;
+
+#[derive(Default)]
+struct Device {
+    // The command currently being received, including its opcode.
+    command: Vec<u8>,
+
+    // The responses waiting to be read by the host.
+    responses: VecDeque<u8>,
+}
+
+impl Device {
+    fn receive(&mut self, c: u8) {
+        self.command.push(c);
+        let opcode = self.command[0];
+        if self.command.len() < 1 + arg_len(opcode) {
+            return;
+        }
+        match opcode {
+            b'e' | 0x80..=0xbf => self.responses.extend(&self.command[1..]),
+            _ => {}
+        }
+        self.command.clear();
+    }
+}
+
+// arg_len returns the number of argument bytes that follow the given opcode.
+fn arg_len(opcode: u8) -> usize {
+    match opcode {
+        b'e' | b'a' | b'b' => 1,
+        0x80..=0xbf => (opcode - 0x80) as usize + 1,
+        0xc0..=0xff => (opcode - 0xc0) as usize + 1,
+        _ => 0,
+    }
+}
+
+pub struct MockTx(Rc<RefCell<Device>>);
+
+pub struct MockRx(Rc<RefCell<Device>>);
+
+// new returns the serial writer and reader for a new simulated SPIDriver.
+pub fn new() -> (MockTx, MockRx) {
+    let device = Rc::new(RefCell::new(Device::default()));
+    (MockTx(device.clone()), MockRx(device))
+}
+
+impl serial::Write<u8> for MockTx {
+    type Error = Infallible;
+
+    fn write(&mut self, c: u8) -> nb::Result<(), Self::Error> {
+        self.0.borrow_mut().receive(c);
+        Ok(())
+    }
+
+    fn flush(&mut self) -> nb::Result<(), Self::Error> {
+        Ok(())
+    }
+}
+
+impl serial::Read<u8> for MockRx {
+    type Error = Infallible;
+
+    fn read(&mut self) -> nb::Result<u8, Self::Error> {
+        self.0
+            .borrow_mut()
+            .responses
+            .pop_front()
+            .ok_or(nb::Error::WouldBlock)
+    }
+}
@@ -0,0 +1,50 @@
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use spidriver::{SPIDriver, MAX_FRAME};
+
+mod mock;
+
+// The amount of data sent in each iteration, and the chunk sizes to split it
+// into. Each chunk becomes one protocol frame.
+const TOTAL: usize = 4096;
+const CHUNK_SIZES: [usize; 4] = [1, 8, 32, MAX_FRAME];
+
+fn write(c: &mut Criterion) {
+    let (tx, rx) = mock::new();
+    let mut sd = SPIDriver::new(tx, rx);
+    let data = [0xa5_u8; TOTAL];
+
+    let mut group = c.benchmark_group("write");
+    group.throughput(Throughput::Bytes(TOTAL as u64));
+    for size in CHUNK_SIZES {
+        group.bench_with_input(BenchmarkId::from_parameter(size), &size, |b, &size| {
+            b.iter(|| {
+                for chunk in data.chunks(size) {
+                    sd.write(chunk).unwrap();
+                }
+            })
+        });
+    }
+    group.finish();
+}
+
+fn transfer(c: &mut Criterion) {
+    let (tx, rx) = mock::new();
+    let mut sd = SPIDriver::new(tx, rx);
+    let mut data = [0xa5_u8; TOTAL];
+
+    let mut group = c.benchmark_group("transfer");
+    group.throughput(Throughput::Bytes(TOTAL as u64));
+    for size in CHUNK_SIZES {
+        group.bench_with_input(BenchmarkId::from_parameter(size), &size, |b, &size| {
+            b.iter(|| {
+                for chunk in data.chunks_mut(size) {
+                    sd.transfer(chunk).unwrap();
+                }
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, write, transfer);
+criterion_main!(benches);
@@ -0,0 +1,84 @@
+use std::time::{Duration, Instant};
+
+use embedded_hal::serial;
+use serial_embedded_hal::{PortSettings, Serial};
+use spidriver::{SPIDriver, MAX_FRAME};
+
+#[path = "../benches/mock/mod.rs"]
+mod mock;
+
+// The amount of data sent for each measurement, and the chunk sizes to split
+// it into. Each chunk becomes one protocol frame.
+const TOTAL: usize = 16 * 1024;
+const CHUNK_SIZES: [usize; 4] = [1, 8, 32, MAX_FRAME];
+
+fn main() {
+    // This example measures the write and transfer throughput achievable
+    // at various chunk sizes. If given the path of a serial port then it
+    // measures a real SPIDriver connected to that port, and otherwise it
+    // measures a simulated device, which shows the overhead of the library
+    // itself.
+    //
+    // The measurements with a real device send data over the SPI bus, so
+    // make sure that nothing sensitive to stray data is connected to it.
+
+    match std::env::args().nth(1) {
+        Some(path) => {
+            let port = Serial::new(
+                &path,
+                &PortSettings {
+                    baud_rate: serial_embedded_hal::BaudRate::BaudOther(460800),
+                    char_size: serial_embedded_hal::CharSize::Bits8,
+                    parity: serial_embedded_hal::Parity::ParityNone,
+                    stop_bits: serial_embedded_hal::StopBits::Stop1,
+                    flow_control: serial_embedded_hal::FlowControl::FlowNone,
+                },
+            )
+            .unwrap();
+            let (tx, rx) = port.split();
+            println!("measuring SPIDriver at {}", path);
+            run(&mut SPIDriver::new(tx, rx));
+        }
+        None => {
+            let (tx, rx) = mock::new();
+            println!("measuring simulated SPIDriver");
+            run(&mut SPIDriver::new(tx, rx));
+        }
+    }
+}
+
+fn run<TX, RX>(sd: &mut SPIDriver<TX, RX>)
+where
+    TX: serial::Write<u8>,
+    RX: serial::Read<u8>,
+    TX::Error: std::fmt::Debug,
+    RX::Error: std::fmt::Debug,
+{
+    let mut data = [0xa5_u8; TOTAL];
+    for size in CHUNK_SIZES {
+        let start = Instant::now();
+        for chunk in data.chunks(size) {
+            sd.write(chunk).unwrap();
+        }
+        let write = start.elapsed();
+
+        let start = Instant::now();
+        for chunk in data.chunks_mut(size) {
+            sd.transfer(chunk).unwrap();
+        }
+        let transfer = start.elapsed();
+
+        println!(
+            "{:>2}-byte chunks: write {:>10.0} bytes/sec, transfer {:>10.0} bytes/sec",
+            size,
+            rate(write),
+            rate(transfer),
+        );
+    }
+}
+
+// rate returns the throughput in bytes per second of sending TOTAL bytes in
+// the given duration.
+fn rate(elapsed: Duration) -> f64 {
+    TOTAL as f64 / elapsed.as_secs_f64()
+}
@@ -1,6 +1,6 @@
 use embedded_hal::serial;
 use serial_embedded_hal::{PortSettings, Serial};
-use spidriver::SPIDriver;
+use spidriver::{AuxPin, DcMode, PinRoles, SPIDriver, SPIDriverBuilder};
 
 fn main() {
     // This example demonstrates using the SPIDriver to configure an SSD1322
@@ -27,7 +27,14 @@ fn main() {
     .unwrap();
     let (tx, rx) = port.split();
 
-    let mut sd = SPIDriver::new(tx, rx);
+    let mut sd = SPIDriverBuilder::new()
+        .pin_roles(PinRoles {
+            dc: Some(AuxPin::A),
+            reset: Some(AuxPin::B),
+            ..PinRoles::default()
+        })
+        .build(tx, rx)
+        .unwrap();
 
     init(&mut sd).unwrap();
 
@@ -41,10 +48,13 @@ fn main() {
     for y in 0..HEIGHT {
         // each byte represents two pixels
         for x in 0..(WIDTH / 2) {
-            buf[(y * WIDTH / 2 + x)] = if y % 2 == 0 { 0xf0 } else { 0x0f };
+            buf[y * WIDTH / 2 + x] = if y % 2 == 0 { 0xf0 } else { 0x0f };
         }
     }
-    cmd_n(&mut sd, 0x5c, &mut buf[..]).unwrap();
+    // A display refresh loop would push a newly-rendered buffer on each
+    // iteration, but we only need to push our pattern once.
+    let mut frames = sd.stream_frames(BUF_SIZE, Some(0x5c)).unwrap();
+    frames.push(&buf[..]).unwrap();
 }
 
 fn init<TX: serial::Write<u8>, RX: serial::Read<u8>>(
@@ -84,20 +94,19 @@ fn init<TX: serial::Write<u8>, RX: serial::Read<u8>>(
 fn reset<TX: serial::Write<u8>, RX: serial::Read<u8>>(
     sd: &mut SPIDriver<TX, RX>,
 ) -> Result<(), spidriver::Error<TX::Error, RX::Error>> {
-    // We'll pulse the B signal on the SPIDriver, which we assume is connected
-    // to the reset line on the display driver.
-    sd.set_b(false)?;
-    sd.set_b(true)
+    // We'll pulse the B signal on the SPIDriver, which we assigned the reset
+    // role when we created the SPIDriver object.
+    sd.pulse_reset()
 }
 
 fn cmd_0<TX: serial::Write<u8>, RX: serial::Read<u8>>(
     sd: &mut SPIDriver<TX, RX>,
     cmd: u8,
 ) -> Result<(), spidriver::Error<TX::Error, RX::Error>> {
-    sd.select()?;
-    sd.set_a(false)?; // Command mode
-    sd.write_byte(cmd)?;
-    sd.unselect()
+    sd.with_selected(|sd| {
+        sd.set_dc(DcMode::Command)?;
+        sd.write_byte(cmd)
+    })
 }
 
 fn cmd_1<TX: serial::Write<u8>, RX: serial::Read<u8>>(
@@ -105,12 +114,12 @@ fn cmd_1<TX: serial::Write<u8>, RX: serial::Read<u8>>(
     cmd: u8,
     a: u8,
 ) -> Result<(), spidriver::Error<TX::Error, RX::Error>> {
-    sd.select()?;
-    sd.set_a(false)?; // Command mode
-    sd.write_byte(cmd)?;
-    sd.set_a(true)?; // Data mode
-    sd.write_byte(a)?;
-    sd.unselect()
+    sd.with_selected(|sd| {
+        sd.set_dc(DcMode::Command)?;
+        sd.write_byte(cmd)?;
+        sd.set_dc(DcMode::Data)?;
+        sd.write_byte(a)
+    })
 }
 
 fn cmd_2<TX: serial::Write<u8>, RX: serial::Read<u8>>(
@@ -119,30 +128,11 @@ fn cmd_2<TX: serial::Write<u8>, RX: serial::Read<u8>>(
     a: u8,
     b: u8,
 ) -> Result<(), spidriver::Error<TX::Error, RX::Error>> {
-    sd.select()?;
-    sd.set_a(false)?; // Command mode
-    sd.write_byte(cmd)?;
-    let msg: [u8; 2] = [a, b];
-    sd.set_a(true)?; // Data mode
-    sd.write(&msg[..])?;
-    sd.unselect()
-}
-
-fn cmd_n<TX: serial::Write<u8>, RX: serial::Read<u8>>(
-    sd: &mut SPIDriver<TX, RX>,
-    cmd: u8,
-    data: &mut [u8],
-) -> Result<(), spidriver::Error<TX::Error, RX::Error>> {
-    sd.select()?;
-    sd.set_a(false)?; // Command mode
-    sd.write_byte(cmd)?;
-    let mut remain = data;
-    sd.set_a(true)?; // Data mode
-    while remain.len() > 0 {
-        let len: usize = if remain.len() > 64 { 64 } else { remain.len() };
-        let (this, next) = remain.split_at_mut(len);
-        sd.write(this)?;
-        remain = next;
-    }
-    sd.unselect()
+    sd.with_selected(|sd| {
+        sd.set_dc(DcMode::Command)?;
+        sd.write_byte(cmd)?;
+        let msg: [u8; 2] = [a, b];
+        sd.set_dc(DcMode::Data)?;
+        sd.write(&msg[..])
+    })
 }
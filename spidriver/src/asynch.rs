@@ -0,0 +1,327 @@
+use embedded_hal_async::delay::DelayNs;
+use embedded_hal_async::spi::{self, Operation};
+use embedded_io_async::{Read, Write};
+
+use embassy_futures::join::join;
+
+use crate::{Context, Error, ErrorKind, ProtocolFault, MAX_FRAME};
+
+/// `AsyncSPIDriver` represents an SPIDriver device connected through an
+/// asynchronous serial transport, such as a UART driver from an embassy HAL
+/// or a serial port adapted for use with tokio.
+///
+/// As with `SPIDriver`, the transport is given as separate writer and reader
+/// halves, so that `AsyncSPIDriver` can send the next frame of a long
+/// transfer while still receiving the response to the previous one. Most
+/// asynchronous UART drivers and serial port adapters can be split in this
+/// way.
+///
+/// `AsyncSPIDriver` implements the `embedded-hal-async` `SpiBus` trait, which
+/// leaves the chip select signal alone, and `device` returns an
+/// implementation of the `SpiDevice` trait that asserts the SPIDriver's own
+/// chip select signal around each transaction. Data longer than 64 bytes is
+/// split into several protocol frames, as with `SPIDriver::write_all` and
+/// `SPIDriver::transfer_all`.
+///
+/// Only the core SPI operations are available asynchronously. There is no
+/// read timeout, because an application can instead race any operation
+/// against a timer using its executor's own facilities. If an operation is
+/// cancelled part way through then the host and device may be out of step,
+/// so the transport should then be reset before using it again.
+///
+/// This type is available only when the `async` feature is enabled.
+#[derive(Debug)]
+pub struct AsyncSPIDriver<TX, RX> {
+    tx: TX,
+    rx: RX,
+}
+
+impl<TX, RX, TXErr, RXErr> AsyncSPIDriver<TX, RX>
+where
+    TX: Write<Error = TXErr>,
+    RX: Read<Error = RXErr>,
+{
+    /// `new` consumes the writer and reader halves of an asynchronous serial
+    /// transport to produce an `AsyncSPIDriver` object.
+    pub fn new(tx: TX, rx: RX) -> Self {
+        Self { tx, rx }
+    }
+
+    /// `into_inner` consumes the `AsyncSPIDriver` and returns the writer and
+    /// reader halves of the serial transport.
+    pub fn into_inner(self) -> (TX, RX) {
+        (self.tx, self.rx)
+    }
+
+    /// `device` returns an implementation of the `embedded-hal-async`
+    /// `SpiDevice` trait, which asserts the SPIDriver's chip select signal
+    /// for the duration of each transaction.
+    ///
+    /// `delay` implements any `Operation::DelayNs` in a transaction. That
+    /// delay is measured on the host, and so is only a lower bound on the
+    /// delay between the operations on the bus.
+    pub fn device<D>(&mut self, delay: D) -> AsyncDevice<'_, TX, RX, D> {
+        AsyncDevice { sd: self, delay }
+    }
+
+    /// `select` asserts the chip select signal by driving it low.
+    pub async fn select(&mut self) -> Result<(), Error<TXErr, RXErr>> {
+        command(&mut self.tx, b's', &[]).await?;
+        flush_io(&mut self.tx, b's').await
+    }
+
+    /// `unselect` de-asserts the chip select signal by driving it high.
+    pub async fn unselect(&mut self) -> Result<(), Error<TXErr, RXErr>> {
+        command(&mut self.tx, b'u', &[]).await?;
+        flush_io(&mut self.tx, b'u').await
+    }
+
+    /// `write_all` sends all of the given data out over the SPIDriver's MOSI
+    /// line, in as many 64-byte protocol frames as necessary.
+    pub async fn write_all(&mut self, data: &[u8]) -> Result<(), Error<TXErr, RXErr>> {
+        for (i, chunk) in data.chunks(MAX_FRAME).enumerate() {
+            let opcode = 0xc0 - 1 + chunk.len() as u8;
+            command(&mut self.tx, opcode, chunk)
+                .await
+                .map_err(|e| e.at_offset(i * MAX_FRAME))?;
+        }
+        Ok(())
+    }
+
+    /// `transfer_all` sends all of the given data out over the SPIDriver's
+    /// MOSI line, in as many 64-byte protocol frames as necessary, and
+    /// replaces it with the data returned by the target device.
+    ///
+    /// Each frame after the first is sent while the response to the frame
+    /// before it is still being received, so that a transport that can
+    /// carry data in both directions at once spends less time idle than it
+    /// would if each frame waited for the previous response.
+    pub async fn transfer_all(&mut self, data: &mut [u8]) -> Result<(), Error<TXErr, RXErr>> {
+        let mut chunks = data.chunks_mut(MAX_FRAME).enumerate();
+        let (mut i, mut chunk) = match chunks.next() {
+            Some(first) => first,
+            None => return Ok(()),
+        };
+        send_transfer(&mut self.tx, chunk)
+            .await
+            .map_err(|e| e.at_offset(i * MAX_FRAME))?;
+        loop {
+            let opcode = transfer_opcode(chunk);
+            let (next_i, next_chunk) = match chunks.next() {
+                Some(next) => next,
+                None => {
+                    return receive(&mut self.rx, opcode, chunk)
+                        .await
+                        .map_err(|e| e.at_offset(i * MAX_FRAME));
+                }
+            };
+            let (received, sent) = join(
+                receive(&mut self.rx, opcode, chunk),
+                send_transfer(&mut self.tx, next_chunk),
+            )
+            .await;
+            // If both directions failed then the error for the earlier
+            // frame is the more useful one to report.
+            received.map_err(|e| e.at_offset(i * MAX_FRAME))?;
+            sent.map_err(|e| e.at_offset(next_i * MAX_FRAME))?;
+            i = next_i;
+            chunk = next_chunk;
+        }
+    }
+}
+
+fn transfer_opcode(chunk: &[u8]) -> u8 {
+    0x80 - 1 + chunk.len() as u8
+}
+
+// send_transfer sends a transfer command for the given frame and flushes
+// it, so that the device can begin responding.
+async fn send_transfer<TX, TXErr, RXErr>(
+    tx: &mut TX,
+    chunk: &[u8],
+) -> Result<(), Error<TXErr, RXErr>>
+where
+    TX: Write<Error = TXErr>,
+{
+    let opcode = transfer_opcode(chunk);
+    command(tx, opcode, chunk).await?;
+    flush_io(tx, opcode).await
+}
+
+// command sends a protocol command, assembling it first so that it can be
+// written all at once.
+async fn command<TX, TXErr, RXErr>(
+    tx: &mut TX,
+    opcode: u8,
+    args: &[u8],
+) -> Result<(), Error<TXErr, RXErr>>
+where
+    TX: Write<Error = TXErr>,
+{
+    let mut buf = [0_u8; MAX_FRAME + 1];
+    buf[0] = opcode;
+    buf[1..=args.len()].copy_from_slice(args);
+    tx.write_all(&buf[..=args.len()])
+        .await
+        .map_err(|err| Error::write(err, context(opcode)))
+}
+
+async fn flush_io<TX, TXErr, RXErr>(tx: &mut TX, opcode: u8) -> Result<(), Error<TXErr, RXErr>>
+where
+    TX: Write<Error = TXErr>,
+{
+    tx.flush()
+        .await
+        .map_err(|err| Error::write(err, context(opcode)))
+}
+
+// receive reads the response to a command, filling the whole of the given
+// buffer.
+async fn receive<RX, TXErr, RXErr>(
+    rx: &mut RX,
+    opcode: u8,
+    buf: &mut [u8],
+) -> Result<(), Error<TXErr, RXErr>>
+where
+    RX: Read<Error = RXErr>,
+{
+    let expected = buf.len();
+    let mut received = 0;
+    while received < expected {
+        let n = rx
+            .read(&mut buf[received..])
+            .await
+            .map_err(|err| Error::read(err, context(opcode)))?;
+        if n == 0 {
+            // The transport has reached end of file, so the rest of the
+            // response will never arrive.
+            return Err(Error::with_context(
+                ErrorKind::Protocol(ProtocolFault::ShortResponse { expected, received }),
+                context(opcode),
+            ));
+        }
+        received += n;
+    }
+    Ok(())
+}
+
+fn context(command: u8) -> Context {
+    Context { command, offset: 0 }
+}
+
+impl<TX, RX, TXErr, RXErr> spi::ErrorType for AsyncSPIDriver<TX, RX>
+where
+    TX: Write<Error = TXErr>,
+    RX: Read<Error = RXErr>,
+    TXErr: core::fmt::Debug,
+    RXErr: core::fmt::Debug,
+{
+    type Error = Error<TXErr, RXErr>;
+}
+
+impl<TX, RX, TXErr, RXErr> spi::SpiBus<u8> for AsyncSPIDriver<TX, RX>
+where
+    TX: Write<Error = TXErr>,
+    RX: Read<Error = RXErr>,
+    TXErr: core::fmt::Debug,
+    RXErr: core::fmt::Debug,
+{
+    async fn read(&mut self, words: &mut [u8]) -> Result<(), Self::Error> {
+        words.fill(0xff);
+        self.transfer_all(words).await
+    }
+
+    async fn write(&mut self, words: &[u8]) -> Result<(), Self::Error> {
+        self.write_all(words).await
+    }
+
+    async fn transfer(&mut self, read: &mut [u8], write: &[u8]) -> Result<(), Self::Error> {
+        // We transfer in place in the read buffer so that the frames can
+        // be pipelined as in transfer_all. Any write data beyond the end of
+        // the read buffer is sent with write commands, since its response
+        // would be discarded anyway.
+        let n = read.len().min(write.len());
+        read[..n].copy_from_slice(&write[..n]);
+        read[n..].fill(0xff);
+        self.transfer_all(read).await?;
+        self.write_all(&write[n..])
+            .await
+            .map_err(|e| e.at_offset(n))
+    }
+
+    async fn transfer_in_place(&mut self, words: &mut [u8]) -> Result<(), Self::Error> {
+        self.transfer_all(words).await
+    }
+
+    async fn flush(&mut self) -> Result<(), Self::Error> {
+        // Write commands are complete once they have been written to the
+        // transport, so we need only ensure that they have left the host.
+        flush_io(&mut self.tx, 0).await
+    }
+}
+
+/// `AsyncDevice` implements the `embedded-hal-async` `SpiDevice` trait in
+/// terms of an `AsyncSPIDriver`, using the SPIDriver's own chip select
+/// signal. Use `AsyncSPIDriver::device` to obtain one.
+///
+/// Each transaction asserts the chip select signal, performs all of its
+/// operations in order, and then releases the chip select signal again,
+/// even if one of the operations failed. The data sent during
+/// `Operation::Read` is 0xff, matching the idle state of the MOSI line.
+#[derive(Debug)]
+pub struct AsyncDevice<'a, TX, RX, D> {
+    sd: &'a mut AsyncSPIDriver<TX, RX>,
+    delay: D,
+}
+
+impl<'a, TX, RX, D, TXErr, RXErr> spi::ErrorType for AsyncDevice<'a, TX, RX, D>
+where
+    TX: Write<Error = TXErr>,
+    RX: Read<Error = RXErr>,
+    TXErr: core::fmt::Debug,
+    RXErr: core::fmt::Debug,
+{
+    type Error = Error<TXErr, RXErr>;
+}
+
+impl<'a, TX, RX, D, TXErr, RXErr> spi::SpiDevice<u8> for AsyncDevice<'a, TX, RX, D>
+where
+    TX: Write<Error = TXErr>,
+    RX: Read<Error = RXErr>,
+    D: DelayNs,
+    TXErr: core::fmt::Debug,
+    RXErr: core::fmt::Debug,
+{
+    async fn transaction(
+        &mut self,
+        operations: &mut [Operation<'_, u8>],
+    ) -> Result<(), Self::Error> {
+        use spi::SpiBus;
+
+        self.sd.select().await?;
+        let mut result = Ok(());
+        for op in operations.iter_mut() {
+            result = match op {
+                Operation::Read(buf) => self.sd.read(buf).await,
+                Operation::Write(buf) => self.sd.write_all(buf).await,
+                Operation::Transfer(read, write) => self.sd.transfer(read, write).await,
+                Operation::TransferInPlace(buf) => self.sd.transfer_all(buf).await,
+                Operation::DelayNs(ns) => {
+                    // The preceding operations must reach the device before
+                    // the delay starts.
+                    let flushed = self.sd.flush().await;
+                    self.delay.delay_ns(*ns).await;
+                    flushed
+                }
+            };
+            if result.is_err() {
+                break;
+            }
+        }
+        // We release the chip select signal even if an operation failed,
+        // but report the operation's error in preference to any error
+        // from releasing it.
+        let released = self.sd.unselect().await;
+        result.and(released)
+    }
+}
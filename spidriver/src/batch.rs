@@ -0,0 +1,177 @@
+use embedded_hal::serial;
+
+use crate::{CsState, Error, PinState, SPIDriver, MAX_FRAME};
+
+/// `DEFAULT_BATCH_CAPACITY` is the number of bytes of encoded commands that
+/// a `Batch` created by `SPIDriver::batch` accumulates before passing them
+/// on to the serial writer.
+///
+/// This is large enough for a typical transaction of a few frames and pin
+/// changes to go out in one write.
+pub const DEFAULT_BATCH_CAPACITY: usize = 4 * (MAX_FRAME + 1);
+
+// The smallest capacity a Batch can have, which is enough to hold one
+// complete write command.
+const MIN_BATCH_CAPACITY: usize = MAX_FRAME + 1;
+
+/// `Batch` accumulates a sequence of commands for an SPIDriver and then sends
+/// them all together, encoded into one contiguous buffer and with only a
+/// single flush of the serial writer.
+///
+/// When block writes are enabled using `SPIDriver::enable_block_writes`, the
+/// whole buffer is written with a single call, so a transaction such as
+/// selecting the target, writing a frame, and unselecting again costs only
+/// one write and one flush.
+///
+/// Obtain a `Batch` by calling `SPIDriver::batch`, queue commands by calling
+/// its methods, and then call `commit` to send them:
+///
+/// ```rust,ignore
+/// sd.batch()
+///     .select()
+///     .set_a(PinState::Low)
+///     .write(&cmd)
+///     .set_a(PinState::High)
+///     .write(&data)
+///     .unselect()
+///     .commit()?;
+/// ```
+///
+/// The queuing methods can't fail, so any error that occurs while queuing is
+/// retained and returned from `commit` instead, and the remaining commands
+/// are discarded. A `Batch` can only queue commands that produce no
+/// response from the device.
+///
+/// Long batches may be passed on to the serial writer in several parts
+/// before `commit` is called. Dropping a `Batch` without calling `commit`
+/// discards any part that has not yet been passed on.
+///
+/// The batch buffer is held inline, and its capacity `N` in bytes is
+/// `DEFAULT_BATCH_CAPACITY` unless a different capacity is chosen using
+/// `SPIDriver::batch_with_capacity`. A smaller capacity saves memory on
+/// hosts with little RAM at the expense of passing long batches on in more
+/// parts. The capacity must be at least 65 bytes, enough for one complete
+/// write command, which is checked at compile time.
+pub struct Batch<'a, TX, RX, const N: usize = DEFAULT_BATCH_CAPACITY>
+where
+    TX: serial::Write<u8>,
+    RX: serial::Read<u8>,
+{
+    sd: &'a mut SPIDriver<TX, RX>,
+    buf: [u8; N],
+    len: usize,
+    err: Option<Error<TX::Error, RX::Error>>,
+}
+
+impl<'a, TX, RX, TXErr, RXErr, const N: usize> Batch<'a, TX, RX, N>
+where
+    TX: serial::Write<u8, Error = TXErr>,
+    RX: serial::Read<u8, Error = RXErr>,
+{
+    const CAPACITY_OK: () = assert!(
+        N >= MIN_BATCH_CAPACITY,
+        "Batch capacity must be large enough for one write command"
+    );
+
+    pub(crate) fn new(sd: &'a mut SPIDriver<TX, RX>) -> Self {
+        let () = Self::CAPACITY_OK;
+        Self {
+            sd,
+            buf: [0; N],
+            len: 0,
+            err: None,
+        }
+    }
+
+    /// `select` queues a command to assert the chip select signal.
+    pub fn select(&mut self) -> &mut Self {
+        self.push(b's', &[])
+    }
+
+    /// `unselect` queues a command to release the chip select signal.
+    pub fn unselect(&mut self) -> &mut Self {
+        self.push(b'u', &[])
+    }
+
+    /// `set_cs` queues a command to put the chip select signal in the given
+    /// state.
+    pub fn set_cs(&mut self, state: impl Into<CsState>) -> &mut Self {
+        match state.into() {
+            CsState::Asserted => self.select(),
+            CsState::Released => self.unselect(),
+        }
+    }
+
+    /// `set_a` queues a command to set the level of the auxillary "A" pin.
+    pub fn set_a(&mut self, state: impl Into<PinState>) -> &mut Self {
+        self.push(b'a', &[state.into().protocol_arg()])
+    }
+
+    /// `set_b` queues a command to set the level of the auxillary "B" pin.
+    pub fn set_b(&mut self, state: impl Into<PinState>) -> &mut Self {
+        self.push(b'b', &[state.into().protocol_arg()])
+    }
+
+    /// `write` queues commands to write the given data out over the MOSI
+    /// line, splitting it into as many 64-byte protocol frames as necessary.
+    ///
+    /// If empty requests are rejected using `SPIDriver::set_reject_empty`
+    /// then queuing an empty write fails, and `commit` returns the error.
+    pub fn write(&mut self, data: &[u8]) -> &mut Self {
+        if self.err.is_none() {
+            if let Err(err) = self.sd.check_empty(data.len()) {
+                self.err = Some(err);
+            }
+        }
+        for chunk in data.chunks(self.sd.chunk_size) {
+            self.push(0xc0 - 1 + chunk.len() as u8, chunk);
+        }
+        self
+    }
+
+    /// `write_byte` is like `write` but queues only a single byte.
+    pub fn write_byte(&mut self, b: u8) -> &mut Self {
+        self.push(0xc0, &[b])
+    }
+
+    /// `commit` sends all of the queued commands to the SPIDriver and then
+    /// flushes the serial writer, returning the first error that occurred
+    /// either while queuing or while sending.
+    ///
+    /// After `commit` returns the batch is empty, and so can be reused to
+    /// queue more commands.
+    pub fn commit(&mut self) -> Result<(), Error<TXErr, RXErr>> {
+        if let Some(err) = self.err.take() {
+            self.len = 0;
+            return Err(err);
+        }
+        self.drain()?;
+        self.sd.ch.flush()
+    }
+
+    // push queues a single command. The buffer always contains only
+    // complete commands, as send_encoded requires.
+    fn push(&mut self, opcode: u8, args: &[u8]) -> &mut Self {
+        if self.err.is_some() {
+            return self;
+        }
+        let len = 1 + args.len();
+        if self.len + len > N {
+            if let Err(err) = self.drain() {
+                self.err = Some(err);
+                return self;
+            }
+        }
+        self.buf[self.len] = opcode;
+        self.buf[self.len + 1..self.len + len].copy_from_slice(args);
+        self.len += len;
+        self
+    }
+
+    fn drain(&mut self) -> Result<(), Error<TXErr, RXErr>> {
+        let data = &self.buf[..self.len];
+        self.len = 0;
+        trace!("sending batch of {} bytes", data.len());
+        self.sd.ch.send_encoded(data)
+    }
+}
@@ -0,0 +1,37 @@
+use embedded_hal::serial;
+
+use crate::{Capability, Error, ErrorKind, SPIDriver};
+
+impl<TX, RX, TXErr, RXErr> SPIDriver<TX, RX>
+where
+    TX: serial::Write<u8, Error = TXErr>,
+    RX: serial::Read<u8, Error = RXErr>,
+{
+    /// `upgrade_baud` switches the serial link to the highest baud rate that
+    /// the firmware supports and that is no higher than `max`, returning the
+    /// rate that was selected.
+    ///
+    /// After the SPIDriver switches, `upgrade_baud` calls `reconfigure` with
+    /// the serial writer, the serial reader, and the new rate, and the
+    /// function must change the rate of the underlying serial port to match
+    /// before returning.
+    ///
+    /// Switching the baud rate requires the `BaudSelect` capability, as
+    /// determined by `probe_capabilities`. No known SPIDriver firmware has
+    /// it, so `upgrade_baud` currently always returns the `Unsupported`
+    /// error without sending anything to the device or calling
+    /// `reconfigure`, and the link stays at the default 460800 baud.
+    pub fn upgrade_baud(
+        &mut self,
+        max: u32,
+        reconfigure: impl FnOnce(&mut TX, &mut RX, u32),
+    ) -> Result<u32, Error<TXErr, RXErr>> {
+        self.require(Capability::BaudSelect)?;
+
+        // Firmware that reports BaudSelect must also come with the command
+        // that selects a rate, which belongs here. Until then there is no
+        // way to switch, so we fail in the same way as require would.
+        let _ = (max, reconfigure);
+        Err(Error::new(ErrorKind::Unsupported(Capability::BaudSelect)))
+    }
+}
@@ -0,0 +1,253 @@
+use core::time::Duration;
+
+use embedded_hal::serial;
+
+use crate::channel::StatsClock;
+use crate::clock::ReadTimeout;
+use crate::observer::Observer;
+#[cfg(feature = "std")]
+use crate::WaitStrategy;
+use crate::{
+    Clock, Error, InitialPins, PinRoles, ProtocolObserver, RetryPolicy, SPIDriver, SpiMode,
+    Teardown, WireTap,
+};
+
+/// `SPIDriverBuilder` configures the initial setup of a connection to an
+/// SPIDriver device.
+///
+/// Using a builder is an alternative to calling `SPIDriver::new` and then
+/// calling various methods to prepare the device. The builder performs all
+/// of the requested setup steps in `build`, so that the caller either gets
+/// a fully-configured `SPIDriver` or an error.
+///
+/// ```rust,ignore
+/// let sd = SPIDriverBuilder::new()
+///     .initial_pins(InitialPins::default())
+///     .mode(SpiMode::Mode0)
+///     .build(tx, rx)?;
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct SPIDriverBuilder {
+    probe: bool,
+    pins: Option<InitialPins>,
+    mode: Option<SpiMode>,
+    roles: PinRoles,
+    auto_chunk: bool,
+    chunk_size: Option<usize>,
+    check_interval: Option<usize>,
+    teardown: Option<Teardown>,
+    read_timeout: Option<ReadTimeout>,
+    stats_clock: Option<StatsClock>,
+    poll_batch: Option<usize>,
+    retry: Option<RetryPolicy>,
+    observer: Option<Observer>,
+    strict_responses: bool,
+    reject_empty: bool,
+    low_latency: bool,
+    #[cfg(feature = "std")]
+    wait: WaitStrategy,
+    wire_tap: Option<WireTap>,
+}
+
+impl SPIDriverBuilder {
+    /// `new` creates a builder that performs no setup steps at all, and so
+    /// would produce the same result as `SPIDriver::new`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// `probe` enables or disables probing the device using
+    /// `SPIDriver::probe_capabilities` during `build`.
+    ///
+    /// Probing also verifies that the device is responding as an SPIDriver,
+    /// so `build` will fail if it isn't.
+    pub fn probe(mut self, enable: bool) -> Self {
+        self.probe = enable;
+        self
+    }
+
+    /// `initial_pins` requests that `build` drive the output pins to the
+    /// given states, as with `SPIDriver::new_with_pins`.
+    pub fn initial_pins(mut self, pins: InitialPins) -> Self {
+        self.pins = Some(pins);
+        self
+    }
+
+    /// `mode` requests that `build` check the given SPI mode using
+    /// `SPIDriver::set_mode`.
+    ///
+    /// The SPIDriver firmware only supports `SpiMode::Mode0`, so `build`
+    /// will fail with the `Unsupported` error for any other mode, before
+    /// sending anything to the device.
+    pub fn mode(mut self, mode: SpiMode) -> Self {
+        self.mode = Some(mode);
+        self
+    }
+
+    /// `pin_roles` assigns logical roles to the auxillary pins of the
+    /// resulting `SPIDriver`, as with `SPIDriver::set_pin_roles`.
+    pub fn pin_roles(mut self, roles: PinRoles) -> Self {
+        self.roles = roles;
+        self
+    }
+
+    /// `auto_chunk` enables or disables automatic chunking of long writes
+    /// and transfers, as with `SPIDriver::set_auto_chunk`.
+    pub fn auto_chunk(mut self, enable: bool) -> Self {
+        self.auto_chunk = enable;
+        self
+    }
+
+    /// `reject_empty` enables or disables rejection of empty writes and
+    /// transfers, as with `SPIDriver::set_reject_empty`.
+    pub fn reject_empty(mut self, enable: bool) -> Self {
+        self.reject_empty = enable;
+        self
+    }
+
+    /// `chunk_size` sets the largest number of data bytes to put in each
+    /// protocol frame when splitting data, as with
+    /// `SPIDriver::set_chunk_size`.
+    pub fn chunk_size(mut self, size: usize) -> Self {
+        self.chunk_size = Some(size);
+        self
+    }
+
+    /// `check_interval` enables checked mode with the given interval, as
+    /// with `SPIDriver::set_check_interval`.
+    pub fn check_interval(mut self, interval: usize) -> Self {
+        self.check_interval = Some(interval);
+        self
+    }
+
+    /// `teardown` enables teardown on drop for the resulting `SPIDriver`, as
+    /// with `SPIDriver::set_teardown`.
+    pub fn teardown(mut self, teardown: Teardown) -> Self {
+        self.teardown = Some(teardown);
+        self
+    }
+
+    /// `read_timeout` sets the read timeout for the resulting `SPIDriver`,
+    /// as with `SPIDriver::set_read_timeout`.
+    ///
+    /// The timeout also applies to any probing `build` does.
+    pub fn read_timeout(mut self, timeout: Duration, clock: &'static (dyn Clock + Sync)) -> Self {
+        self.read_timeout = Some(ReadTimeout::new(timeout, clock));
+        self
+    }
+
+    /// `stats_clock` sets the clock used to time each write and transfer
+    /// frame, as with `SPIDriver::set_stats_clock`.
+    pub fn stats_clock(mut self, clock: &'static (dyn Clock + Sync)) -> Self {
+        self.stats_clock = Some(StatsClock(clock));
+        self
+    }
+
+    /// `poll_batch` sets how many times the serial reader is polled between
+    /// checks of the read timeout, as with `SPIDriver::set_poll_batch`.
+    pub fn poll_batch(mut self, batch: usize) -> Self {
+        self.poll_batch = Some(batch);
+        self
+    }
+
+    /// `retry_policy` sets the policy for retrying frames that fail with
+    /// transient errors, as with `SPIDriver::set_retry_policy`.
+    ///
+    /// The policy doesn't apply to the setup steps `build` performs.
+    pub fn retry_policy(mut self, policy: RetryPolicy) -> Self {
+        self.retry = Some(policy);
+        self
+    }
+
+    /// `observer` sets the protocol observer for the resulting `SPIDriver`,
+    /// as with `SPIDriver::set_observer`.
+    ///
+    /// The observer is also notified about the commands `build` sends.
+    pub fn observer(mut self, observer: &'static (dyn ProtocolObserver + Sync)) -> Self {
+        self.observer = Some(Observer(observer));
+        self
+    }
+
+    /// `low_latency` enables or disables low-latency mode, as with
+    /// `SPIDriver::set_low_latency`.
+    pub fn low_latency(mut self, enable: bool) -> Self {
+        self.low_latency = enable;
+        self
+    }
+
+    /// `wait_strategy` selects what to do while waiting for a response, as
+    /// with `SPIDriver::set_wait_strategy`.
+    ///
+    /// This method is available only when the `std` feature is enabled.
+    #[cfg(feature = "std")]
+    pub fn wait_strategy(mut self, strategy: WaitStrategy) -> Self {
+        self.wait = strategy;
+        self
+    }
+
+    /// `strict_responses` enables or disables strict response mode, as with
+    /// `SPIDriver::set_strict_responses`.
+    pub fn strict_responses(mut self, enable: bool) -> Self {
+        self.strict_responses = enable;
+        self
+    }
+
+    /// `wire_tap` sets the function to call with the raw bytes written to
+    /// and read from the serial port, as with `SPIDriver::set_wire_tap`.
+    ///
+    /// The wire tap also sees the traffic from the setup steps `build`
+    /// performs.
+    pub fn wire_tap(mut self, tap: WireTap) -> Self {
+        self.wire_tap = Some(tap);
+        self
+    }
+
+    /// `build` consumes a serial `Write` and `Read` implementation to produce
+    /// an `SPIDriver` object, and then performs all of the configured setup
+    /// steps.
+    pub fn build<TX, RX, TXErr, RXErr>(
+        self,
+        tx: TX,
+        rx: RX,
+    ) -> Result<SPIDriver<TX, RX>, Error<TXErr, RXErr>>
+    where
+        TX: serial::Write<u8, Error = TXErr>,
+        RX: serial::Read<u8, Error = RXErr>,
+    {
+        let mut sd = SPIDriver::new(tx, rx);
+        sd.set_pin_roles(self.roles);
+        sd.set_auto_chunk(self.auto_chunk);
+        if let Some(size) = self.chunk_size {
+            sd.set_chunk_size(size);
+        }
+        sd.set_reject_empty(self.reject_empty);
+        sd.set_check_interval(self.check_interval);
+        sd.ch.set_read_timeout(self.read_timeout);
+        sd.set_stats_clock(self.stats_clock.map(|clock| clock.0));
+        if let Some(batch) = self.poll_batch {
+            sd.set_poll_batch(batch);
+        }
+        sd.ch.set_observer(self.observer);
+        sd.set_strict_responses(self.strict_responses);
+        sd.set_low_latency(self.low_latency);
+        #[cfg(feature = "std")]
+        sd.set_wait_strategy(self.wait);
+        sd.set_wire_tap(self.wire_tap);
+        if let Some(mode) = self.mode {
+            sd.set_mode(mode)?;
+        }
+        if self.probe {
+            sd.probe_capabilities()?;
+        }
+        if let Some(pins) = self.pins {
+            sd.set_pins_initial(pins)?;
+        }
+
+        // We enable teardown only once setup has succeeded, so that a
+        // failed build won't send anything more to a device that might not
+        // even be an SPIDriver.
+        sd.set_teardown(self.teardown);
+        sd.set_retry_policy(self.retry);
+        Ok(sd)
+    }
+}
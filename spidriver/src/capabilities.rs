@@ -0,0 +1,124 @@
+use crate::Identifier;
+
+/// `Capabilities` describes which of the optional protocol features the
+/// firmware of a connected SPIDriver supports.
+///
+/// Call `SPIDriver::probe_capabilities` to detect the capabilities of the
+/// connected device. Until then, an `SPIDriver` assumes the `baseline`
+/// capabilities that all SPIDriver firmware supports, and so methods that
+/// depend on optional features will return the `Unsupported` error rather
+/// than sending commands the device may not understand.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Capabilities {
+    /// `model` is the product identifier reported by the device, or `None`
+    /// if the capabilities were not obtained by probing a device.
+    pub model: Option<Identifier>,
+}
+
+impl Capabilities {
+    /// `baseline` returns the capabilities supported by all SPIDriver
+    /// firmware, with none of the optional features available.
+    pub const fn baseline() -> Self {
+        Self { model: None }
+    }
+
+    /// `supports` returns `true` if the given optional capability is
+    /// available.
+    ///
+    /// A capability is reported only if this crate knows the commands that
+    /// use it on the detected firmware. None of the firmware it knows about
+    /// has any of the optional features yet, so `supports` currently always
+    /// returns `false`.
+    pub fn supports(&self, cap: Capability) -> bool {
+        match cap {
+            Capability::ModeSelect | Capability::ClockSelect | Capability::BaudSelect => false,
+        }
+    }
+
+    // `for_model` returns the capabilities of the firmware that reports the
+    // given product identifier.
+    //
+    // Unrecognized products are assumed to have only the baseline
+    // capabilities, so that we never send them commands they might not
+    // understand.
+    pub(crate) fn for_model(model: Identifier) -> Self {
+        let mut ret = KNOWN_FIRMWARE
+            .iter()
+            .find(|(name, _)| *name == model.as_str())
+            .map(|(_, caps)| *caps)
+            .unwrap_or_else(Self::baseline);
+        ret.model = Some(model);
+        ret
+    }
+}
+
+impl Default for Capabilities {
+    fn default() -> Self {
+        Self::baseline()
+    }
+}
+
+impl core::fmt::Display for Capabilities {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self.model {
+            Some(model) => write!(f, "{}", model)?,
+            None => f.write_str("unknown model")?,
+        }
+        for cap in Capability::ALL {
+            write!(f, ", {} {}", cap, yes_no(self.supports(*cap)))?;
+        }
+        Ok(())
+    }
+}
+
+fn yes_no(v: bool) -> &'static str {
+    if v {
+        "yes"
+    } else {
+        "no"
+    }
+}
+
+/// `Capability` identifies one of the optional protocol features that only
+/// some SPIDriver firmware supports.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Capability {
+    /// `ModeSelect` is the ability to select the SPI mode.
+    ModeSelect,
+
+    /// `ClockSelect` is the ability to change the SPI clock rate.
+    ClockSelect,
+
+    /// `BaudSelect` is the ability to switch the serial link to a higher
+    /// baud rate.
+    BaudSelect,
+}
+
+impl Capability {
+    // ALL lists every capability, in the order they are described.
+    const ALL: &'static [Capability] = &[
+        Capability::ModeSelect,
+        Capability::ClockSelect,
+        Capability::BaudSelect,
+    ];
+}
+
+impl core::fmt::Display for Capability {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str(match self {
+            Capability::ModeSelect => "SPI mode selection",
+            Capability::ClockSelect => "SPI clock rate selection",
+            Capability::BaudSelect => "serial baud rate selection",
+        })
+    }
+}
+
+// The capabilities of each firmware we know about, keyed by the product
+// identifier it reports in its status report. The original SPIDriver
+// firmware supports only the baseline protocol. Firmware that supports an
+// optional feature needs both an entry here and a case in
+// Capabilities::supports, and the SPIDriver methods that check for that
+// capability must then send its commands.
+const KNOWN_FIRMWARE: &[(&str, Capabilities)] = &[("spidriver1", Capabilities::baseline())];
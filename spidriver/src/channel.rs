@@ -0,0 +1,594 @@
+use embedded_hal::serial;
+
+use crate::clock::ReadTimeout;
+use crate::observer::Observer;
+use crate::transport::BlockWriter;
+#[cfg(feature = "std")]
+use crate::wait::WaitStrategy;
+use crate::wiretap::{Direction, WireTap};
+use crate::Clock;
+use crate::{Context, Error, ErrorKind, Progress, ProtocolFault, Stats, MAX_FRAME};
+
+// The default number of times receive polls the serial reader before
+// checking the read timeout. See SPIDriver::set_poll_batch.
+pub(crate) const DEFAULT_POLL_BATCH: usize = 64;
+
+// The number of bytes that the channel can read ahead of its callers.
+const READ_AHEAD: usize = 2 * MAX_FRAME;
+
+// Channel is the lowest layer of the protocol implementation, dealing only
+// in individual bytes sent to and received from the serial port.
+#[derive(Debug)]
+pub(crate) struct Channel<TX: serial::Write<u8>, RX: serial::Read<u8>> {
+    pub(crate) tx: TX,
+    pub(crate) rx: RX,
+    timeout: Option<ReadTimeout>,
+    poll_batch: usize,
+    #[cfg(feature = "std")]
+    pub(crate) wait: WaitStrategy,
+
+    // Bytes already taken from the serial reader but not yet consumed.
+    ahead: RingBuffer<READ_AHEAD>,
+
+    // The command currently in progress, for error reporting.
+    current: Context,
+
+    pub(crate) stats: Stats,
+    observer: Option<Observer>,
+
+    // In strict mode, the channel checks that there is no unexpected data
+    // waiting to be read before each command and after each response.
+    strict: bool,
+
+    // In low-latency mode, the channel skips strict mode's checks and
+    // doesn't update the statistics, other than counting errors.
+    pub(crate) low_latency: bool,
+
+    // Set after any communication error, because the host and device may
+    // then be out of step. While poisoned, the channel refuses to send.
+    pub(crate) poisoned: bool,
+
+    // Set when a watchdog finds that the device has stopped responding.
+    // While unresponsive, the channel refuses to send.
+    pub(crate) unresponsive: bool,
+
+    tap: Option<WireTap>,
+    stats_clock: Option<StatsClock>,
+
+    // If set, commands are written with a single call to this function
+    // rather than one byte at a time. See SPIDriver::enable_block_writes.
+    pub(crate) block: Option<BlockWriter<TX>>,
+}
+
+impl<TX, RX, TXErr, RXErr> Channel<TX, RX>
+where
+    TX: serial::Write<u8, Error = TXErr>,
+    RX: serial::Read<u8, Error = RXErr>,
+{
+    pub fn new(tx: TX, rx: RX) -> Self {
+        Self {
+            tx,
+            rx,
+            timeout: None,
+            poll_batch: DEFAULT_POLL_BATCH,
+            #[cfg(feature = "std")]
+            wait: WaitStrategy::Spin,
+            ahead: RingBuffer::new(),
+            current: Context::default(),
+            stats: Stats::default(),
+            observer: None,
+            strict: false,
+            low_latency: false,
+            poisoned: false,
+            unresponsive: false,
+            tap: None,
+            stats_clock: None,
+            block: None,
+        }
+    }
+
+    // send begins a new protocol command by sending its opcode and the
+    // arguments that follow it.
+    pub fn send(&mut self, opcode: u8, args: &[u8]) -> Result<(), Error<TXErr, RXErr>> {
+        self.begin(opcode)?;
+        trace!(
+            "sending command 0x{:02x} with {} argument bytes",
+            opcode,
+            args.len()
+        );
+        // We assemble the whole command where possible, so that it can be
+        // written and passed to the tap all at once.
+        let mut buf = [0_u8; MAX_FRAME + 1];
+        if args.len() < buf.len() {
+            buf[0] = opcode;
+            buf[1..=args.len()].copy_from_slice(args);
+            self.write_all(&buf[..=args.len()])?;
+        } else {
+            self.write_all(&[opcode])?;
+            self.write_all(args)?;
+        }
+        self.observe_command(opcode, args);
+        Ok(())
+    }
+
+    // send_encoded sends a buffer of complete encoded commands with a single
+    // write. Only commands without a response can be sent this way, and
+    // only those whose lengths arg_len knows.
+    pub fn send_encoded(&mut self, data: &[u8]) -> Result<(), Error<TXErr, RXErr>> {
+        for (opcode, _) in commands(data) {
+            self.begin(opcode)?;
+        }
+        self.write_all(data)?;
+        for (opcode, args) in commands(data) {
+            self.observe_command(opcode, args);
+        }
+        Ok(())
+    }
+
+    // begin checks that a new command can be sent and records it as the
+    // command in progress, without writing anything.
+    pub fn begin(&mut self, opcode: u8) -> Result<(), Error<TXErr, RXErr>> {
+        if self.unresponsive {
+            return Err(Error::new(ErrorKind::DeviceUnresponsive));
+        }
+        if self.poisoned {
+            return Err(Error::new(ErrorKind::Poisoned));
+        }
+        self.current = Context {
+            command: opcode,
+            offset: 0,
+        };
+        if self.low_latency {
+            return Ok(());
+        }
+        if self.strict {
+            self.expect_idle(ProtocolFault::StaleData)?;
+        }
+        self.stats.commands = self.stats.commands.wrapping_add(1);
+        if opcode >= 0x80 {
+            // Opcodes from 0x80 upwards are all write or transfer frames.
+            self.stats.frames = self.stats.frames.wrapping_add(1);
+        }
+        Ok(())
+    }
+
+    // sent completes a command written one byte at a time by a caller that
+    // started it with begin, passing it to the wire tap and the observer.
+    pub fn sent(&mut self, opcode: u8, args: &[u8]) {
+        if let Some(tap) = self.tap {
+            tap(Direction::Outgoing, &[opcode]);
+            tap(Direction::Outgoing, args);
+        }
+        self.observe_command(opcode, args);
+    }
+
+    // observe_command notifies the observer about a command that has been
+    // sent.
+    fn observe_command(&self, opcode: u8, args: &[u8]) {
+        if let Some(observer) = self.observer {
+            observer.0.command(opcode, args);
+        }
+    }
+
+    // receive reads the response to the command most recently sent, filling
+    // the whole of the given buffer.
+    //
+    // Rather than blocking on each byte in turn, receive polls the serial
+    // reader in batches and consults the clock only after a whole batch of
+    // polls has produced nothing, since reading the clock can be
+    // comparatively expensive.
+    pub fn receive(&mut self, buf: &mut [u8]) -> Result<(), Error<TXErr, RXErr>> {
+        let expected = buf.len();
+        let mut received = 0;
+        let mut waiting_since = None;
+        let mut waited = 0;
+        while received < expected {
+            self.read_ahead()?;
+            let before = received;
+            let mut polls = 0;
+            while received < expected && polls < self.poll_batch {
+                match self.poll_read() {
+                    Ok(c) => {
+                        buf[received] = c;
+                        received += 1;
+                    }
+                    Err(nb::Error::WouldBlock) => polls += 1,
+                    Err(nb::Error::Other(err)) => return Err(self.rx_error(err)),
+                }
+            }
+            if !self.low_latency {
+                self.stats.bytes_read = self
+                    .stats
+                    .bytes_read
+                    .wrapping_add((received - before) as u64);
+            }
+            if received > before {
+                // Any progress restarts the timeout.
+                waiting_since = None;
+                waited = 0;
+                continue;
+            }
+            self.idle(waited);
+            waited += 1;
+
+            let timeout = match self.timeout {
+                Some(timeout) => timeout,
+                None => continue,
+            };
+            let now = timeout.clock.now_micros();
+            let start = *waiting_since.get_or_insert(now);
+            if now.wrapping_sub(start) < timeout.micros {
+                continue;
+            }
+            self.count_error();
+            if received == 0 {
+                debug!("timed out during {}", self.current);
+                return Err(Error::with_context(ErrorKind::Timeout, self.current));
+            }
+            // If the response stops part way through then we report that
+            // specifically, since it suggests that the device was
+            // disconnected or reset rather than just slow to start.
+            debug!("response truncated after {} bytes", received);
+            self.tap_incoming(&buf[..received]);
+            return Err(Error::with_context(
+                ErrorKind::Protocol(ProtocolFault::ShortResponse { expected, received }),
+                self.current,
+            ));
+        }
+        self.tap_incoming(buf);
+        if self.strict && !self.low_latency {
+            self.expect_idle(ProtocolFault::ExcessData)?;
+        }
+        trace!(
+            "received {} byte response to command 0x{:02x}",
+            buf.len(),
+            self.current.command
+        );
+        self.observe_response(buf);
+        Ok(())
+    }
+
+    // poll_read returns the next byte received, taking it from the
+    // read-ahead buffer if possible and otherwise polling the serial reader.
+    pub fn poll_read(&mut self) -> nb::Result<u8, RXErr> {
+        match self.ahead.pop() {
+            Some(c) => Ok(c),
+            None => self.rx.read(),
+        }
+    }
+
+    // read_ahead moves any bytes that the serial reader already has waiting
+    // into the read-ahead buffer, without blocking. Pulling everything that
+    // has arrived in one go avoids depending on how the operating system
+    // happens to split incoming data, and means that stale or excess data
+    // is seen as early as possible.
+    fn read_ahead(&mut self) -> Result<(), Error<TXErr, RXErr>> {
+        while !self.ahead.is_full() {
+            match self.rx.read() {
+                Ok(c) => self.ahead.push(c),
+                Err(nb::Error::WouldBlock) => break,
+                Err(nb::Error::Other(err)) => return Err(self.rx_error(err)),
+            }
+        }
+        Ok(())
+    }
+
+    // observe_response reports a response to the observer, for callers that
+    // read responses without using receive.
+    pub fn observe_response(&self, data: &[u8]) {
+        if let Some(observer) = self.observer {
+            observer.0.response(self.current.command, data);
+        }
+    }
+
+    // observe_progress reports progress through a multi-frame operation to
+    // the observer.
+    pub fn observe_progress(&self, progress: Progress) {
+        if let Some(observer) = self.observer {
+            observer.0.progress(progress);
+        }
+    }
+
+    pub fn set_observer(&mut self, observer: Option<Observer>) {
+        self.observer = observer;
+    }
+
+    pub fn set_stats_clock(&mut self, clock: Option<&'static (dyn Clock + Sync)>) {
+        self.stats_clock = clock.map(StatsClock);
+    }
+
+    // start_frame returns the time at which a write or transfer frame
+    // started, if frames are being timed.
+    pub fn start_frame(&self) -> Option<u64> {
+        if self.low_latency {
+            return None;
+        }
+        self.stats_clock.map(|clock| clock.0.now_micros())
+    }
+
+    // finish_frame records a completed write or transfer frame of the given
+    // length that started at the time returned by start_frame.
+    pub fn finish_frame(&mut self, start: Option<u64>, len: usize) {
+        if self.low_latency {
+            return;
+        }
+        let micros = self
+            .stats_clock
+            .zip(start)
+            .map(|(clock, start)| clock.0.now_micros().wrapping_sub(start));
+        self.stats.record_frame(len, micros);
+    }
+
+    // set_wire_tap sets or clears the function to call with raw traffic.
+    pub fn set_wire_tap(&mut self, tap: Option<WireTap>) {
+        self.tap = tap;
+    }
+
+    // tap_incoming passes bytes read from the serial port to the wire tap.
+    pub fn tap_incoming(&self, data: &[u8]) {
+        if let Some(tap) = self.tap {
+            tap(Direction::Incoming, data);
+        }
+    }
+
+    // set_strict enables or disables strict mode, returning the previous
+    // setting.
+    pub fn set_strict(&mut self, strict: bool) -> bool {
+        core::mem::replace(&mut self.strict, strict)
+    }
+
+    // expect_idle returns a Protocol error with the given fault if there is
+    // any data waiting to be read, discarding that data.
+    fn expect_idle(&mut self, fault: ProtocolFault) -> Result<(), Error<TXErr, RXErr>> {
+        match self.poll_read() {
+            Ok(c) => {
+                self.stats.bytes_read = self.stats.bytes_read.wrapping_add(1);
+                self.tap_incoming(&[c]);
+                self.drain()?;
+                Err(self.protocol_error(fault))
+            }
+            Err(nb::Error::WouldBlock) => Ok(()),
+            Err(nb::Error::Other(err)) => Err(self.rx_error(err)),
+        }
+    }
+
+    // protocol_error returns a Protocol error for the command in progress.
+    pub fn protocol_error(&mut self, fault: ProtocolFault) -> Error<TXErr, RXErr> {
+        self.count_error();
+        debug!("{} during {}", fault, self.current);
+        Error::with_context(ErrorKind::Protocol(fault), self.current)
+    }
+
+    fn tx_error(&mut self, source: TXErr) -> Error<TXErr, RXErr> {
+        self.count_error();
+        debug!("serial write failed during {}", self.current);
+        Error::write(source, self.current)
+    }
+
+    fn rx_error(&mut self, source: RXErr) -> Error<TXErr, RXErr> {
+        self.count_error();
+        debug!("serial read failed during {}", self.current);
+        Error::read(source, self.current)
+    }
+
+    // idle is called each time a batch of polls produces nothing, given the
+    // number of times it has already been called since anything was last
+    // received, to wait in accordance with the wait strategy.
+    fn idle(&self, waited: u32) {
+        #[cfg(feature = "std")]
+        self.wait.wait(waited);
+        #[cfg(not(feature = "std"))]
+        let _ = waited;
+    }
+
+    // count_error records that a communication error has occurred.
+    pub fn count_error(&mut self) {
+        self.stats.errors = self.stats.errors.wrapping_add(1);
+        self.poisoned = true;
+    }
+
+    pub fn set_read_timeout(&mut self, timeout: Option<ReadTimeout>) {
+        self.timeout = timeout;
+    }
+
+    pub fn set_poll_batch(&mut self, batch: usize) {
+        self.poll_batch = batch.max(1);
+    }
+
+    pub fn read(&mut self) -> Result<u8, Error<TXErr, RXErr>> {
+        let c = self.read_byte()?;
+        if !self.low_latency {
+            self.stats.bytes_read = self.stats.bytes_read.wrapping_add(1);
+        }
+        Ok(c)
+    }
+
+    fn read_byte(&mut self) -> Result<u8, Error<TXErr, RXErr>> {
+        let start = self.timeout.map(|timeout| timeout.clock.now_micros());
+        let mut polls = 0;
+        let mut waited = 0;
+        loop {
+            match self.poll_read() {
+                Ok(c) => return Ok(c),
+                Err(nb::Error::Other(err)) => return Err(self.rx_error(err)),
+                Err(nb::Error::WouldBlock) => {
+                    polls += 1;
+                    if polls < self.poll_batch {
+                        continue;
+                    }
+                    polls = 0;
+                    self.idle(waited);
+                    waited += 1;
+                    let (timeout, start) = match self.timeout.zip(start) {
+                        Some(timeout) => timeout,
+                        None => continue,
+                    };
+                    if timeout.clock.now_micros().wrapping_sub(start) >= timeout.micros {
+                        self.count_error();
+                        debug!("timed out during {}", self.current);
+                        return Err(Error::with_context(ErrorKind::Timeout, self.current));
+                    }
+                }
+            }
+        }
+    }
+
+    // drain discards any bytes that have already been received.
+    pub fn drain(&mut self) -> Result<(), Error<TXErr, RXErr>> {
+        loop {
+            match self.poll_read() {
+                Ok(c) => {
+                    if !self.low_latency {
+                        self.stats.bytes_read = self.stats.bytes_read.wrapping_add(1);
+                    }
+                    self.tap_incoming(&[c]);
+                }
+                Err(nb::Error::WouldBlock) => return Ok(()),
+                Err(nb::Error::Other(err)) => return Err(self.rx_error(err)),
+            }
+        }
+    }
+
+    // try_write writes a single byte if the serial writer can accept it
+    // without blocking.
+    pub fn try_write(&mut self, c: u8) -> nb::Result<(), Error<TXErr, RXErr>> {
+        match self.tx.write(c) {
+            Ok(()) => {
+                if !self.low_latency {
+                    self.stats.bytes_written = self.stats.bytes_written.wrapping_add(1);
+                }
+                Ok(())
+            }
+            Err(nb::Error::WouldBlock) => Err(nb::Error::WouldBlock),
+            Err(nb::Error::Other(err)) => Err(nb::Error::Other(self.tx_error(err))),
+        }
+    }
+
+    // try_read reads a single byte if one is available.
+    pub fn try_read(&mut self) -> nb::Result<u8, Error<TXErr, RXErr>> {
+        match self.poll_read() {
+            Ok(c) => {
+                if !self.low_latency {
+                    self.stats.bytes_read = self.stats.bytes_read.wrapping_add(1);
+                }
+                Ok(c)
+            }
+            Err(nb::Error::WouldBlock) => Err(nb::Error::WouldBlock),
+            Err(nb::Error::Other(err)) => Err(nb::Error::Other(self.rx_error(err))),
+        }
+    }
+
+    pub fn write(&mut self, c: u8) -> Result<(), Error<TXErr, RXErr>> {
+        nb::block!(self.tx.write(c)).map_err(|err| self.tx_error(err))?;
+        if !self.low_latency {
+            self.stats.bytes_written = self.stats.bytes_written.wrapping_add(1);
+        }
+        Ok(())
+    }
+
+    // write_all writes the given bytes and passes them to the wire tap.
+    pub fn write_all(&mut self, data: &[u8]) -> Result<(), Error<TXErr, RXErr>> {
+        match self.block {
+            Some(block) => {
+                (block.0)(&mut self.tx, data).map_err(|err| self.tx_error(err))?;
+                if !self.low_latency {
+                    self.stats.bytes_written =
+                        self.stats.bytes_written.wrapping_add(data.len() as u64);
+                }
+            }
+            None => {
+                for c in data {
+                    self.write(*c)?;
+                }
+            }
+        }
+        if let Some(tap) = self.tap {
+            tap(Direction::Outgoing, data);
+        }
+        Ok(())
+    }
+
+    // read_all fills the given buffer with bytes read from the serial port
+    // and passes them to the wire tap.
+    pub fn read_all(&mut self, buf: &mut [u8]) -> Result<(), Error<TXErr, RXErr>> {
+        for c in buf.iter_mut() {
+            *c = self.read()?;
+        }
+        self.tap_incoming(buf);
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> Result<(), Error<TXErr, RXErr>> {
+        nb::block!(self.tx.flush()).map_err(|err| self.tx_error(err))
+    }
+}
+
+// StatsClock wraps the clock used to time frames so that the types that
+// hold one can still implement Debug.
+#[derive(Clone, Copy)]
+pub(crate) struct StatsClock(pub &'static (dyn Clock + Sync));
+
+impl core::fmt::Debug for StatsClock {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str("StatsClock")
+    }
+}
+
+// commands splits a buffer of complete encoded commands into the opcode and
+// arguments of each command.
+fn commands(mut data: &[u8]) -> impl Iterator<Item = (u8, &[u8])> {
+    core::iter::from_fn(move || {
+        let (&opcode, rest) = data.split_first()?;
+        let (args, next) = rest.split_at(arg_len(opcode));
+        data = next;
+        Some((opcode, args))
+    })
+}
+
+// arg_len returns the number of argument bytes that follow the given opcode,
+// for the commands that send_encoded can send.
+fn arg_len(opcode: u8) -> usize {
+    match opcode {
+        b'a' | b'b' => 1,
+        0xc0..=0xff => (opcode - 0xc0) as usize + 1,
+        _ => 0,
+    }
+}
+
+// RingBuffer is a fixed-capacity first-in-first-out queue of bytes.
+#[derive(Debug)]
+struct RingBuffer<const N: usize> {
+    buf: [u8; N],
+    start: usize,
+    len: usize,
+}
+
+impl<const N: usize> RingBuffer<N> {
+    fn new() -> Self {
+        Self {
+            buf: [0; N],
+            start: 0,
+            len: 0,
+        }
+    }
+
+    fn is_full(&self) -> bool {
+        self.len == N
+    }
+
+    // push appends a byte, which the caller must ensure there is room for.
+    fn push(&mut self, c: u8) {
+        self.buf[(self.start + self.len) % N] = c;
+        self.len += 1;
+    }
+
+    fn pop(&mut self) -> Option<u8> {
+        if self.len == 0 {
+            return None;
+        }
+        let c = self.buf[self.start];
+        self.start = (self.start + 1) % N;
+        self.len -= 1;
+        Some(c)
+    }
+}
@@ -0,0 +1,56 @@
+/// `Clock` is a source of monotonic time, used to implement timeouts.
+///
+/// `SPIDriver` doesn't assume any particular platform, so any operation that
+/// needs to measure time takes a `Clock` provided by the caller. On a
+/// microcontroller this would typically be implemented in terms of a
+/// hardware timer. When the `std` feature is enabled, `StdClock` implements
+/// `Clock` using the standard library.
+pub trait Clock {
+    /// `now_micros` returns the number of microseconds elapsed since some
+    /// arbitrary fixed point in time.
+    ///
+    /// The result must never decrease, except that it may wrap around on
+    /// overflow.
+    fn now_micros(&self) -> u64;
+}
+
+// ReadTimeout is a timeout together with the clock used to measure it.
+#[derive(Clone, Copy)]
+pub(crate) struct ReadTimeout {
+    pub micros: u64,
+    pub clock: &'static (dyn Clock + Sync),
+}
+
+impl ReadTimeout {
+    pub fn new(timeout: core::time::Duration, clock: &'static (dyn Clock + Sync)) -> Self {
+        Self {
+            micros: timeout.as_micros() as u64,
+            clock,
+        }
+    }
+}
+
+impl core::fmt::Debug for ReadTimeout {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("ReadTimeout")
+            .field("micros", &self.micros)
+            .finish()
+    }
+}
+
+/// `StdClock` implements `Clock` using the standard library's monotonic
+/// clock.
+///
+/// This type is available only when the `std` feature is enabled.
+#[cfg(feature = "std")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct StdClock;
+
+#[cfg(feature = "std")]
+impl Clock for StdClock {
+    fn now_micros(&self) -> u64 {
+        static EPOCH: std::sync::OnceLock<std::time::Instant> = std::sync::OnceLock::new();
+        let epoch = EPOCH.get_or_init(std::time::Instant::now);
+        epoch.elapsed().as_micros() as u64
+    }
+}
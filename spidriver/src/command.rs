@@ -0,0 +1,95 @@
+use embedded_hal::serial;
+
+use crate::{CsState, DeviceStatus, Error, PinState, SPIDriver, SpiMode};
+
+/// `Command` represents one of the operations an `SPIDriver` can perform, as
+/// data.
+///
+/// Pass a `Command` to `SPIDriver::execute` to perform it. This allows
+/// describing device interactions as tables or scripts rather than as code,
+/// which can be useful for table-driven device drivers and test harnesses.
+#[derive(Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Command<'a> {
+    /// `Echo` corresponds to `SPIDriver::echo`.
+    Echo(u8),
+
+    /// `Status` corresponds to `SPIDriver::status`.
+    Status,
+
+    /// `Select` corresponds to `SPIDriver::select`.
+    Select,
+
+    /// `Unselect` corresponds to `SPIDriver::unselect`.
+    Unselect,
+
+    /// `SetCs` corresponds to `SPIDriver::set_cs`.
+    SetCs(CsState),
+
+    /// `SetA` corresponds to `SPIDriver::set_a`.
+    SetA(PinState),
+
+    /// `SetB` corresponds to `SPIDriver::set_b`.
+    SetB(PinState),
+
+    /// `SetMode` corresponds to `SPIDriver::set_mode`.
+    SetMode(SpiMode),
+
+    /// `Write` corresponds to `SPIDriver::write_all`, and so accepts data of
+    /// any length.
+    Write(&'a [u8]),
+
+    /// `Transfer` corresponds to `SPIDriver::transfer_all`, and so accepts
+    /// data of any length. The buffer is updated in-place with the data
+    /// returned by the target device.
+    Transfer(&'a mut [u8]),
+
+    /// `ClockBytes` corresponds to `SPIDriver::clock_bytes`.
+    ClockBytes(usize),
+
+    /// `Disconnect` corresponds to `SPIDriver::disconnect`.
+    Disconnect,
+}
+
+/// `Response` is the result of executing a `Command` using
+/// `SPIDriver::execute`.
+///
+/// Most commands produce no response other than success. A `Transfer`
+/// command's response is written into its buffer, so it also produces
+/// `Response::None`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Response {
+    /// `None` is the response to commands that don't return any data.
+    None,
+
+    /// `Echo` is the response to `Command::Echo`.
+    Echo(u8),
+
+    /// `Status` is the response to `Command::Status`.
+    Status(DeviceStatus),
+}
+
+impl<TX, RX, TXErr, RXErr> SPIDriver<TX, RX>
+where
+    TX: serial::Write<u8, Error = TXErr>,
+    RX: serial::Read<u8, Error = RXErr>,
+{
+    /// `execute` performs the operation described by the given `Command`.
+    pub fn execute(&mut self, cmd: Command<'_>) -> Result<Response, Error<TXErr, RXErr>> {
+        match cmd {
+            Command::Echo(c) => self.echo(c).map(Response::Echo),
+            Command::Status => self.status().map(Response::Status),
+            Command::Select => self.select().map(|_| Response::None),
+            Command::Unselect => self.unselect().map(|_| Response::None),
+            Command::SetCs(state) => self.set_cs(state).map(|_| Response::None),
+            Command::SetA(state) => self.set_a(state).map(|_| Response::None),
+            Command::SetB(state) => self.set_b(state).map(|_| Response::None),
+            Command::SetMode(mode) => self.set_mode(mode).map(|_| Response::None),
+            Command::Write(data) => self.write_all(data).map(|_| Response::None),
+            Command::Transfer(data) => self.transfer_all(data).map(|_| Response::None),
+            Command::ClockBytes(n) => self.clock_bytes(n).map(|_| Response::None),
+            Command::Disconnect => self.disconnect().map(|_| Response::None),
+        }
+    }
+}
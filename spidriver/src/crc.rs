@@ -0,0 +1,164 @@
+use embedded_hal::serial;
+
+use crate::{Error, ErrorKind, SPIDriver, MAX_FRAME};
+
+/// `CrcCheckedSpiDriver` wraps an `SPIDriver` to verify the integrity of
+/// the data it sends, at the expense of throughput.
+///
+/// The SPIDriver maintains a running CRC-16-CCITT of the data it sends to
+/// the target device, and reports it in its status report. After each
+/// write or transfer, the wrapper requests a status report and compares the
+/// device's CRC with one computed on the host from the data that should
+/// have been sent. If they differ then the data was corrupted somewhere
+/// between the host and the SPI bus, and the operation fails with an error
+/// of kind `ErrorKind::CrcMismatch`.
+///
+/// Each check costs a status round-trip, which transfers 80 bytes from the
+/// device, so this is best suited to noisy links where data integrity
+/// matters more than speed. The verification is skipped while the wrapped
+/// `SPIDriver` is in low-latency mode, as described for
+/// `SPIDriver::set_low_latency`.
+///
+/// The host-side CRC is only correct if all data is sent through the
+/// wrapper. After using `inner_mut` to send data directly, or after any
+/// other error, call `rebase` to resynchronize the host-side CRC with the
+/// device.
+#[derive(Debug)]
+pub struct CrcCheckedSpiDriver<TX, RX>
+where
+    TX: serial::Write<u8>,
+    RX: serial::Read<u8>,
+{
+    sd: SPIDriver<TX, RX>,
+    crc: u16,
+}
+
+impl<TX, RX, TXErr, RXErr> CrcCheckedSpiDriver<TX, RX>
+where
+    TX: serial::Write<u8, Error = TXErr>,
+    RX: serial::Read<u8, Error = RXErr>,
+{
+    /// `new` wraps the given `SPIDriver`, requesting a status report to
+    /// learn the device's current CRC.
+    pub fn new(sd: SPIDriver<TX, RX>) -> Result<Self, Error<TXErr, RXErr>> {
+        let mut ret = Self { sd, crc: 0 };
+        ret.rebase()?;
+        Ok(ret)
+    }
+
+    /// `rebase` requests a status report and adopts the device's current
+    /// CRC as the starting point for subsequent checks.
+    pub fn rebase(&mut self) -> Result<(), Error<TXErr, RXErr>> {
+        self.crc = self.sd.status()?.crc;
+        Ok(())
+    }
+
+    /// `select` asserts the chip select signal, as with `SPIDriver::select`.
+    pub fn select(&mut self) -> Result<(), Error<TXErr, RXErr>> {
+        self.sd.select()
+    }
+
+    /// `unselect` releases the chip select signal, as with
+    /// `SPIDriver::unselect`.
+    pub fn unselect(&mut self) -> Result<(), Error<TXErr, RXErr>> {
+        self.sd.unselect()
+    }
+
+    /// `write` is like `SPIDriver::write`, but verifies the CRC afterwards.
+    pub fn write(&mut self, data: &[u8]) -> Result<(), Error<TXErr, RXErr>> {
+        self.sd.write(data)?;
+        self.check(data)
+    }
+
+    /// `transfer` is like `SPIDriver::transfer`, but verifies the CRC
+    /// afterwards.
+    pub fn transfer<'v>(&mut self, data: &'v mut [u8]) -> Result<&'v [u8], Error<TXErr, RXErr>> {
+        if data.len() > MAX_FRAME && !self.sd.auto_chunk {
+            // Let the SPIDriver reject the request before we update the CRC.
+            return self.sd.transfer(data);
+        }
+        self.update(data);
+        self.sd.transfer(data)?;
+        self.verify()?;
+        Ok(data)
+    }
+
+    /// `write_all` is like `SPIDriver::write_all`, but verifies the CRC once
+    /// all of the data has been sent.
+    pub fn write_all(&mut self, data: &[u8]) -> Result<(), Error<TXErr, RXErr>> {
+        self.sd.write_all(data)?;
+        self.check(data)
+    }
+
+    /// `transfer_all` is like `SPIDriver::transfer_all`, but verifies the
+    /// CRC once all of the data has been transferred.
+    pub fn transfer_all<'v>(
+        &mut self,
+        data: &'v mut [u8],
+    ) -> Result<&'v [u8], Error<TXErr, RXErr>> {
+        self.update(data);
+        self.sd.transfer_all(data)?;
+        self.verify()?;
+        Ok(data)
+    }
+
+    /// `inner` returns a shared reference to the wrapped `SPIDriver`.
+    pub fn inner(&self) -> &SPIDriver<TX, RX> {
+        &self.sd
+    }
+
+    /// `inner_mut` returns a mutable reference to the wrapped `SPIDriver`.
+    ///
+    /// Data sent directly through the `SPIDriver` isn't included in the
+    /// host-side CRC, so call `rebase` afterwards.
+    pub fn inner_mut(&mut self) -> &mut SPIDriver<TX, RX> {
+        &mut self.sd
+    }
+
+    /// `into_inner` consumes the wrapper and returns the `SPIDriver`.
+    pub fn into_inner(self) -> SPIDriver<TX, RX> {
+        self.sd
+    }
+
+    fn check(&mut self, sent: &[u8]) -> Result<(), Error<TXErr, RXErr>> {
+        self.update(sent);
+        self.verify()
+    }
+
+    fn update(&mut self, sent: &[u8]) {
+        for c in sent {
+            self.crc = crc_ccitt_update(self.crc, *c);
+        }
+    }
+
+    fn verify(&mut self) -> Result<(), Error<TXErr, RXErr>> {
+        if self.sd.ch.low_latency {
+            // The device keeps its running CRC regardless, so the host-side
+            // CRC remains valid for checks after low-latency mode ends.
+            return Ok(());
+        }
+        let actual = self.sd.status()?.crc;
+        if actual != self.crc {
+            let expected = self.crc;
+            // Adopt the device's CRC so that one corrupted operation doesn't
+            // cause all subsequent checks to fail too.
+            self.crc = actual;
+            return Err(Error::new(ErrorKind::CrcMismatch { expected, actual }));
+        }
+        Ok(())
+    }
+}
+
+// crc_ccitt_update adds one byte to a CRC-16-CCITT (polynomial 0x1021,
+// most significant bit first), matching the SPIDriver firmware.
+fn crc_ccitt_update(crc: u16, c: u8) -> u16 {
+    let mut crc = crc ^ ((c as u16) << 8);
+    for _ in 0..8 {
+        crc = if crc & 0x8000 != 0 {
+            (crc << 1) ^ 0x1021
+        } else {
+            crc << 1
+        };
+    }
+    crc
+}
@@ -0,0 +1,72 @@
+use std::sync::mpsc;
+use std::thread;
+
+use embedded_hal::serial;
+
+use crate::{Error, SPIDriver};
+
+impl<TX, RX, TXErr, RXErr> SPIDriver<TX, RX>
+where
+    TX: serial::Write<u8, Error = TXErr> + Send,
+    RX: serial::Read<u8, Error = RXErr> + Send,
+    TXErr: Send,
+    RXErr: Send,
+{
+    /// `stream_double_buffered` is like `stream_frames`, but renders each
+    /// frame while the previous one is still being sent, using two frame
+    /// buffers of `len` bytes.
+    ///
+    /// `render` is called repeatedly on the calling thread to fill a buffer
+    /// with the next frame, and should return `false` once there are no more
+    /// frames to send, in which case the buffer it was given is discarded.
+    /// Meanwhile, a second thread sends each completed frame to the
+    /// SPIDriver, so rendering one frame overlaps with sending the previous
+    /// one. The buffer passed to `render` contains an earlier frame, which
+    /// can save work when only part of the display changes.
+    ///
+    /// Returns once `render` returns `false` and the last frame has been
+    /// sent, or as soon as possible after sending a frame fails.
+    ///
+    /// This method is available only when the `std` feature is enabled.
+    pub fn stream_double_buffered(
+        &mut self,
+        len: usize,
+        command: Option<u8>,
+        mut render: impl FnMut(&mut [u8]) -> bool,
+    ) -> Result<(), Error<TXErr, RXErr>> {
+        let mut frames = self.stream_frames(len, command)?;
+        let (filled_tx, filled_rx) = mpsc::channel::<Vec<u8>>();
+        let (free_tx, free_rx) = mpsc::channel();
+        for _ in 0..2 {
+            free_tx.send(vec![0_u8; len]).unwrap();
+        }
+
+        thread::scope(|scope| {
+            let sender = scope.spawn(move || {
+                for buf in filled_rx {
+                    frames.push(&buf)?;
+                    // The renderer may already have stopped, in which case
+                    // we just let the buffer go.
+                    let _ = free_tx.send(buf);
+                }
+                Ok(())
+            });
+
+            // The free buffers run out only if the sending thread has
+            // stopped early due to an error, which we'll then return below.
+            while let Ok(mut buf) = free_rx.recv() {
+                if !render(&mut buf) {
+                    break;
+                }
+                if filled_tx.send(buf).is_err() {
+                    break;
+                }
+            }
+            drop(filled_tx);
+            match sender.join() {
+                Ok(result) => result,
+                Err(panic) => std::panic::resume_unwind(panic),
+            }
+        })
+    }
+}
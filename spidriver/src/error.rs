@@ -0,0 +1,495 @@
+use crate::{Capability, PinRole};
+
+/// `Error` represents communication errors.
+///
+/// Each error has an `ErrorKind` describing what went wrong, a `Context`
+/// describing the command that was in progress if the error occurred while
+/// communicating with the device, and the error returned by the underlying
+/// serial implementation if that was the cause.
+#[derive(Debug)]
+#[non_exhaustive]
+pub struct Error<TXErr, RXErr> {
+    kind: ErrorKind,
+    context: Option<Context>,
+    source: Option<TransportError<TXErr, RXErr>>,
+}
+
+/// `ErrorKind` describes the different kinds of `Error`.
+///
+/// New kinds may be added in future releases, so code that matches on
+/// `ErrorKind` must include a wildcard arm.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[non_exhaustive]
+pub enum ErrorKind {
+    /// `Protocol` indicates that the library receieved an invalid or unexpected
+    /// response from the SPIDriver in response to a request.
+    ///
+    /// The data describes what was wrong with the response.
+    Protocol(ProtocolFault),
+
+    /// `Request` indicates that the caller provided invalid arguments that
+    /// could not be checked at compile time.
+    ///
+    /// The data describes what was wrong with the request.
+    Request(RequestError),
+
+    /// `Unsupported` indicates that the caller requested an operation that
+    /// the firmware of the connected SPIDriver doesn't support.
+    ///
+    /// The data is the capability that the operation requires.
+    Unsupported(Capability),
+
+    /// `Timeout` indicates that the SPIDriver didn't respond within the
+    /// read timeout set using `SPIDriver::set_read_timeout`.
+    Timeout,
+
+    /// `Poisoned` indicates that an earlier communication error left the
+    /// host and the SPIDriver in an unknown state, and so the operation was
+    /// rejected without sending anything to the device.
+    ///
+    /// Call `SPIDriver::resync` or `SPIDriver::reset_state` to recover.
+    Poisoned,
+
+    /// `DeviceUnresponsive` indicates that a `Watchdog` found that the
+    /// SPIDriver had stopped answering keep-alive checks, and so the
+    /// operation was rejected without sending anything to the device.
+    ///
+    /// Call `SPIDriver::resync` or `SPIDriver::reset_state` to recover once
+    /// the device is available again.
+    DeviceUnresponsive,
+
+    /// `CrcMismatch` indicates that the CRC the SPIDriver computed over the
+    /// data it sent differs from the CRC computed on the host, as detected
+    /// by `CrcCheckedSpiDriver`.
+    CrcMismatch { expected: u16, actual: u16 },
+
+    /// `Write` indicates that the underlying serial write object returned an
+    /// error, which is available as the error's transport error.
+    Write,
+
+    /// `Read` indicates that the underlying serial read object returned an
+    /// error, which is available as the error's transport error.
+    Read,
+}
+
+/// `TransportError` is an error returned by the underlying serial
+/// implementation, as returned from `Error::transport_error`.
+#[derive(Debug)]
+pub enum TransportError<TXErr, RXErr> {
+    /// `Write` is an error from the serial write object.
+    Write(TXErr),
+
+    /// `Read` is an error from the serial read object.
+    Read(RXErr),
+}
+
+impl<TXErr, RXErr> Error<TXErr, RXErr> {
+    pub(crate) fn new(kind: ErrorKind) -> Self {
+        Self {
+            kind,
+            context: None,
+            source: None,
+        }
+    }
+
+    pub(crate) fn with_context(kind: ErrorKind, context: Context) -> Self {
+        Self {
+            kind,
+            context: Some(context),
+            source: None,
+        }
+    }
+
+    pub(crate) fn write(source: TXErr, context: Context) -> Self {
+        Self {
+            kind: ErrorKind::Write,
+            context: Some(context),
+            source: Some(TransportError::Write(source)),
+        }
+    }
+
+    pub(crate) fn read(source: RXErr, context: Context) -> Self {
+        Self {
+            kind: ErrorKind::Read,
+            context: Some(context),
+            source: Some(TransportError::Read(source)),
+        }
+    }
+
+    /// `kind` returns the kind of the error.
+    pub fn kind(&self) -> ErrorKind {
+        self.kind
+    }
+
+    /// `context` returns information about the command that was in progress
+    /// when the error occurred, if the error occurred while communicating
+    /// with the device.
+    pub fn context(&self) -> Option<Context> {
+        self.context
+    }
+
+    /// `transport_error` returns the error returned by the underlying serial
+    /// implementation, if that was the cause of the error.
+    pub fn transport_error(&self) -> Option<&TransportError<TXErr, RXErr>> {
+        self.source.as_ref()
+    }
+
+    /// `into_transport_error` is like `transport_error`, but consumes the
+    /// error to return the underlying serial error by value.
+    pub fn into_transport_error(self) -> Option<TransportError<TXErr, RXErr>> {
+        self.source
+    }
+
+    /// `is_transient` returns `true` if the error was caused by a failure of
+    /// the serial connection, such as a brief USB disconnection, rather than
+    /// by an invalid request or an unexpected response from the device.
+    ///
+    /// Operations that fail with transient errors may succeed if retried
+    /// after resynchronizing with `SPIDriver::resync`.
+    pub fn is_transient(&self) -> bool {
+        self.kind.is_transient()
+    }
+
+    // at_offset records that the error occurred in the frame starting at the
+    // given offset within a larger chunked operation.
+    pub(crate) fn at_offset(mut self, offset: usize) -> Self {
+        if let Some(context) = &mut self.context {
+            context.offset += offset;
+        }
+        self
+    }
+}
+
+impl ErrorKind {
+    fn is_transient(&self) -> bool {
+        matches!(
+            self,
+            ErrorKind::Timeout | ErrorKind::Write | ErrorKind::Read
+        )
+    }
+}
+
+impl core::fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            ErrorKind::Protocol(fault) => write!(f, "{} from SPIDriver", fault),
+            ErrorKind::Request(err) => write!(f, "invalid request: {}", err),
+            ErrorKind::Unsupported(cap) => write!(f, "SPIDriver firmware doesn't support {}", cap),
+            ErrorKind::Timeout => f.write_str("timed out waiting for SPIDriver"),
+            ErrorKind::Poisoned => f.write_str("SPIDriver connection is in an unknown state"),
+            ErrorKind::DeviceUnresponsive => f.write_str("SPIDriver stopped responding"),
+            ErrorKind::CrcMismatch { expected, actual } => write!(
+                f,
+                "SPIDriver reported CRC {:04x} but expected {:04x}",
+                actual, expected
+            ),
+            ErrorKind::Write => f.write_str("failed to write to serial port"),
+            ErrorKind::Read => f.write_str("failed to read from serial port"),
+        }
+    }
+}
+
+/// `Context` describes the command that was in progress when an error
+/// occurred while communicating with the device.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Context {
+    /// `command` is the opcode of the protocol command that was in progress.
+    /// For write and transfer commands, the opcode also encodes the length
+    /// of the frame.
+    pub command: u8,
+
+    /// `offset` is the position within the data of the operation at which
+    /// the frame that failed begins. It is non-zero only for operations
+    /// that are split into multiple frames, such as `write_all`.
+    pub offset: usize,
+}
+
+impl core::fmt::Display for Context {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "command 0x{:02x}", self.command)?;
+        if self.offset != 0 {
+            write!(f, " at offset {}", self.offset)?;
+        }
+        Ok(())
+    }
+}
+
+impl<TXErr, RXErr> core::fmt::Display for Error<TXErr, RXErr> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write_error(f, &self.kind, &self.context)
+    }
+}
+
+fn write_error(
+    f: &mut core::fmt::Formatter<'_>,
+    kind: &ErrorKind,
+    context: &Option<Context>,
+) -> core::fmt::Result {
+    match context {
+        Some(context) => write!(f, "{} during {}", kind, context),
+        None => write!(f, "{}", kind),
+    }
+}
+
+/// `ProtocolFault` describes why a response was rejected with an error of
+/// kind `ErrorKind::Protocol`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[non_exhaustive]
+pub enum ProtocolFault {
+    /// `UnexpectedResponse` indicates that the response had the expected
+    /// length but the wrong content, such as an echo command returning a
+    /// different byte or a status report that couldn't be parsed.
+    UnexpectedResponse,
+
+    /// `StaleData` indicates that data was already waiting to be read before
+    /// a command was sent, which suggests that an earlier response was
+    /// longer than expected. Reported only in strict response mode.
+    StaleData,
+
+    /// `ExcessData` indicates that more data arrived immediately after a
+    /// complete response. Reported only in strict response mode.
+    ExcessData,
+
+    /// `ShortResponse` indicates that the device stopped sending part way
+    /// through a response, such as when the cable is disconnected during a
+    /// transfer. `received` is the number of bytes that arrived before the
+    /// read timeout expired.
+    ///
+    /// Truncated responses can only be detected when a read timeout is set
+    /// using `SPIDriver::set_read_timeout`. Without one, reading the
+    /// response blocks until the remaining bytes arrive.
+    ShortResponse { expected: usize, received: usize },
+}
+
+impl core::fmt::Display for ProtocolFault {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            ProtocolFault::UnexpectedResponse => f.write_str("unexpected response"),
+            ProtocolFault::StaleData => f.write_str("stale data"),
+            ProtocolFault::ExcessData => f.write_str("excess data"),
+            ProtocolFault::ShortResponse { expected, received } => write!(
+                f,
+                "response truncated after {} of {} bytes",
+                received, expected
+            ),
+        }
+    }
+}
+
+/// `Error` implements `std::error::Error` when the `std` feature is enabled,
+/// with the underlying serial errors as sources.
+#[cfg(feature = "std")]
+impl<TXErr, RXErr> std::error::Error for Error<TXErr, RXErr>
+where
+    TXErr: std::error::Error + 'static,
+    RXErr: std::error::Error + 'static,
+{
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match &self.source {
+            Some(TransportError::Write(err)) => Some(err),
+            Some(TransportError::Read(err)) => Some(err),
+            None => None,
+        }
+    }
+}
+
+/// `Error` implements the embedded-hal 1.0 SPI `Error` trait when the `eh1`
+/// feature is enabled, so that generic drivers can classify failures.
+///
+/// Errors from the underlying serial reader are reported as `Overrun`,
+/// because they typically mean that some of the data returned from the
+/// target device was lost. All other errors are reported as `Other`.
+#[cfg(feature = "eh1")]
+impl<TXErr, RXErr> embedded_hal_1::spi::Error for Error<TXErr, RXErr>
+where
+    TXErr: core::fmt::Debug,
+    RXErr: core::fmt::Debug,
+{
+    fn kind(&self) -> embedded_hal_1::spi::ErrorKind {
+        self.kind.spi_kind()
+    }
+}
+
+#[cfg(feature = "eh1")]
+impl ErrorKind {
+    fn spi_kind(&self) -> embedded_hal_1::spi::ErrorKind {
+        match self {
+            ErrorKind::Read => embedded_hal_1::spi::ErrorKind::Overrun,
+            _ => embedded_hal_1::spi::ErrorKind::Other,
+        }
+    }
+}
+
+// The defmt implementations for the error types omit the underlying serial
+// errors, because serial implementations don't generally implement Format.
+#[cfg(feature = "defmt")]
+impl<TXErr, RXErr> defmt::Format for Error<TXErr, RXErr> {
+    fn format(&self, f: defmt::Formatter<'_>) {
+        defmt::write!(f, "{}", self.kind);
+        if let Some(context) = self.context {
+            defmt::write!(f, " during {}", context);
+        }
+    }
+}
+
+/// `SimpleError` is a variant of `Error` for the common case where the
+/// serial write and read objects have the same error type, such as when
+/// both are halves of the same serial port.
+///
+/// `SimpleError` has only one type parameter, and its transport error is
+/// the underlying serial error directly. Use `Error::into_unified`, or the
+/// `From` implementation with the `?` operator, to convert an
+/// `Error<E, E>`.
+#[derive(Debug)]
+#[non_exhaustive]
+pub struct SimpleError<E> {
+    kind: ErrorKind,
+    context: Option<Context>,
+    source: Option<E>,
+}
+
+impl<E> SimpleError<E> {
+    /// `kind` returns the kind of the error.
+    pub fn kind(&self) -> ErrorKind {
+        self.kind
+    }
+
+    /// `context` is equivalent to `Error::context`.
+    pub fn context(&self) -> Option<Context> {
+        self.context
+    }
+
+    /// `transport_error` returns the error returned by the underlying serial
+    /// implementation, if that was the cause of the error.
+    pub fn transport_error(&self) -> Option<&E> {
+        self.source.as_ref()
+    }
+
+    /// `into_transport_error` is like `transport_error`, but consumes the
+    /// error to return the underlying serial error by value.
+    pub fn into_transport_error(self) -> Option<E> {
+        self.source
+    }
+
+    /// `is_transient` is equivalent to `Error::is_transient`.
+    pub fn is_transient(&self) -> bool {
+        self.kind.is_transient()
+    }
+}
+
+impl<E> Error<E, E> {
+    /// `into_unified` converts the error into a `SimpleError`.
+    pub fn into_unified(self) -> SimpleError<E> {
+        SimpleError {
+            kind: self.kind,
+            context: self.context,
+            source: self.source.map(|source| match source {
+                TransportError::Write(err) | TransportError::Read(err) => err,
+            }),
+        }
+    }
+}
+
+impl<E> From<Error<E, E>> for SimpleError<E> {
+    fn from(err: Error<E, E>) -> Self {
+        err.into_unified()
+    }
+}
+
+impl<E> core::fmt::Display for SimpleError<E> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write_error(f, &self.kind, &self.context)
+    }
+}
+
+#[cfg(feature = "std")]
+impl<E> std::error::Error for SimpleError<E>
+where
+    E: std::error::Error + 'static,
+{
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match &self.source {
+            Some(err) => Some(err),
+            None => None,
+        }
+    }
+}
+
+#[cfg(feature = "eh1")]
+impl<E> embedded_hal_1::spi::Error for SimpleError<E>
+where
+    E: core::fmt::Debug,
+{
+    fn kind(&self) -> embedded_hal_1::spi::ErrorKind {
+        self.kind.spi_kind()
+    }
+}
+
+#[cfg(feature = "defmt")]
+impl<E> defmt::Format for SimpleError<E> {
+    fn format(&self, f: defmt::Formatter<'_>) {
+        defmt::write!(f, "{}", self.kind);
+        if let Some(context) = self.context {
+            defmt::write!(f, " during {}", context);
+        }
+    }
+}
+
+/// `RequestError` describes why a request was rejected with an error of
+/// kind `ErrorKind::Request`.
+///
+/// New reasons may be added in future releases, so code that matches on
+/// `RequestError` must include a wildcard arm.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[non_exhaustive]
+pub enum RequestError {
+    /// `TooLong` indicates that the given data was longer than a single
+    /// protocol frame can carry.
+    TooLong { len: usize, max: usize },
+
+    /// `NoPinRole` indicates that the operation uses a pin role that hasn't
+    /// been assigned to any pin.
+    NoPinRole(PinRole),
+
+    /// `AddressWidth` indicates that a register address width other than 1
+    /// or 2 bytes was requested.
+    AddressWidth(u8),
+
+    /// `InProgress` indicates that a non-blocking operation was started
+    /// while a different one was still in progress.
+    InProgress,
+
+    /// `FrameLength` indicates that a frame pushed to a `FrameStream` was a
+    /// different length than the stream was created for.
+    FrameLength { len: usize, expected: usize },
+
+    /// `Empty` indicates that a write or transfer was given no data while
+    /// empty requests are being rejected. See `SPIDriver::set_reject_empty`.
+    Empty,
+}
+
+impl core::fmt::Display for RequestError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            RequestError::TooLong { len, max } => {
+                write!(f, "{} bytes is longer than the maximum of {}", len, max)
+            }
+            RequestError::NoPinRole(role) => write!(f, "no pin has the {} role", role),
+            RequestError::AddressWidth(width) => {
+                write!(f, "unsupported register address width {}", width)
+            }
+            RequestError::FrameLength { len, expected } => {
+                write!(f, "frame of {} bytes should be {} bytes", len, expected)
+            }
+            RequestError::InProgress => f.write_str("a different operation is in progress"),
+            RequestError::Empty => f.write_str("no data to send"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for RequestError {}
@@ -0,0 +1,131 @@
+use embedded_hal::serial;
+
+use crate::{AuxPin, Error, ErrorKind, PinRole, PinState, RequestError, SPIDriver, MAX_FRAME};
+
+// The longest header a FrameStream sends before each frame: select, then
+// set the data/command pin, write the command byte, and set the pin again.
+const HEADER_MAX: usize = 1 + 2 + 2 + 2;
+
+/// `FrameStream` repeatedly sends buffers of a fixed size, such as the
+/// successive frames rendered for a display, as quickly as possible.
+///
+/// Obtain a `FrameStream` by calling `SPIDriver::stream_frames`. Each call
+/// to `push` selects the target device, optionally sends a command byte
+/// with the data/command pin in command mode, sends the whole buffer with
+/// the pin in data mode, and then unselects the target device again:
+///
+/// ```rust,ignore
+/// let mut frames = sd.stream_frames(BUF_SIZE, Some(0x5c))?;
+/// loop {
+///     render(&mut buf);
+///     frames.push(&buf)?;
+/// }
+/// ```
+///
+/// The commands that precede each frame and the way the frame is split into
+/// protocol frames are worked out only once, when the stream is created,
+/// and the serial writer is flushed only once per frame. The frames are
+/// split according to the chunk size set using `SPIDriver::set_chunk_size`.
+pub struct FrameStream<'a, TX, RX>
+where
+    TX: serial::Write<u8>,
+    RX: serial::Read<u8>,
+{
+    sd: &'a mut SPIDriver<TX, RX>,
+    len: usize,
+    chunk_size: usize,
+    header: [u8; HEADER_MAX],
+    header_len: usize,
+}
+
+impl<TX, RX, TXErr, RXErr> SPIDriver<TX, RX>
+where
+    TX: serial::Write<u8, Error = TXErr>,
+    RX: serial::Read<u8, Error = RXErr>,
+{
+    /// `stream_frames` returns a `FrameStream` for sending buffers of exactly
+    /// `len` bytes.
+    ///
+    /// If `command` is set then that command byte is sent before each frame
+    /// with the data/command pin in command mode, and the frame itself is
+    /// sent in data mode, as is typical for display controllers. In that
+    /// case `stream_frames` returns the `Request` error with
+    /// `RequestError::NoPinRole` if no pin has been assigned the
+    /// data/command role.
+    pub fn stream_frames(
+        &mut self,
+        len: usize,
+        command: Option<u8>,
+    ) -> Result<FrameStream<'_, TX, RX>, Error<TXErr, RXErr>> {
+        let mut header = [0_u8; HEADER_MAX];
+        header[0] = b's';
+        let mut header_len = 1;
+        if let Some(command) = command {
+            let pin =
+                self.roles
+                    .dc
+                    .ok_or(Error::new(ErrorKind::Request(RequestError::NoPinRole(
+                        PinRole::DataCommand,
+                    ))))?;
+            let set_pin = match pin {
+                AuxPin::A => b'a',
+                AuxPin::B => b'b',
+            };
+            // The data/command pin is low for commands and high for data.
+            header[1..7].copy_from_slice(&[
+                set_pin,
+                PinState::Low.protocol_arg(),
+                0xc0,
+                command,
+                set_pin,
+                PinState::High.protocol_arg(),
+            ]);
+            header_len = 7;
+        }
+        Ok(FrameStream {
+            len,
+            chunk_size: self.chunk_size,
+            header,
+            header_len,
+            sd: self,
+        })
+    }
+}
+
+impl<'a, TX, RX, TXErr, RXErr> FrameStream<'a, TX, RX>
+where
+    TX: serial::Write<u8, Error = TXErr>,
+    RX: serial::Read<u8, Error = RXErr>,
+{
+    /// `push` sends one frame, which must be exactly the length given when
+    /// the stream was created.
+    ///
+    /// Returns the `Request` error with `RequestError::FrameLength` if the
+    /// frame is the wrong length, without sending anything.
+    pub fn push(&mut self, frame: &[u8]) -> Result<(), Error<TXErr, RXErr>> {
+        if frame.len() != self.len {
+            return Err(Error::new(ErrorKind::Request(RequestError::FrameLength {
+                len: frame.len(),
+                expected: self.len,
+            })));
+        }
+        let chunk_size = self.chunk_size;
+        let sd = &mut *self.sd;
+        sd.ch.send_encoded(&self.header[..self.header_len])?;
+        let mut buf = [0_u8; MAX_FRAME + 1];
+        for (i, chunk) in frame.chunks(chunk_size).enumerate() {
+            buf[0] = 0xc0 - 1 + chunk.len() as u8;
+            buf[1..=chunk.len()].copy_from_slice(chunk);
+            let start = sd.ch.start_frame();
+            sd.ch
+                .send_encoded(&buf[..=chunk.len()])
+                .and_then(|_| {
+                    sd.ch.finish_frame(start, chunk.len());
+                    sd.after_data(chunk.len())
+                })
+                .map_err(|e| e.at_offset(i * chunk_size))?;
+        }
+        sd.ch.send_encoded(b"u")?;
+        sd.ch.flush()
+    }
+}
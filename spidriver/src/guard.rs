@@ -0,0 +1,78 @@
+use core::ops::{Deref, DerefMut};
+
+use embedded_hal::serial;
+
+use crate::{Error, SPIDriver};
+
+/// `SelectGuard` keeps the chip select signal of an SPIDriver asserted for
+/// as long as it exists, releasing it again when dropped.
+///
+/// Obtain a `SelectGuard` by calling `SPIDriver::select_guard`. The guard
+/// dereferences to the `SPIDriver` it was created from, so all of the usual
+/// methods remain available through it while the target is selected.
+///
+/// Any error from releasing the chip select signal during drop is ignored.
+/// Call `release` instead to release it explicitly and observe any error.
+#[derive(Debug)]
+pub struct SelectGuard<'a, TX, RX>
+where
+    TX: serial::Write<u8>,
+    RX: serial::Read<u8>,
+{
+    sd: &'a mut SPIDriver<TX, RX>,
+    released: bool,
+}
+
+impl<'a, TX, RX, TXErr, RXErr> SelectGuard<'a, TX, RX>
+where
+    TX: serial::Write<u8, Error = TXErr>,
+    RX: serial::Read<u8, Error = RXErr>,
+{
+    pub(crate) fn new(sd: &'a mut SPIDriver<TX, RX>) -> Self {
+        Self {
+            sd,
+            released: false,
+        }
+    }
+
+    /// `release` consumes the guard and releases the chip select signal,
+    /// returning any error that occurs while doing so.
+    pub fn release(mut self) -> Result<(), Error<TXErr, RXErr>> {
+        self.released = true;
+        self.sd.unselect()
+    }
+}
+
+impl<'a, TX, RX> Deref for SelectGuard<'a, TX, RX>
+where
+    TX: serial::Write<u8>,
+    RX: serial::Read<u8>,
+{
+    type Target = SPIDriver<TX, RX>;
+
+    fn deref(&self) -> &Self::Target {
+        self.sd
+    }
+}
+
+impl<'a, TX, RX> DerefMut for SelectGuard<'a, TX, RX>
+where
+    TX: serial::Write<u8>,
+    RX: serial::Read<u8>,
+{
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.sd
+    }
+}
+
+impl<'a, TX, RX> Drop for SelectGuard<'a, TX, RX>
+where
+    TX: serial::Write<u8>,
+    RX: serial::Read<u8>,
+{
+    fn drop(&mut self) {
+        if !self.released {
+            let _ = self.sd.unselect();
+        }
+    }
+}
@@ -0,0 +1,71 @@
+use embedded_hal::serial;
+
+use crate::SPIDriver;
+
+impl<TX, RX, TXErr, RXErr> SPIDriver<TX, RX>
+where
+    TX: serial::Write<u8, Error = TXErr>,
+    RX: serial::Read<u8, Error = RXErr>,
+{
+    /// `is_connected_with` checks whether the SPIDriver is still responding
+    /// by performing a single echo round-trip, returning `true` only if the
+    /// expected byte comes back.
+    ///
+    /// The given function is called repeatedly while waiting for the
+    /// response, and should return `true` once the caller is no longer
+    /// willing to wait. This allows using whatever clock is available on the
+    /// host platform to implement a timeout.
+    ///
+    /// The echo command doesn't affect the SPI bus or the output pins, so
+    /// this check can be made at any time between other operations.
+    ///
+    /// If the check fails for any reason, including the caller giving up
+    /// before the response arrives, the failure is counted as an error and
+    /// the connection becomes poisoned, because a late response would
+    /// otherwise remain buffered and confuse subsequent operations. Call
+    /// `SPIDriver::resync` to recover once the device is responding again.
+    ///
+    /// `is_connected_with` always returns `false` while the connection is
+    /// poisoned or unresponsive, as described under `ErrorKind::Poisoned`
+    /// and `ErrorKind::DeviceUnresponsive`.
+    pub fn is_connected_with(&mut self, mut expired: impl FnMut() -> bool) -> bool {
+        self.check_seq = self.check_seq.wrapping_add(1);
+        let want = self.check_seq;
+        let sent = self.ch.send(b'e', &[want]).and_then(|_| self.ch.flush());
+        if sent.is_err() {
+            return false;
+        }
+        loop {
+            match self.ch.poll_read() {
+                Ok(got) => {
+                    self.ch.tap_incoming(&[got]);
+                    self.ch.observe_response(&[got]);
+                    if got != want {
+                        self.ch.count_error();
+                    }
+                    return got == want;
+                }
+                Err(nb::Error::WouldBlock) => {
+                    if expired() {
+                        self.ch.count_error();
+                        return false;
+                    }
+                }
+                Err(nb::Error::Other(_)) => {
+                    self.ch.count_error();
+                    return false;
+                }
+            }
+        }
+    }
+
+    /// `is_connected` is like `is_connected_with`, but waits for at most the
+    /// given duration as measured by the standard library's monotonic clock.
+    ///
+    /// This method is available only when the `std` feature is enabled.
+    #[cfg(feature = "std")]
+    pub fn is_connected(&mut self, timeout: std::time::Duration) -> bool {
+        let deadline = std::time::Instant::now() + timeout;
+        self.is_connected_with(|| std::time::Instant::now() >= deadline)
+    }
+}
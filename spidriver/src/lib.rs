@@ -11,7 +11,10 @@
 //! [`serial_embedded_hal`](https://docs.rs/serial-embedded-hal/0.1.2/serial_embedded_hal/struct.Serial.html)
 //! to connect with a serial port provided by your operating system:
 //!
-//! ```rust
+//! ```rust,no_run
+//! # use serial_embedded_hal::{PortSettings, Serial};
+//! # use spidriver::SPIDriver;
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
 //! let port = Serial::new(
 //!     "/dev/ttyUSB0",
 //!     &PortSettings {
@@ -24,16 +27,143 @@
 //! )?;
 //! let (tx, rx) = port.split();
 //! let sd = SPIDriver::new(tx, rx);
+//! # Ok(())
+//! # }
 //! ```
+//!
+//! The library is `no_std` by default. Enabling the `std` feature adds some
+//! additional helpers that require the standard library, such as `Monitor`.
+//! Enabling the `eh1` feature implements the embedded-hal 1.0 SPI `Error`
+//! trait for `Error`, and enabling the `defmt` feature implements
+//! `defmt::Format` for the public types, and enabling the `log` feature
+//! emits log records describing each command sent to the device. Enabling
+//! the `async` feature adds `AsyncSPIDriver`, which implements the
+//! `embedded-hal-async` SPI traits over an asynchronous serial transport.
 
-#![no_std]
+#![cfg_attr(not(feature = "std"), no_std)]
+#![forbid(unsafe_code)]
 
 use embedded_hal::serial;
 
+use channel::Channel;
+use clock::ReadTimeout;
+use core::time::Duration;
+use teardown::TeardownOnDrop;
+
+#[macro_use]
+mod logging;
+
+#[cfg(feature = "async")]
+mod asynch;
+mod batch;
+mod baud;
+mod builder;
+mod capabilities;
+mod channel;
+mod clock;
+mod command;
+mod crc;
+#[cfg(feature = "std")]
+mod double_buffer;
+mod error;
+mod frames;
+mod guard;
+mod health;
+#[cfg(feature = "std")]
+mod monitor;
+mod nonblocking;
+mod observer;
+pub mod prelude;
+mod progress;
+mod pulse;
+mod register;
+mod resync;
+mod retry;
+mod roles;
+mod stats;
+mod status;
+#[cfg(feature = "std")]
+mod stream;
+mod teardown;
+#[cfg(feature = "std")]
+mod threaded;
+mod transport;
+pub mod typestate;
+#[cfg(feature = "std")]
+mod wait;
+#[cfg(feature = "std")]
+mod watchdog;
+mod wiretap;
+mod words;
+
+#[cfg(feature = "async")]
+pub use asynch::{AsyncDevice, AsyncSPIDriver};
+pub use batch::{Batch, DEFAULT_BATCH_CAPACITY};
+pub use builder::SPIDriverBuilder;
+pub use capabilities::{Capabilities, Capability};
+pub use clock::Clock;
+#[cfg(feature = "std")]
+pub use clock::StdClock;
+pub use command::{Command, Response};
+pub use crc::CrcCheckedSpiDriver;
+pub use error::{
+    Context, Error, ErrorKind, ProtocolFault, RequestError, SimpleError, TransportError,
+};
+pub use frames::FrameStream;
+pub use guard::SelectGuard;
+
+#[cfg(feature = "std")]
+pub use monitor::{Monitor, MonitorError, Violation};
+pub use observer::ProtocolObserver;
+pub use progress::Progress;
+pub use register::{RegisterAccess, RegisterConfig};
+pub use retry::RetryPolicy;
+pub use roles::{AuxPin, DcMode, PinRole, PinRoles, Target};
+pub use stats::Stats;
+pub use status::{DeviceStatus, Identifier};
+#[cfg(feature = "std")]
+pub use stream::StreamError;
+pub use teardown::Teardown;
+#[cfg(feature = "std")]
+pub use threaded::{ThreadedWriter, WriterHandle};
+pub use transport::BlockWrite;
+#[cfg(feature = "embedded-io")]
+pub use transport::EioWriter;
+#[cfg(feature = "std")]
+pub use transport::IoWriter;
+#[cfg(feature = "std")]
+pub use wait::WaitStrategy;
+#[cfg(feature = "std")]
+pub use watchdog::Watchdog;
+pub use wiretap::{Direction, WireTap};
+pub use words::Endianness;
+
+/// `MAX_FRAME` is the maximum number of data bytes that the SPIDriver
+/// protocol allows in a single write or transfer command.
+pub const MAX_FRAME: usize = 64;
+
 /// `SPIDriver` represents a connected SPIDriver device.
+///
+/// `SPIDriver` is `Send` whenever `TX` and `RX` are, and so can be moved
+/// into a worker thread that then communicates with the device.
 #[derive(Debug)]
 pub struct SPIDriver<TX: serial::Write<u8>, RX: serial::Read<u8>> {
-    ch: Channel<TX, RX>,
+    ch: TeardownOnDrop<TX, RX>,
+    caps: Capabilities,
+    roles: PinRoles,
+    auto_chunk: bool,
+    chunk_size: usize,
+    reject_empty: bool,
+
+    // State for checked mode and sync. See set_check_interval.
+    check_interval: Option<usize>,
+    unchecked: usize,
+    check_seq: u8,
+
+    retry: Option<RetryPolicy>,
+
+    // The non-blocking operation in progress, if any. See try_write.
+    pending: Option<nonblocking::Pending>,
 }
 
 impl<TX, RX, TXErr, RXErr> SPIDriver<TX, RX>
@@ -45,78 +175,464 @@ where
     /// an `SPIDriver` object.
     pub fn new(tx: TX, rx: RX) -> Self {
         Self {
-            ch: Channel { tx: tx, rx: rx },
+            ch: TeardownOnDrop::new(Channel::new(tx, rx)),
+            caps: Capabilities::baseline(),
+            roles: PinRoles::default(),
+            auto_chunk: false,
+            chunk_size: MAX_FRAME,
+            reject_empty: false,
+            check_interval: None,
+            unchecked: 0,
+            check_seq: 0,
+            retry: None,
+            pending: None,
         }
     }
 
+    /// `new_with_pins` is like `new` but also immediately drives the chip
+    /// select signal and the auxillary pins to the levels described in the
+    /// given `InitialPins`.
+    ///
+    /// `new` leaves all of the pins in whatever state the device was already
+    /// in, which may be the result of some earlier unrelated program. If the
+    /// auxillary pins are connected to signals like a reset line then it is
+    /// often important to put them in a known state before doing anything
+    /// else.
+    pub fn new_with_pins(tx: TX, rx: RX, pins: InitialPins) -> Result<Self, Error<TXErr, RXErr>> {
+        let mut ret = Self::new(tx, rx);
+        ret.set_pins_initial(pins)?;
+        Ok(ret)
+    }
+
+    pub(crate) fn set_pins_initial(
+        &mut self,
+        pins: InitialPins,
+    ) -> Result<(), Error<TXErr, RXErr>> {
+        self.set_cs(pins.cs)?;
+        self.set_a(pins.a)?;
+        self.set_b(pins.b)
+    }
+
+    /// `free` consumes the `SPIDriver` object and returns the serial `Write`
+    /// and `Read` implementations it was created from.
+    ///
+    /// `free` doesn't send anything to the device, even if teardown on drop
+    /// is enabled, so the SPIDriver's pins remain in whatever state they
+    /// were left in. Any data that the `SPIDriver` had already taken from
+    /// the serial reader but not yet consumed is discarded.
+    pub fn free(self) -> (TX, RX) {
+        let ch = self.ch.into_channel();
+        (ch.tx, ch.rx)
+    }
+
     /// `echo` asks the SPIDriver to echo back the given character.
     ///
     /// This method can be useful for detecting whether the remote device on
     /// the serial line is actually a SPIDriver: ask it to echo back a few
     /// bytes and verify that it does.
     pub fn echo(&mut self, ch: u8) -> Result<u8, Error<TXErr, RXErr>> {
-        self.ch.write(b'e')?;
-        self.ch.write(ch)?;
+        let mut got = [0_u8; 1];
+        self.ch.send(b'e', &[ch])?;
         self.ch.flush()?;
-        self.ch.read()
+        self.ch.receive(&mut got)?;
+        Ok(got[0])
+    }
+
+    /// `probe_capabilities` interrogates the connected device to determine
+    /// which optional protocol features its firmware supports, and then
+    /// remembers the result so that other methods can avoid sending
+    /// unsupported commands.
+    ///
+    /// The probe first checks that the device echoes back a few test bytes,
+    /// returning the `Protocol` error if it doesn't, and then requests a
+    /// status report to learn which firmware the device is running.
+    pub fn probe_capabilities(&mut self) -> Result<Capabilities, Error<TXErr, RXErr>> {
+        for c in [0x55, 0x00, 0xff, 0xaa] {
+            if self.echo(c)? != c {
+                return Err(self.ch.protocol_error(ProtocolFault::UnexpectedResponse));
+            }
+        }
+        let status = self.status()?;
+        self.caps = Capabilities::for_model(status.model);
+        Ok(self.caps)
+    }
+
+    /// `capabilities` returns the capabilities most recently detected by
+    /// `probe_capabilities`, or the baseline capabilities if the device has
+    /// not yet been probed.
+    pub fn capabilities(&self) -> Capabilities {
+        self.caps
+    }
+
+    /// `status` requests a status report from the SPIDriver, which includes
+    /// its identity, measurements of the supply voltage, the current drawn
+    /// by the target device and the SPIDriver's internal temperature, and
+    /// the current states of its output pins.
+    pub fn status(&mut self) -> Result<DeviceStatus, Error<TXErr, RXErr>> {
+        let mut buf = [0_u8; status::REPORT_LEN];
+        self.ch.send(b'?', &[])?;
+        self.ch.flush()?;
+        self.ch.receive(&mut buf)?;
+        DeviceStatus::parse(&buf)
+            .ok_or_else(|| self.ch.protocol_error(ProtocolFault::UnexpectedResponse))
     }
 
     /// `select` asserts the chip select signal by driving it low.
     pub fn select(&mut self) -> Result<(), Error<TXErr, RXErr>> {
-        self.ch.write(b's')?;
+        self.ch.send(b's', &[])?;
         self.ch.flush()
     }
 
     /// `unselect` de-asserts the chip select signal by driving it high.
     pub fn unselect(&mut self) -> Result<(), Error<TXErr, RXErr>> {
-        self.ch.write(b'u')?;
+        self.ch.send(b'u', &[])?;
         self.ch.flush()
     }
 
-    /// `set_a` sets the active state of the auxillary "A" pin on the SPIDriver.
-    pub fn set_a(&mut self, high: bool) -> Result<(), Error<TXErr, RXErr>> {
-        self.ch.write(b'a')?;
-        if high {
-            self.ch.write(b'1')?
-        } else {
-            self.ch.write(b'0')?
+    /// `with_selected` asserts the chip select signal, calls the given
+    /// function, and then releases the chip select signal again.
+    ///
+    /// The chip select signal is released even if the function returns an
+    /// error, in which case that error is returned. If the function succeeds
+    /// but releasing the chip select signal fails then the release error is
+    /// returned instead.
+    pub fn with_selected<R, E>(&mut self, f: impl FnOnce(&mut Self) -> Result<R, E>) -> Result<R, E>
+    where
+        E: From<Error<TXErr, RXErr>>,
+    {
+        self.select()?;
+        let result = f(self);
+        let unselected = self.unselect();
+        let ret = result?;
+        unselected?;
+        Ok(ret)
+    }
+
+    /// `select_guard` asserts the chip select signal and returns a guard
+    /// object that releases it again when dropped.
+    ///
+    /// This is an alternative to `with_selected` that is more convenient
+    /// when the selected phase uses early returns:
+    ///
+    /// ```rust,ignore
+    /// let mut cs = sd.select_guard()?;
+    /// cs.write(&cmd)?;
+    /// cs.transfer(&mut response)?;
+    /// // chip select is released here, when `cs` goes out of scope
+    /// ```
+    pub fn select_guard(&mut self) -> Result<SelectGuard<'_, TX, RX>, Error<TXErr, RXErr>> {
+        self.select()?;
+        Ok(SelectGuard::new(self))
+    }
+
+    /// `batch` returns a `Batch` for queuing a sequence of commands to be
+    /// sent to the device together.
+    pub fn batch(&mut self) -> Batch<'_, TX, RX> {
+        Batch::new(self)
+    }
+
+    /// `batch_with_capacity` is like `batch`, but returns a `Batch` whose
+    /// buffer holds `N` bytes of encoded commands rather than
+    /// `DEFAULT_BATCH_CAPACITY`.
+    pub fn batch_with_capacity<const N: usize>(&mut self) -> Batch<'_, TX, RX, N> {
+        Batch::new(self)
+    }
+
+    /// `set_cs` either asserts or releases the chip select signal, depending
+    /// on the given state.
+    ///
+    /// This is equivalent to calling either `select` or `unselect`, but can
+    /// be more convenient when the desired state is determined dynamically.
+    pub fn set_cs(&mut self, state: impl Into<CsState>) -> Result<(), Error<TXErr, RXErr>> {
+        match state.into() {
+            CsState::Asserted => self.select(),
+            CsState::Released => self.unselect(),
         }
+    }
+
+    /// `set_a` sets the active state of the auxillary "A" pin on the SPIDriver.
+    pub fn set_a(&mut self, state: impl Into<PinState>) -> Result<(), Error<TXErr, RXErr>> {
+        self.ch.send(b'a', &[state.into().protocol_arg()])?;
         self.ch.flush()
     }
 
     /// `set_b` sets the active state of the auxillary "B" pin on the SPIDriver.
-    pub fn set_b(&mut self, high: bool) -> Result<(), Error<TXErr, RXErr>> {
-        self.ch.write(b'b')?;
-        if high {
-            self.ch.write(b'1')?
-        } else {
-            self.ch.write(b'0')?
+    pub fn set_b(&mut self, state: impl Into<PinState>) -> Result<(), Error<TXErr, RXErr>> {
+        self.ch.send(b'b', &[state.into().protocol_arg()])?;
+        self.ch.flush()
+    }
+
+    /// `set_pins` updates any combination of the chip select signal and the
+    /// auxillary pins, sending all of the necessary commands together with
+    /// only a single flush of the serial writer.
+    ///
+    /// Pins whose argument is `None` are left unchanged. The auxillary pins
+    /// are updated before the chip select signal is asserted or after it is
+    /// released, so that signals such as data/command select are already
+    /// stable whenever the target device is selected.
+    pub fn set_pins(
+        &mut self,
+        cs: Option<CsState>,
+        a: Option<PinState>,
+        b: Option<PinState>,
+    ) -> Result<(), Error<TXErr, RXErr>> {
+        if cs == Some(CsState::Released) {
+            self.ch.send(b'u', &[])?;
+        }
+        if let Some(a) = a {
+            self.ch.send(b'a', &[a.protocol_arg()])?;
+        }
+        if let Some(b) = b {
+            self.ch.send(b'b', &[b.protocol_arg()])?;
+        }
+        if cs == Some(CsState::Asserted) {
+            self.ch.send(b's', &[])?;
         }
         self.ch.flush()
     }
 
+    /// `set_clock_hz` selects the supported SPI clock rate nearest to the given
+    /// frequency in hertz, and returns the rate that was actually selected.
+    ///
+    /// Changing the clock rate requires the `ClockSelect` capability, as
+    /// determined by `probe_capabilities`. No known SPIDriver firmware has
+    /// it, so `set_clock_hz` currently always returns the `Unsupported`
+    /// error without sending anything to the device.
+    pub fn set_clock_hz(&mut self, hz: u32) -> Result<u32, Error<TXErr, RXErr>> {
+        self.require(Capability::ClockSelect)?;
+        Ok(hz)
+    }
+
+    /// `set_mode` checks that the SPIDriver can use the given SPI mode, which
+    /// determines the clock polarity and phase used for transfers.
+    ///
+    /// All SPIDriver firmware uses `SpiMode::Mode0` by default, so `set_mode`
+    /// succeeds without sending anything for `Mode0`. Any other mode requires
+    /// the `ModeSelect` capability, as determined by `probe_capabilities`,
+    /// and `set_mode` returns the `Unsupported` error without sending
+    /// anything if the firmware lacks it.
+    pub fn set_mode(&mut self, mode: SpiMode) -> Result<(), Error<TXErr, RXErr>> {
+        if mode == SpiMode::Mode0 {
+            return Ok(());
+        }
+        self.require(Capability::ModeSelect)
+    }
+
+    // require returns the Unsupported error unless the firmware detected by
+    // probe_capabilities has the given capability.
+    fn require(&self, cap: Capability) -> Result<(), Error<TXErr, RXErr>> {
+        if !self.caps.supports(cap) {
+            debug!("firmware lacks {}", cap);
+            return Err(Error::new(ErrorKind::Unsupported(cap)));
+        }
+        Ok(())
+    }
+
     /// `disconnect` requests that the SPIDriver disconnect from the SPI signals,
     pub fn disconnect(&mut self) -> Result<(), Error<TXErr, RXErr>> {
-        self.ch.write(b'x')
+        self.ch.send(b'x', &[])?;
+        self.ch.flush()
+    }
+
+    /// `set_read_timeout` sets the maximum time to wait for each byte of a
+    /// response from the SPIDriver, as measured by the given clock.
+    ///
+    /// If a byte doesn't arrive in time then the operation that was waiting
+    /// for it fails with the `Timeout` error, or with the `Protocol` error
+    /// and `ProtocolFault::ShortResponse` if some of the response had
+    /// already arrived. Without a timeout, which is the default, a device
+    /// that stops responding will cause operations to block forever.
+    ///
+    /// When the `std` feature is enabled, `StdClock` is a suitable clock
+    /// for use on a general computing platform.
+    pub fn set_read_timeout(&mut self, timeout: Duration, clock: &'static (dyn Clock + Sync)) {
+        self.ch
+            .set_read_timeout(Some(ReadTimeout::new(timeout, clock)));
+    }
+
+    /// `clear_read_timeout` removes any read timeout previously set using
+    /// `set_read_timeout`.
+    pub fn clear_read_timeout(&mut self) {
+        self.ch.set_read_timeout(None);
+    }
+
+    /// `set_poll_batch` sets how many times the serial reader is polled
+    /// without receiving anything before the read timeout is checked, while
+    /// waiting for the response to a transfer.
+    ///
+    /// Larger batches reduce how often the clock is read, which can help on
+    /// slow hosts where reading the clock is expensive, at the expense of
+    /// timeouts being detected slightly later. The default is 64, and a
+    /// batch size of zero is treated as one.
+    pub fn set_poll_batch(&mut self, batch: usize) {
+        self.ch.set_poll_batch(batch);
+    }
+
+    /// `set_auto_chunk` enables or disables automatic chunking of long
+    /// writes and transfers.
+    ///
+    /// When automatic chunking is enabled, `write` and `transfer` behave
+    /// like `write_all` and `transfer_all` respectively when given more than
+    /// 64 bytes, rather than returning an error. This is convenient for
+    /// applications that don't need to control where the frame boundaries
+    /// fall.
+    pub fn set_auto_chunk(&mut self, enable: bool) {
+        self.auto_chunk = enable;
+    }
+
+    /// `set_chunk_size` sets the largest number of data bytes that operations
+    /// which split their data into protocol frames, such as `write_all` and
+    /// `transfer_all`, put in each frame.
+    ///
+    /// The default is the protocol maximum of 64 bytes, which gives the best
+    /// throughput. Smaller frames reduce how long the SPIDriver spends on
+    /// each command, which can reduce latency jitter for applications that
+    /// interleave other operations with long transfers. The given size is
+    /// clamped to between 1 and 64.
+    pub fn set_chunk_size(&mut self, size: usize) {
+        self.chunk_size = size.clamp(1, MAX_FRAME);
+    }
+
+    /// `set_reject_empty` enables or disables rejection of empty writes and
+    /// transfers.
+    ///
+    /// By default, writing or transferring an empty slice succeeds without
+    /// sending anything to the device. When rejection is enabled, it fails
+    /// with the `Request` error with `RequestError::Empty` instead, which
+    /// can help to catch bugs where a driver accidentally sends empty
+    /// frames.
+    pub fn set_reject_empty(&mut self, enable: bool) {
+        self.reject_empty = enable;
+    }
+
+    // check_empty returns the error for an empty request if empty requests
+    // are being rejected.
+    fn check_empty(&self, len: usize) -> Result<(), Error<TXErr, RXErr>> {
+        if len == 0 && self.reject_empty {
+            return Err(Error::new(ErrorKind::Request(RequestError::Empty)));
+        }
+        Ok(())
+    }
+
+    /// `set_check_interval` enables or disables checked mode.
+    ///
+    /// In checked mode, `write` and `transfer` interleave an echo command
+    /// after each time at least the given number of data bytes have been
+    /// sent, and verify that the device echoes back the expected byte. If it
+    /// doesn't then the host and device have become out of step, and the
+    /// operation fails with the `Protocol` error rather than continuing to
+    /// send data the device may be misinterpreting.
+    ///
+    /// Each check costs a round-trip to the device, so smaller intervals
+    /// detect problems sooner at the expense of throughput. Pass `None` to
+    /// disable checked mode, which is the default.
+    pub fn set_check_interval(&mut self, interval: Option<usize>) {
+        self.check_interval = interval;
+        self.unchecked = 0;
+    }
+
+    /// `set_strict_responses` enables or disables strict response mode.
+    ///
+    /// In strict response mode, the `SPIDriver` checks that no data is
+    /// already waiting to be read before sending each command, and that no
+    /// further data arrives immediately after each complete response. Either
+    /// situation means that the host and device have become out of step,
+    /// and so the operation fails with the `Protocol` error rather than
+    /// silently treating misaligned data as a response. Any unexpected data
+    /// is discarded.
+    ///
+    /// The additional checks are non-blocking, so they don't add any
+    /// round-trips to the device, but they can't detect data that arrives
+    /// late. Strict response mode is disabled by default.
+    pub fn set_strict_responses(&mut self, enable: bool) {
+        self.ch.set_strict(enable);
+    }
+
+    /// `set_low_latency` enables or disables low-latency mode, for bursts of
+    /// operations where each must reach the device as soon as possible.
+    ///
+    /// In low-latency mode, `write` and `transfer` skip the echo checks of
+    /// checked mode, `CrcCheckedSpiDriver` skips its CRC verification, the
+    /// additional checks of strict response mode are skipped, and the
+    /// activity counters and frame timings in `stats` aren't updated, except
+    /// for the count of errors. Each operation then sends only the bytes of
+    /// its own command, and doesn't wait for anything other than its own
+    /// response.
+    ///
+    /// Because the activity counters don't change, a `Watchdog` regards the
+    /// device as idle throughout, and so may send its checks between
+    /// operations.
+    ///
+    /// The other modes keep their settings while low-latency mode is
+    /// enabled, and take effect again once it is disabled. Low-latency mode
+    /// is disabled by default.
+    pub fn set_low_latency(&mut self, enable: bool) {
+        self.ch.low_latency = enable;
+    }
+
+    // after_data is called after each write or transfer frame to implement
+    // checked mode.
+    fn after_data(&mut self, len: usize) -> Result<(), Error<TXErr, RXErr>> {
+        let interval = match self.check_interval {
+            Some(interval) if !self.ch.low_latency => interval,
+            _ => return Ok(()),
+        };
+        self.unchecked += len;
+        if self.unchecked < interval {
+            return Ok(());
+        }
+        self.unchecked = 0;
+        self.sync()
+    }
+
+    /// `sync` waits until the SPIDriver has finished processing all of the
+    /// commands sent so far, including any writes, by asking it to echo back
+    /// a byte and waiting for the response.
+    ///
+    /// Writes don't otherwise wait for a response, so `sync` is useful when
+    /// something outside of the SPIDriver, such as a chip select signal
+    /// driven by the host, must not change until a write is complete.
+    pub fn sync(&mut self) -> Result<(), Error<TXErr, RXErr>> {
+        // We use a different byte for each sync so that a stale response
+        // left over from an earlier one can't be mistaken for a new one.
+        self.check_seq = self.check_seq.wrapping_add(1);
+        let want = self.check_seq;
+        if self.echo(want)? != want {
+            return Err(self.ch.protocol_error(ProtocolFault::UnexpectedResponse));
+        }
+        Ok(())
     }
 
     /// `write` sends up to 64 bytes out over the SPIDriver's MOSI line.
     ///
     /// If the given slice is longer than 64 bytes then `write` will return
-    /// the `Request` error.
+    /// the `Request` error with `RequestError::TooLong`, unless automatic
+    /// chunking is enabled using `set_auto_chunk`.
+    ///
+    /// Writing an empty slice does nothing, unless empty requests are
+    /// rejected using `set_reject_empty`.
     pub fn write(&mut self, data: &[u8]) -> Result<(), Error<TXErr, RXErr>> {
-        if data.len() == 0 {
-            return Ok(()); // nothing to do
+        if data.is_empty() {
+            return self.check_empty(0); // nothing to do
         }
-        if data.len() > 64 {
-            return Err(Error::Request);
+        if data.len() > MAX_FRAME {
+            if self.auto_chunk {
+                return self.write_all(data);
+            }
+            return Err(Error::new(ErrorKind::Request(RequestError::TooLong {
+                len: data.len(),
+                max: MAX_FRAME,
+            })));
         }
+        self.retrying(|sd| sd.write_frame(data))
+    }
+
+    fn write_frame(&mut self, data: &[u8]) -> Result<(), Error<TXErr, RXErr>> {
         let len = data.len() as u8;
-        self.ch.write(0xc0 - 1 + len)?;
-        for c in data {
-            self.ch.write(*c)?;
-        }
-        Ok(())
+        let start = self.ch.start_frame();
+        self.ch.send(0xc0 - 1 + len, data)?;
+        self.ch.finish_frame(start, data.len());
+        self.after_data(data.len())
     }
 
     /// `transfer` sends up to 64 bytes out over the SPIDriver's MOSI line,
@@ -127,23 +643,180 @@ where
     /// a slice with the same backing array.
     ///
     /// If the given slice is longer than 64 bytes then `write` will return
-    /// the `Request` error.
+    /// the `Request` error with `RequestError::TooLong`, unless automatic
+    /// chunking is enabled using `set_auto_chunk`.
+    ///
+    /// Transferring an empty slice does nothing, unless empty requests are
+    /// rejected using `set_reject_empty`.
     pub fn transfer<'v>(&mut self, data: &'v mut [u8]) -> Result<&'v [u8], Error<TXErr, RXErr>> {
-        if data.len() == 0 {
+        if data.is_empty() {
+            self.check_empty(0)?;
             return Ok(data); // nothing to do
         }
-        if data.len() > 64 {
-            return Err(Error::Request);
+        if data.len() > MAX_FRAME {
+            if self.auto_chunk {
+                return self.transfer_all(data);
+            }
+            return Err(Error::new(ErrorKind::Request(RequestError::TooLong {
+                len: data.len(),
+                max: MAX_FRAME,
+            })));
         }
+        if self.retry.is_none() {
+            self.transfer_frame(data)?;
+            return Ok(data);
+        }
+
+        // A failed attempt may have already overwritten some of the data
+        // with the response, so we keep the original to retry with.
+        let mut orig = [0_u8; MAX_FRAME];
+        orig[..data.len()].copy_from_slice(data);
+        self.retrying(|sd| {
+            data.copy_from_slice(&orig[..data.len()]);
+            sd.transfer_frame(data)
+        })?;
+        Ok(data)
+    }
+
+    fn transfer_frame(&mut self, data: &mut [u8]) -> Result<(), Error<TXErr, RXErr>> {
         let len = data.len() as u8;
-        self.ch.write(0x80 - 1 + len)?;
-        for i in 0..data.len() {
-            self.ch.write(data[i])?;
+        let start = self.ch.start_frame();
+        self.ch.send(0x80 - 1 + len, data)?;
+        self.ch.receive(data)?;
+        self.ch.finish_frame(start, data.len());
+        self.after_data(data.len())
+    }
+
+    /// `write_all` is like `write` but accepts a slice of any length,
+    /// splitting it into as many 64-byte protocol frames as necessary. Use
+    /// `set_chunk_size` to make the frames smaller.
+    ///
+    /// Because of the chunking behavior, larger messages may have inconsistent
+    /// timing at the chunk boundaries, which may affect devices with particularly
+    /// sensitive clock timing constraints.
+    pub fn write_all(&mut self, data: &[u8]) -> Result<(), Error<TXErr, RXErr>> {
+        self.write_all_with_progress(data, |_| ())
+    }
+
+    /// `write_all_vectored` is like `write_all`, but sends the concatenation
+    /// of all of the given slices.
+    ///
+    /// This is convenient for messages made of several separate parts, such
+    /// as a header followed by a payload, because the parts are packed into
+    /// 64-byte protocol frames without the caller first copying them into
+    /// a single buffer. Frames span the boundaries between the parts.
+    pub fn write_all_vectored(&mut self, bufs: &[&[u8]]) -> Result<(), Error<TXErr, RXErr>> {
+        self.check_empty(bufs.iter().map(|buf| buf.len()).sum())?;
+        let mut frame = [0_u8; MAX_FRAME];
+        let mut len = 0;
+        let mut progress = Progress::new(bufs.iter().map(|buf| buf.len()).sum());
+        for mut buf in bufs.iter().copied() {
+            while !buf.is_empty() {
+                let n = buf.len().min(self.chunk_size - len);
+                frame[len..len + n].copy_from_slice(&buf[..n]);
+                len += n;
+                buf = &buf[n..];
+                if len == self.chunk_size {
+                    let start = self.ch.start_frame();
+                    self.write(&frame[..len])
+                        .map_err(|e| e.at_offset(progress.done))?;
+                    self.advance(&mut progress, len, start);
+                    len = 0;
+                }
+            }
         }
-        for i in 0..data.len() {
-            data[i] = self.ch.read()?;
+        if len > 0 {
+            let start = self.ch.start_frame();
+            self.write(&frame[..len])
+                .map_err(|e| e.at_offset(progress.done))?;
+            self.advance(&mut progress, len, start);
         }
-        Ok(data)
+        Ok(())
+    }
+
+    /// `transfer_all` is like `transfer` but accepts a slice of any length,
+    /// splitting it into as many 64-byte protocol frames as necessary. Use
+    /// `set_chunk_size` to make the frames smaller.
+    ///
+    /// Each frame's response overwrites the corresponding part of the given
+    /// slice, so once `transfer_all` returns the whole slice contains the
+    /// data returned by the target device, in order.
+    ///
+    /// Because of the chunking behavior, larger messages may have inconsistent
+    /// timing at the chunk boundaries, which may affect devices with particularly
+    /// sensitive clock timing constraints.
+    pub fn transfer_all<'v>(
+        &mut self,
+        data: &'v mut [u8],
+    ) -> Result<&'v [u8], Error<TXErr, RXErr>> {
+        self.transfer_all_with_progress(data, |_| ())
+    }
+
+    /// `clock_bytes` generates clock pulses for the given number of bytes
+    /// while holding the MOSI line high, discarding anything the target
+    /// device sends in return.
+    ///
+    /// Some devices, such as SD cards and some ADCs, require a number of
+    /// dummy clock cycles during initialization. `clock_bytes` doesn't
+    /// change the chip select signal, so the caller must select or unselect
+    /// the target device as appropriate beforehand.
+    pub fn clock_bytes(&mut self, n: usize) -> Result<(), Error<TXErr, RXErr>> {
+        let idle = [0xff_u8; MAX_FRAME];
+        let mut progress = Progress::new(n);
+        while progress.done < n {
+            let len = (n - progress.done).min(self.chunk_size);
+            let start = self.ch.start_frame();
+            self.write(&idle[..len])
+                .map_err(|e| e.at_offset(progress.done))?;
+            self.advance(&mut progress, len, start);
+        }
+        Ok(())
+    }
+
+    /// `write_then_transfer` selects the target device, writes all of the
+    /// data from `write` and then performs a full-duplex transfer of all of
+    /// the data in `transfer`, before unselecting the target device again.
+    ///
+    /// This is for target devices whose transactions consist of a
+    /// write-only phase, such as a command or address, followed by a phase
+    /// where data flows in both directions. Both phases are split into
+    /// 64-byte protocol frames as necessary, as with `write_all` and
+    /// `transfer_all`, and the chip select signal is released even if one
+    /// of the phases fails.
+    pub fn write_then_transfer<'v>(
+        &mut self,
+        write: &[u8],
+        transfer: &'v mut [u8],
+    ) -> Result<&'v [u8], Error<TXErr, RXErr>> {
+        self.with_selected(move |sd| {
+            sd.write_all(write)?;
+            sd.transfer_all(transfer)
+        })
+    }
+
+    /// `send_raw` writes the given bytes to the SPIDriver's serial line
+    /// verbatim and then flushes.
+    ///
+    /// This is an advanced escape hatch for exercising firmware commands that
+    /// this library doesn't otherwise support. The library cannot know what
+    /// effect the bytes will have, so it's the caller's responsibility to
+    /// send only complete commands and to consume any response they produce
+    /// using `read_raw`. Leaving a partial command or an unread response
+    /// behind will cause all subsequent operations to misbehave.
+    pub fn send_raw(&mut self, data: &[u8]) -> Result<(), Error<TXErr, RXErr>> {
+        self.ch.write_all(data)?;
+        self.ch.flush()
+    }
+
+    /// `read_raw` reads exactly enough bytes from the SPIDriver's serial line
+    /// to fill the given slice, blocking until they have all arrived.
+    ///
+    /// This is the counterpart of `send_raw`, for reading the responses to
+    /// commands sent that way. Reading when the device has nothing more to
+    /// send will block forever.
+    pub fn read_raw<'v>(&mut self, buf: &'v mut [u8]) -> Result<&'v [u8], Error<TXErr, RXErr>> {
+        self.ch.read_all(buf)?;
+        Ok(buf)
     }
 
     // `write_byte` is like `write` but writes only a single byte.
@@ -151,65 +824,171 @@ where
     // This is a convenience helper to avoid constructing an array and a slice
     // from that array just to send one byte.
     pub fn write_byte(&mut self, b: u8) -> Result<(), Error<TXErr, RXErr>> {
-        self.ch.write(0xc0)?;
-        self.ch.write(b)
+        self.ch.send(0xc0, &[b])
     }
 }
 
-#[derive(Debug)]
-struct Channel<TX: serial::Write<u8>, RX: serial::Read<u8>> {
-    tx: TX,
-    rx: RX,
+/// `PinState` represents the level of one of the SPIDriver's auxillary
+/// output pins.
+///
+/// `PinState` can be converted from `bool`, with `true` representing `High`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum PinState {
+    /// `Low` represents the pin being driven low.
+    Low,
+
+    /// `High` represents the pin being driven high.
+    High,
 }
 
-impl<TX, RX, TXErr, RXErr> Channel<TX, RX>
-where
-    TX: serial::Write<u8, Error = TXErr>,
-    RX: serial::Read<u8, Error = RXErr>,
-{
-    pub fn read(&mut self) -> Result<u8, Error<TXErr, RXErr>> {
-        nb::block!(self.rx.read()).map_err(Error::rx)
+impl PinState {
+    fn protocol_arg(self) -> u8 {
+        match self {
+            PinState::Low => b'0',
+            PinState::High => b'1',
+        }
     }
+}
 
-    pub fn write(&mut self, c: u8) -> Result<(), Error<TXErr, RXErr>> {
-        nb::block!(self.tx.write(c)).map_err(Error::tx)
+impl core::fmt::Display for PinState {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str(match self {
+            PinState::Low => "low",
+            PinState::High => "high",
+        })
     }
+}
 
-    pub fn flush(&mut self) -> Result<(), Error<TXErr, RXErr>> {
-        nb::block!(self.tx.flush()).map_err(Error::tx)
+impl From<bool> for PinState {
+    fn from(high: bool) -> Self {
+        if high {
+            PinState::High
+        } else {
+            PinState::Low
+        }
     }
 }
 
-/// `Error` represents communication errors.
-#[derive(Debug)]
-pub enum Error<TXErr, RXErr> {
-    /// `Protocol` indicates that the library receieved an invalid or unexpected
-    /// response from the Bus Pirate in response to a request.
-    Protocol,
+/// `CsState` represents the state of the SPIDriver's chip select signal.
+///
+/// The chip select signal is active low, so `Asserted` corresponds to the
+/// signal being driven low and `Released` to it being driven high.
+///
+/// `CsState` can be converted from `bool`, with `true` representing
+/// `Asserted`, or from the `PinState` of the underlying signal.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum CsState {
+    /// `Asserted` represents the target device being selected.
+    Asserted,
 
-    /// `Request` indicates that the caller provided invalid arguments that
-    /// could not be checked at compile time.
-    Request,
+    /// `Released` represents the target device being unselected.
+    Released,
+}
 
-    /// `Write` indicates that the underlying serial write object returned an
-    /// error.
-    ///
-    /// The data is the error returned by the underlying serial implementation.
-    Write(TXErr),
+impl core::fmt::Display for CsState {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str(match self {
+            CsState::Asserted => "asserted",
+            CsState::Released => "released",
+        })
+    }
+}
 
-    /// `Read` indicates that the underlying serial read object returned an
-    /// error.
-    ///
-    /// The data is the error returned by the underlying serial implementation.
-    Read(RXErr),
+impl From<bool> for CsState {
+    fn from(asserted: bool) -> Self {
+        if asserted {
+            CsState::Asserted
+        } else {
+            CsState::Released
+        }
+    }
 }
 
-impl<TXErr, RXErr> Error<TXErr, RXErr> {
-    fn tx(got: TXErr) -> Self {
-        Error::Write(got)
+impl From<PinState> for CsState {
+    fn from(level: PinState) -> Self {
+        match level {
+            PinState::Low => CsState::Asserted,
+            PinState::High => CsState::Released,
+        }
     }
+}
+
+/// `SpiMode` represents one of the four combinations of SPI clock polarity
+/// (CPOL) and clock phase (CPHA).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum SpiMode {
+    /// `Mode0` is CPOL=0, CPHA=0: clock idles low, data captured on the
+    /// rising edge. This is the only mode the SPIDriver firmware supports.
+    Mode0 = 0,
 
-    fn rx(got: RXErr) -> Self {
-        Error::Read(got)
+    /// `Mode1` is CPOL=0, CPHA=1: clock idles low, data captured on the
+    /// falling edge.
+    Mode1 = 1,
+
+    /// `Mode2` is CPOL=1, CPHA=0: clock idles high, data captured on the
+    /// falling edge.
+    Mode2 = 2,
+
+    /// `Mode3` is CPOL=1, CPHA=1: clock idles high, data captured on the
+    /// rising edge.
+    Mode3 = 3,
+}
+
+impl core::fmt::Display for SpiMode {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "mode {}", *self as u8)
     }
 }
+
+/// `InitialPins` describes the states that `SPIDriver::new_with_pins` will
+/// put the SPIDriver's output pins in before returning.
+///
+/// The default is for the chip select signal to be released and for both of
+/// the auxillary pins to be high, which leaves the target device unselected
+/// and any active-low signals connected to the auxillary pins, such as reset
+/// lines, de-asserted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct InitialPins {
+    /// `cs` is the initial state of the chip select signal.
+    pub cs: CsState,
+
+    /// `a` is the initial level of the auxillary "A" pin.
+    pub a: PinState,
+
+    /// `b` is the initial level of the auxillary "B" pin.
+    pub b: PinState,
+}
+
+impl Default for InitialPins {
+    fn default() -> Self {
+        Self {
+            cs: CsState::Released,
+            a: PinState::High,
+            b: PinState::High,
+        }
+    }
+}
+
+// SPIDriver and the other types that wrap the serial implementations are
+// Send whenever those implementations and their errors are. This function is
+// never called, but it fails to compile if a change to any of those types
+// breaks that.
+#[allow(dead_code)]
+fn assert_send<TX, RX>()
+where
+    TX: serial::Write<u8> + Send,
+    RX: serial::Read<u8> + Send,
+    TX::Error: Send,
+    RX::Error: Send,
+{
+    fn check<T: Send>() {}
+    check::<SPIDriver<TX, RX>>();
+    check::<CrcCheckedSpiDriver<TX, RX>>();
+    check::<typestate::Unselected<TX, RX>>();
+    check::<Error<TX::Error, RX::Error>>();
+    check::<SPIDriverBuilder>();
+}
@@ -0,0 +1,17 @@
+// These macros forward to the macros of the same name in the `log` crate
+// when the `log` feature is enabled, and otherwise expand to nothing so that
+// the call sites don't each need their own cfg attribute.
+
+macro_rules! debug {
+    ($($arg:tt)*) => {
+        #[cfg(feature = "log")]
+        log::debug!($($arg)*);
+    };
+}
+
+macro_rules! trace {
+    ($($arg:tt)*) => {
+        #[cfg(feature = "log")]
+        log::trace!($($arg)*);
+    };
+}
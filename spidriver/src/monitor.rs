@@ -0,0 +1,177 @@
+use std::thread;
+use std::time::Duration;
+
+use embedded_hal::serial;
+
+use crate::{DeviceStatus, Error, SPIDriver};
+
+/// `Monitor` periodically polls the status of an SPIDriver and checks the
+/// reported measurements against user-specified thresholds.
+///
+/// An unusually high current draw is a common symptom of a short circuit on
+/// the target board, so a `Monitor` running alongside a test procedure can
+/// detect a faulty board before it is damaged any further.
+///
+/// `Monitor` is available only when the `std` feature is enabled, because it
+/// relies on the standard library to wait between polls.
+#[derive(Debug, Clone)]
+pub struct Monitor {
+    interval: Duration,
+    max_current: Option<u32>,
+    max_temperature: Option<f32>,
+}
+
+impl Monitor {
+    /// `new` creates a `Monitor` that polls at the given interval and that
+    /// initially has no thresholds set.
+    pub fn new(interval: Duration) -> Self {
+        Self {
+            interval,
+            max_current: None,
+            max_temperature: None,
+        }
+    }
+
+    /// `max_current` sets the highest acceptable current draw for the
+    /// target device, in milliamps.
+    pub fn max_current(mut self, milliamps: u32) -> Self {
+        self.max_current = Some(milliamps);
+        self
+    }
+
+    /// `max_temperature` sets the highest acceptable internal temperature of
+    /// the SPIDriver, in degrees Celsius.
+    pub fn max_temperature(mut self, celsius: f32) -> Self {
+        self.max_temperature = Some(celsius);
+        self
+    }
+
+    /// `check` compares the given status against the monitor's thresholds,
+    /// returning the first threshold that the status exceeds, if any.
+    pub fn check(&self, status: &DeviceStatus) -> Result<(), Violation> {
+        if let Some(limit) = self.max_current {
+            if status.current > limit {
+                return Err(Violation::Current {
+                    measured: status.current,
+                    limit,
+                });
+            }
+        }
+        if let Some(limit) = self.max_temperature {
+            if status.temperature > limit {
+                return Err(Violation::Temperature {
+                    measured: status.temperature,
+                    limit,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// `poll` requests the current status from the given SPIDriver once and
+    /// checks it against the monitor's thresholds.
+    pub fn poll<TX, RX, TXErr, RXErr>(
+        &self,
+        sd: &mut SPIDriver<TX, RX>,
+    ) -> Result<DeviceStatus, MonitorError<TXErr, RXErr>>
+    where
+        TX: serial::Write<u8, Error = TXErr>,
+        RX: serial::Read<u8, Error = RXErr>,
+    {
+        let status = sd.status()?;
+        self.check(&status).map_err(MonitorError::Threshold)?;
+        Ok(status)
+    }
+
+    /// `run` polls the given SPIDriver repeatedly, waiting for the monitor's
+    /// interval between each poll, until either a threshold is exceeded or
+    /// the given callback returns `false`.
+    ///
+    /// The callback is called with each status that is within the
+    /// thresholds. If a threshold is exceeded then `run` returns immediately
+    /// with the `Threshold` error, without calling the callback.
+    pub fn run<TX, RX, TXErr, RXErr>(
+        &self,
+        sd: &mut SPIDriver<TX, RX>,
+        mut on_status: impl FnMut(&DeviceStatus) -> bool,
+    ) -> Result<(), MonitorError<TXErr, RXErr>>
+    where
+        TX: serial::Write<u8, Error = TXErr>,
+        RX: serial::Read<u8, Error = RXErr>,
+    {
+        loop {
+            let status = self.poll(sd)?;
+            if !on_status(&status) {
+                return Ok(());
+            }
+            thread::sleep(self.interval);
+        }
+    }
+}
+
+/// `Violation` describes a threshold that was exceeded in a status report
+/// checked by a `Monitor`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Violation {
+    /// `Current` indicates that the target device drew more current than
+    /// allowed. Both values are in milliamps.
+    Current { measured: u32, limit: u32 },
+
+    /// `Temperature` indicates that the SPIDriver was hotter than allowed.
+    /// Both values are in degrees Celsius.
+    Temperature { measured: f32, limit: f32 },
+}
+
+impl std::fmt::Display for Violation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Violation::Current { measured, limit } => {
+                write!(f, "current {} mA exceeds limit of {} mA", measured, limit)
+            }
+            Violation::Temperature { measured, limit } => write!(
+                f,
+                "temperature {:.1} \u{b0}C exceeds limit of {:.1} \u{b0}C",
+                measured, limit
+            ),
+        }
+    }
+}
+
+/// `MonitorError` represents the ways that polling with a `Monitor` can fail.
+#[derive(Debug)]
+pub enum MonitorError<TXErr, RXErr> {
+    /// `Threshold` indicates that the SPIDriver reported a status that
+    /// exceeds one of the monitor's thresholds.
+    Threshold(Violation),
+
+    /// `Comms` indicates that the status request itself failed.
+    Comms(Error<TXErr, RXErr>),
+}
+
+impl<TXErr, RXErr> From<Error<TXErr, RXErr>> for MonitorError<TXErr, RXErr> {
+    fn from(err: Error<TXErr, RXErr>) -> Self {
+        MonitorError::Comms(err)
+    }
+}
+
+impl<TXErr, RXErr> std::fmt::Display for MonitorError<TXErr, RXErr> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MonitorError::Threshold(violation) => violation.fmt(f),
+            MonitorError::Comms(err) => write!(f, "status request failed: {}", err),
+        }
+    }
+}
+
+impl<TXErr, RXErr> std::error::Error for MonitorError<TXErr, RXErr>
+where
+    TXErr: std::error::Error + 'static,
+    RXErr: std::error::Error + 'static,
+{
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            MonitorError::Threshold(_) => None,
+            MonitorError::Comms(err) => Some(err),
+        }
+    }
+}
@@ -0,0 +1,153 @@
+use embedded_hal::serial;
+
+use crate::{Error, ErrorKind, RequestError, SPIDriver, MAX_FRAME};
+
+// Pending tracks the progress of a non-blocking operation between calls.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Pending {
+    opcode: u8,
+
+    // The number of bytes of the command written so far, including the
+    // opcode, and the number of bytes of the response read so far.
+    written: usize,
+    read: usize,
+
+    // The time the command started, for frame timing statistics.
+    started: Option<u64>,
+}
+
+impl<TX, RX, TXErr, RXErr> SPIDriver<TX, RX>
+where
+    TX: serial::Write<u8, Error = TXErr>,
+    RX: serial::Read<u8, Error = RXErr>,
+{
+    /// `try_write` is a non-blocking version of `write`, which sends up to
+    /// 64 bytes out over the SPIDriver's MOSI line.
+    ///
+    /// Rather than waiting for the serial writer, `try_write` returns
+    /// `nb::Error::WouldBlock` whenever the writer can't accept any more
+    /// data, after remembering how much of the command it has already sent.
+    /// The caller must then call `try_write` again with the same data, such
+    /// as by using `nb::block!` or by polling from a main loop between other
+    /// work, until it returns `Ok`. Calling other methods of the `SPIDriver`
+    /// before then will corrupt the command.
+    ///
+    /// Unlike `write`, `try_write` doesn't split longer data into several
+    /// frames even if automatic chunking is enabled, and doesn't retry or
+    /// perform the checks of checked mode.
+    pub fn try_write(&mut self, data: &[u8]) -> nb::Result<(), Error<TXErr, RXErr>> {
+        if data.is_empty() {
+            return self.check_empty(0).map_err(nb::Error::Other);
+        }
+        let opcode = self.start_pending(0xc0, data.len())?;
+        self.write_pending(opcode, data)?;
+        let started = self.pending.take().and_then(|pending| pending.started);
+        self.ch.finish_frame(started, data.len());
+        Ok(())
+    }
+
+    /// `try_transfer_step` is a non-blocking version of `transfer`, which
+    /// sends up to 64 bytes out over the SPIDriver's MOSI line and replaces
+    /// them with the data returned by the target device.
+    ///
+    /// Each call makes as much progress as possible without waiting, first
+    /// sending the command and then reading the response, and returns
+    /// `nb::Error::WouldBlock` if the transfer isn't yet complete. The caller
+    /// must keep calling `try_transfer_step` with the same slice until it
+    /// returns `Ok`, as described for `try_write`. The slice contains the
+    /// complete response only once `try_transfer_step` has returned `Ok`.
+    ///
+    /// The read timeout set using `set_read_timeout` doesn't apply, because
+    /// the caller decides how long to keep trying.
+    pub fn try_transfer_step(&mut self, data: &mut [u8]) -> nb::Result<(), Error<TXErr, RXErr>> {
+        if data.is_empty() {
+            return self.check_empty(0).map_err(nb::Error::Other);
+        }
+        let opcode = self.start_pending(0x80, data.len())?;
+        self.write_pending(opcode, data)?;
+        while let Some(pending) = self.pending {
+            if pending.read == data.len() {
+                break;
+            }
+            match self.ch.try_read() {
+                Ok(c) => data[pending.read] = c,
+                Err(e) => return Err(self.abandon_pending(e)),
+            }
+            self.pending = Some(Pending {
+                read: pending.read + 1,
+                ..pending
+            });
+        }
+        let started = self.pending.take().and_then(|pending| pending.started);
+        self.ch.tap_incoming(data);
+        self.ch.observe_response(data);
+        self.ch.finish_frame(started, data.len());
+        Ok(())
+    }
+
+    // start_pending begins a new non-blocking frame command with the given
+    // base opcode, or checks that the one already in progress is the same
+    // kind of command, returning the full opcode.
+    fn start_pending(&mut self, base: u8, len: usize) -> nb::Result<u8, Error<TXErr, RXErr>> {
+        if len > MAX_FRAME {
+            return Err(nb::Error::Other(Error::new(ErrorKind::Request(
+                RequestError::TooLong {
+                    len,
+                    max: MAX_FRAME,
+                },
+            ))));
+        }
+        let opcode = base - 1 + len as u8;
+        match self.pending {
+            Some(pending) if pending.opcode == opcode => Ok(opcode),
+            Some(_) => Err(nb::Error::Other(Error::new(ErrorKind::Request(
+                RequestError::InProgress,
+            )))),
+            None => {
+                self.ch.begin(opcode).map_err(nb::Error::Other)?;
+                self.pending = Some(Pending {
+                    opcode,
+                    written: 0,
+                    read: 0,
+                    started: self.ch.start_frame(),
+                });
+                Ok(opcode)
+            }
+        }
+    }
+
+    // write_pending writes as much of the pending command as the serial
+    // writer will accept without blocking.
+    fn write_pending(&mut self, opcode: u8, args: &[u8]) -> nb::Result<(), Error<TXErr, RXErr>> {
+        while let Some(pending) = self.pending {
+            let c = match pending.written {
+                0 => opcode,
+                n if n <= args.len() => args[n - 1],
+                _ => return Ok(()),
+            };
+            if let Err(e) = self.ch.try_write(c) {
+                return Err(self.abandon_pending(e));
+            }
+            self.pending = Some(Pending {
+                written: pending.written + 1,
+                ..pending
+            });
+            if pending.written == args.len() {
+                self.ch.sent(opcode, args);
+            }
+        }
+        Ok(())
+    }
+
+    // abandon_pending forgets the operation in progress if the given error
+    // means it can't be completed.
+    fn abandon_pending(
+        &mut self,
+        err: nb::Error<Error<TXErr, RXErr>>,
+    ) -> nb::Error<Error<TXErr, RXErr>> {
+        if let nb::Error::Other(_) = err {
+            self.pending = None;
+        }
+        err
+    }
+}
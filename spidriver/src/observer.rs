@@ -0,0 +1,69 @@
+use embedded_hal::serial;
+
+use crate::{Progress, SPIDriver};
+
+/// `ProtocolObserver` receives notifications about each command an
+/// `SPIDriver` sends and each response it receives, so that applications
+/// can implement logging, metrics, or protocol assertions.
+///
+/// Both methods have default implementations that do nothing, so an
+/// implementation need only override the ones it is interested in.
+///
+/// Observers are called synchronously during each operation, and so should
+/// return promptly. Because they receive only a shared reference, observers
+/// that record information must use interior mutability.
+pub trait ProtocolObserver {
+    /// `command` is called after a command is passed to the serial writer.
+    ///
+    /// `opcode` is the command's opcode byte, and `args` are the bytes that
+    /// followed it, such as the data for a write or transfer command.
+    fn command(&self, opcode: u8, args: &[u8]) {
+        let _ = (opcode, args);
+    }
+
+    /// `response` is called after the response to a command has been read
+    /// in full.
+    ///
+    /// `opcode` is the opcode of the command that the response belongs to,
+    /// and `data` is the raw response.
+    fn response(&self, opcode: u8, data: &[u8]) {
+        let _ = (opcode, data);
+    }
+
+    /// `progress` is called after each frame of an operation that splits
+    /// its data into several protocol frames, such as `write_all` or
+    /// `transfer_all`.
+    ///
+    /// This allows tools that send large amounts of data, such as flash
+    /// programmers, to show progress without threading a callback through
+    /// every call. See `Progress` for the information available.
+    fn progress(&self, progress: Progress) {
+        let _ = progress;
+    }
+}
+
+// Observer wraps an observer reference so that the types that hold one can
+// still implement Debug.
+#[derive(Clone, Copy)]
+pub(crate) struct Observer(pub &'static (dyn ProtocolObserver + Sync));
+
+impl core::fmt::Debug for Observer {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str("Observer")
+    }
+}
+
+impl<TX, RX, TXErr, RXErr> SPIDriver<TX, RX>
+where
+    TX: serial::Write<u8, Error = TXErr>,
+    RX: serial::Read<u8, Error = RXErr>,
+{
+    /// `set_observer` sets or clears the observer to notify about each
+    /// command sent and each response received.
+    ///
+    /// Data sent using `send_raw` or received using `read_raw` is not
+    /// reported, because it isn't associated with any particular command.
+    pub fn set_observer(&mut self, observer: Option<&'static (dyn ProtocolObserver + Sync)>) {
+        self.ch.set_observer(observer.map(Observer));
+    }
+}
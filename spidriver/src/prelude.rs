@@ -0,0 +1,12 @@
+//! Re-exports of the most commonly-used items in this crate.
+//!
+//! Import everything from this module to get started quickly:
+//!
+//! ```rust
+//! use spidriver::prelude::*;
+//! ```
+
+pub use crate::{
+    AuxPin, CsState, DcMode, Endianness, Error, InitialPins, PinRoles, PinState, SPIDriver,
+    SPIDriverBuilder, SimpleError, SpiMode, Target,
+};
@@ -0,0 +1,119 @@
+use embedded_hal::serial;
+
+use crate::{Error, SPIDriver};
+
+/// `Progress` reports how far through a long operation an `SPIDriver` is.
+///
+/// Progress is reported after each protocol frame, so `done` increases in
+/// steps of up to 64 bytes. Besides the callbacks of the `_with_progress`
+/// methods, progress is reported to the `ProtocolObserver`, if any, by all
+/// of the methods that split data into several frames.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Progress {
+    /// `done` is the number of bytes processed so far.
+    pub done: usize,
+
+    /// `total` is the total number of bytes in the operation.
+    pub total: usize,
+
+    /// `rate` is the transfer rate of the most recent frame in bytes per
+    /// second, measured from just before it was sent until just after it
+    /// completed. It is `None` unless a clock has been set using
+    /// `SPIDriver::set_stats_clock`.
+    pub rate: Option<u32>,
+}
+
+impl Progress {
+    pub(crate) fn new(total: usize) -> Self {
+        Self {
+            done: 0,
+            total,
+            rate: None,
+        }
+    }
+
+    /// `fraction` returns the proportion of the operation that is complete,
+    /// between 0.0 and 1.0.
+    pub fn fraction(&self) -> f32 {
+        if self.total == 0 {
+            return 1.0;
+        }
+        self.done as f32 / self.total as f32
+    }
+
+    /// `is_complete` returns `true` once all of the bytes have been
+    /// processed.
+    pub fn is_complete(&self) -> bool {
+        self.done >= self.total
+    }
+}
+
+impl<TX, RX, TXErr, RXErr> SPIDriver<TX, RX>
+where
+    TX: serial::Write<u8, Error = TXErr>,
+    RX: serial::Read<u8, Error = RXErr>,
+{
+    /// `write_all_with_progress` is like `write_all`, but calls the given
+    /// function after each frame is sent to report progress.
+    ///
+    /// The callback can use its own clock to estimate the time remaining,
+    /// since `SPIDriver` itself has no access to one.
+    pub fn write_all_with_progress(
+        &mut self,
+        data: &[u8],
+        mut on_progress: impl FnMut(Progress),
+    ) -> Result<(), Error<TXErr, RXErr>> {
+        self.check_empty(data.len())?;
+        let mut progress = Progress::new(data.len());
+        for chunk in data.chunks(self.chunk_size) {
+            trace!(
+                "writing frame at offset {} of {}",
+                progress.done,
+                progress.total
+            );
+            let start = self.ch.start_frame();
+            self.write(chunk).map_err(|e| e.at_offset(progress.done))?;
+            self.advance(&mut progress, chunk.len(), start);
+            on_progress(progress);
+        }
+        Ok(())
+    }
+
+    /// `transfer_all_with_progress` is like `transfer_all`, but calls the
+    /// given function after each frame's response is received to report
+    /// progress.
+    pub fn transfer_all_with_progress<'v>(
+        &mut self,
+        data: &'v mut [u8],
+        mut on_progress: impl FnMut(Progress),
+    ) -> Result<&'v [u8], Error<TXErr, RXErr>> {
+        self.check_empty(data.len())?;
+        let mut progress = Progress::new(data.len());
+        for chunk in data.chunks_mut(self.chunk_size) {
+            trace!(
+                "transferring frame at offset {} of {}",
+                progress.done,
+                progress.total
+            );
+            let start = self.ch.start_frame();
+            self.transfer(chunk)
+                .map_err(|e| e.at_offset(progress.done))?;
+            self.advance(&mut progress, chunk.len(), start);
+            on_progress(progress);
+        }
+        Ok(data)
+    }
+
+    // advance records that a frame of the given length, which started at
+    // the time returned by Channel::start_frame, has completed, and reports
+    // the new progress to the observer.
+    pub(crate) fn advance(&mut self, progress: &mut Progress, len: usize, start: Option<u64>) {
+        progress.done += len;
+        progress.rate = start.zip(self.ch.start_frame()).map(|(start, now)| {
+            let micros = now.wrapping_sub(start).max(1);
+            (len as u64 * 1_000_000 / micros).min(u32::MAX as u64) as u32
+        });
+        self.ch.observe_progress(*progress);
+    }
+}
@@ -0,0 +1,56 @@
+use embedded_hal::blocking::delay::DelayUs;
+use embedded_hal::serial;
+
+use crate::{AuxPin, Error, PinState, SPIDriver};
+
+impl<TX, RX, TXErr, RXErr> SPIDriver<TX, RX>
+where
+    TX: serial::Write<u8, Error = TXErr>,
+    RX: serial::Read<u8, Error = RXErr>,
+{
+    /// `pulse` drives the given auxillary pin low, waits for at least the
+    /// given number of microseconds using the given delay implementation,
+    /// and then drives the pin high again.
+    ///
+    /// After sending the command to drive the pin low, `pulse` waits for the
+    /// SPIDriver to confirm it has processed that command, as with `sync`,
+    /// before the wait begins. The pulse on the pin will therefore be at
+    /// least as long as requested even if the serial link buffers data. It
+    /// may be longer, depending on serial latency.
+    pub fn pulse(
+        &mut self,
+        pin: AuxPin,
+        width_us: u32,
+        delay: &mut impl DelayUs<u32>,
+    ) -> Result<(), Error<TXErr, RXErr>> {
+        self.pulse_with(pin, || delay.delay_us(width_us))
+    }
+
+    /// `pulse_a` drives the auxillary "A" pin low for at least the given
+    /// duration, as with `pulse`, using the standard library to wait.
+    ///
+    /// This method is available only when the `std` feature is enabled.
+    #[cfg(feature = "std")]
+    pub fn pulse_a(&mut self, width: std::time::Duration) -> Result<(), Error<TXErr, RXErr>> {
+        self.pulse_with(AuxPin::A, || std::thread::sleep(width))
+    }
+
+    /// `pulse_b` drives the auxillary "B" pin low for at least the given
+    /// duration, as with `pulse`, using the standard library to wait.
+    ///
+    /// This method is available only when the `std` feature is enabled.
+    #[cfg(feature = "std")]
+    pub fn pulse_b(&mut self, width: std::time::Duration) -> Result<(), Error<TXErr, RXErr>> {
+        self.pulse_with(AuxPin::B, || std::thread::sleep(width))
+    }
+
+    fn pulse_with(&mut self, pin: AuxPin, wait: impl FnOnce()) -> Result<(), Error<TXErr, RXErr>> {
+        // set_aux only flushes to the serial writer, which may still be
+        // buffering, so we wait for an echo to be sure the device has
+        // actually driven the pin low before we start timing.
+        self.set_aux(pin, PinState::Low)?;
+        self.sync()?;
+        wait();
+        self.set_aux(pin, PinState::High)
+    }
+}
@@ -0,0 +1,126 @@
+use embedded_hal::serial;
+
+use crate::{Error, ErrorKind, RequestError, SPIDriver};
+
+/// `RegisterConfig` describes how a particular target device expects its
+/// registers to be addressed.
+///
+/// Many sensor and radio chips use the convention of sending a register
+/// address, with one bit indicating whether the operation is a read or a
+/// write, followed by the register data. The default configuration is a
+/// one-byte address with the most significant bit set for reads, which is
+/// the most common variant.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct RegisterConfig {
+    /// `address_width` is the number of address bytes sent before the
+    /// register data, either 1 or 2. Two-byte addresses are sent most
+    /// significant byte first.
+    pub address_width: u8,
+
+    /// `read_flag` is combined with the register address using bitwise OR
+    /// when reading.
+    pub read_flag: u16,
+
+    /// `write_flag` is combined with the register address using bitwise OR
+    /// when writing.
+    pub write_flag: u16,
+}
+
+impl Default for RegisterConfig {
+    fn default() -> Self {
+        Self {
+            address_width: 1,
+            read_flag: 0x80,
+            write_flag: 0x00,
+        }
+    }
+}
+
+/// `RegisterAccess` reads and writes the registers of a target device that
+/// uses the "address followed by data" register convention, as described by
+/// a `RegisterConfig`.
+///
+/// Each operation selects the target device, sends the address, transfers
+/// the register data, and then unselects the target device again.
+pub struct RegisterAccess<'a, TX, RX>
+where
+    TX: serial::Write<u8>,
+    RX: serial::Read<u8>,
+{
+    sd: &'a mut SPIDriver<TX, RX>,
+    config: RegisterConfig,
+}
+
+impl<'a, TX, RX, TXErr, RXErr> RegisterAccess<'a, TX, RX>
+where
+    TX: serial::Write<u8, Error = TXErr>,
+    RX: serial::Read<u8, Error = RXErr>,
+{
+    /// `new` binds the given `SPIDriver` to a register configuration.
+    ///
+    /// Returns the `Request` error with `RequestError::AddressWidth` if the
+    /// configuration's address width is not supported.
+    pub fn new(
+        sd: &'a mut SPIDriver<TX, RX>,
+        config: RegisterConfig,
+    ) -> Result<Self, Error<TXErr, RXErr>> {
+        if config.address_width != 1 && config.address_width != 2 {
+            return Err(Error::new(ErrorKind::Request(RequestError::AddressWidth(
+                config.address_width,
+            ))));
+        }
+        Ok(Self { sd, config })
+    }
+
+    /// `read_reg` reads enough consecutive register bytes starting at the
+    /// given address to fill the given buffer.
+    ///
+    /// The target device receives zero bytes while its response is read.
+    pub fn read_reg<'v>(
+        &mut self,
+        addr: u16,
+        buf: &'v mut [u8],
+    ) -> Result<&'v [u8], Error<TXErr, RXErr>> {
+        let (addr_buf, addr_len) = self.encode_addr(addr | self.config.read_flag);
+        for c in buf.iter_mut() {
+            *c = 0;
+        }
+        self.sd.write_then_transfer(&addr_buf[..addr_len], buf)
+    }
+
+    /// `write_reg` writes the given data to consecutive registers starting
+    /// at the given address.
+    pub fn write_reg(&mut self, addr: u16, data: &[u8]) -> Result<(), Error<TXErr, RXErr>> {
+        let (addr_buf, addr_len) = self.encode_addr(addr | self.config.write_flag);
+        self.sd.with_selected(|sd| {
+            sd.write(&addr_buf[..addr_len])?;
+            sd.write_all(data)
+        })
+    }
+
+    /// `modify_reg` reads the single-byte register at the given address,
+    /// passes its value to the given function, and writes the result back,
+    /// returning the value that was written.
+    ///
+    /// The read and the write are separate transactions, so this is not
+    /// atomic with respect to changes made by the target device itself.
+    pub fn modify_reg(
+        &mut self,
+        addr: u16,
+        f: impl FnOnce(u8) -> u8,
+    ) -> Result<u8, Error<TXErr, RXErr>> {
+        let mut buf = [0_u8; 1];
+        self.read_reg(addr, &mut buf)?;
+        let v = f(buf[0]);
+        self.write_reg(addr, &[v])?;
+        Ok(v)
+    }
+
+    fn encode_addr(&self, addr: u16) -> ([u8; 2], usize) {
+        match self.config.address_width {
+            1 => ([addr as u8, 0], 1),
+            _ => (addr.to_be_bytes(), 2),
+        }
+    }
+}
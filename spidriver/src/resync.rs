@@ -0,0 +1,118 @@
+use embedded_hal::serial;
+
+use crate::{Error, ProtocolFault, SPIDriver, MAX_FRAME};
+
+// The known-answer sequence used to confirm that the host and device are
+// back in step. These are the same bytes used by probe_capabilities.
+const RESYNC_PATTERN: [u8; 4] = [0x55, 0x00, 0xff, 0xaa];
+
+// The number of stale bytes resync is willing to discard while waiting for
+// the first byte of the known-answer sequence to come back.
+const RESYNC_MAX_STALE: usize = 4 * MAX_FRAME;
+
+impl<TX, RX, TXErr, RXErr> SPIDriver<TX, RX>
+where
+    TX: serial::Write<u8, Error = TXErr>,
+    RX: serial::Read<u8, Error = RXErr>,
+{
+    /// `resync` attempts to bring the host and the SPIDriver back into step
+    /// after a communication glitch, such as an operation that was
+    /// interrupted part way through, leaving the device waiting for more
+    /// data or still sending responses to an earlier transfer.
+    ///
+    /// It first sends enough filler bytes to complete any partially-sent
+    /// command and discards any responses already waiting to be read. It
+    /// then sends a sequence of echo commands and skips over any stale data
+    /// that arrives before the first expected answer, returning the
+    /// `Protocol` error if the expected answers don't eventually arrive.
+    ///
+    /// If the device was part way through a write or transfer command then
+    /// some of the filler bytes will be sent to the target device, so the
+    /// target device may itself need to be reset afterwards. The chip select
+    /// signal is left in whatever state it was in before the glitch.
+    ///
+    /// `resync` clears the poisoned state that follows a communication
+    /// error, as described under `ErrorKind::Poisoned`, and the unresponsive
+    /// state described under `ErrorKind::DeviceUnresponsive`, if it
+    /// succeeds.
+    ///
+    /// If no read timeout is set then `resync` will block forever if the
+    /// device isn't responding at all.
+    pub fn resync(&mut self) -> Result<(), Error<TXErr, RXErr>> {
+        // Resynchronizing is how we recover from an unknown state, so it
+        // must be allowed even when poisoned. Any error during resync will
+        // poison the channel again.
+        self.ch.poisoned = false;
+        self.ch.unresponsive = false;
+
+        // Stale data is expected here, so we suspend strict response mode.
+        let strict = self.ch.set_strict(false);
+        let result = self.resync_inner();
+        self.ch.set_strict(strict);
+        result
+    }
+
+    fn resync_inner(&mut self) -> Result<(), Error<TXErr, RXErr>> {
+        // '@' is not a valid command, so the device ignores these bytes
+        // unless they complete the data portion of an earlier command.
+        self.ch.send(b'@', &[b'@'; MAX_FRAME])?;
+        self.ch.flush()?;
+        self.ch.drain()?;
+
+        for c in RESYNC_PATTERN {
+            self.ch.send(b'e', &[c])?;
+        }
+        self.ch.flush()?;
+
+        let mut skipped = 0;
+        loop {
+            let c = self.ch.read()?;
+            self.ch.tap_incoming(&[c]);
+            if c == RESYNC_PATTERN[0] {
+                break;
+            }
+            skipped += 1;
+            if skipped > RESYNC_MAX_STALE {
+                return Err(self.ch.protocol_error(ProtocolFault::UnexpectedResponse));
+            }
+        }
+        let mut got = [0_u8; RESYNC_PATTERN.len() - 1];
+        self.ch.read_all(&mut got)?;
+        if got != RESYNC_PATTERN[1..] {
+            return Err(self.ch.protocol_error(ProtocolFault::UnexpectedResponse));
+        }
+
+        // The device is now in a known state, so checked mode can start
+        // counting afresh.
+        self.unchecked = 0;
+        Ok(())
+    }
+
+    /// `is_poisoned` returns `true` if an earlier communication error has
+    /// left the connection in an unknown state, in which case most
+    /// operations will fail with `ErrorKind::Poisoned` until the caller recovers
+    /// using `resync` or `reset_state`.
+    pub fn is_poisoned(&self) -> bool {
+        self.ch.poisoned
+    }
+
+    /// `is_unresponsive` returns `true` if a `Watchdog` has found that the
+    /// device stopped responding, in which case most operations will fail
+    /// with `ErrorKind::DeviceUnresponsive` until the caller recovers using
+    /// `resync` or `reset_state`.
+    pub fn is_unresponsive(&self) -> bool {
+        self.ch.unresponsive
+    }
+
+    /// `reset_state` clears the poisoned and unresponsive states without
+    /// communicating with the device.
+    ///
+    /// This is for situations where the caller knows that the host and
+    /// device are still in step despite the error, such as when the caller
+    /// has already recovered by some other means. Otherwise, prefer
+    /// `resync`.
+    pub fn reset_state(&mut self) {
+        self.ch.poisoned = false;
+        self.ch.unresponsive = false;
+    }
+}
@@ -0,0 +1,93 @@
+use embedded_hal::serial;
+
+use crate::{Error, SPIDriver};
+
+/// `RetryPolicy` describes how an `SPIDriver` should respond to transient
+/// communication errors, as reported by `Error::is_transient`.
+///
+/// When a retry policy is set using `SPIDriver::set_retry_policy`, each
+/// individual write or transfer frame that fails with a transient error is
+/// retried after resynchronizing with the device using `SPIDriver::resync`.
+/// Longer operations such as `write_all` retry only the frame that failed,
+/// rather than starting over from the beginning.
+///
+/// Resynchronizing may send some filler bytes to the target device if the
+/// failure left a frame partially sent, so retrying is appropriate only for
+/// target devices that can tolerate that, such as those that ignore data
+/// while they are not selected or that start a new command on each select.
+#[derive(Debug, Clone, Copy)]
+pub struct RetryPolicy {
+    /// `max_attempts` is the maximum number of times to try each frame,
+    /// including the first attempt.
+    pub max_attempts: u32,
+
+    /// `backoff`, if set, is called before each retry with the number of
+    /// attempts made so far, so that the caller can wait for the connection
+    /// to recover using whatever delay mechanism is available.
+    pub backoff: Option<fn(attempt: u32)>,
+}
+
+impl RetryPolicy {
+    /// `new` returns a policy that tries each frame at most `max_attempts`
+    /// times, retrying immediately.
+    pub fn new(max_attempts: u32) -> Self {
+        Self {
+            max_attempts,
+            backoff: None,
+        }
+    }
+
+    /// `backoff` returns a copy of the policy that calls the given function
+    /// before each retry.
+    pub fn backoff(self, backoff: fn(attempt: u32)) -> Self {
+        Self {
+            backoff: Some(backoff),
+            ..self
+        }
+    }
+}
+
+impl<TX, RX, TXErr, RXErr> SPIDriver<TX, RX>
+where
+    TX: serial::Write<u8, Error = TXErr>,
+    RX: serial::Read<u8, Error = RXErr>,
+{
+    /// `set_retry_policy` sets or clears the policy for retrying frames that
+    /// fail with transient errors. No retries are attempted by default.
+    pub fn set_retry_policy(&mut self, policy: Option<RetryPolicy>) {
+        self.retry = policy;
+    }
+
+    /// `retry_policy` returns the current retry policy, if any.
+    pub fn retry_policy(&self) -> Option<RetryPolicy> {
+        self.retry
+    }
+
+    // retrying calls the given function, which should perform a single
+    // self-contained protocol exchange, and calls it again in accordance
+    // with the retry policy if it fails with a transient error.
+    pub(crate) fn retrying<R>(
+        &mut self,
+        mut f: impl FnMut(&mut Self) -> Result<R, Error<TXErr, RXErr>>,
+    ) -> Result<R, Error<TXErr, RXErr>> {
+        let policy = match self.retry {
+            Some(policy) => policy,
+            None => return f(self),
+        };
+        let mut attempt = 1;
+        loop {
+            match f(self) {
+                Err(err) if err.is_transient() && attempt < policy.max_attempts => {
+                    if let Some(backoff) = policy.backoff {
+                        backoff(attempt);
+                    }
+                    debug!("retrying after transient error: {}", err);
+                    attempt += 1;
+                    self.ch.stats.retries = self.ch.stats.retries.wrapping_add(1);
+                    self.resync()?;
+                }
+                result => return result,
+            }
+        }
+    }
+}
@@ -0,0 +1,221 @@
+use embedded_hal::serial;
+
+use crate::{CsState, Error, ErrorKind, PinState, RequestError, SPIDriver};
+
+/// `AuxPin` identifies one of the SPIDriver's auxillary output pins.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum AuxPin {
+    /// `A` is the auxillary "A" pin.
+    A,
+
+    /// `B` is the auxillary "B" pin.
+    B,
+}
+
+/// `PinRoles` assigns logical roles to the SPIDriver's auxillary pins, so
+/// that code driving a target device can refer to signals by their purpose
+/// rather than by which pin they happen to be connected to.
+///
+/// Assign roles using `SPIDriver::set_pin_roles` and then use methods like
+/// `SPIDriver::set_dc` and `SPIDriver::pulse_reset`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct PinRoles {
+    /// `dc` is the pin connected to the target device's data/command select
+    /// signal, which is low for commands and high for data.
+    pub dc: Option<AuxPin>,
+
+    /// `reset` is the pin connected to the target device's active-low reset
+    /// signal.
+    pub reset: Option<AuxPin>,
+
+    /// `secondary_cs` is the pin connected to the active-low chip select
+    /// signal of a second target device sharing the SPI bus. See
+    /// `SPIDriver::select_target`.
+    pub secondary_cs: Option<AuxPin>,
+}
+
+/// `PinRole` identifies one of the logical roles that can be assigned to
+/// an auxillary pin using `PinRoles`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum PinRole {
+    /// `DataCommand` is the data/command select role.
+    DataCommand,
+
+    /// `Reset` is the reset role.
+    Reset,
+
+    /// `SecondaryCs` is the secondary chip select role.
+    SecondaryCs,
+}
+
+impl core::fmt::Display for PinRole {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str(match self {
+            PinRole::DataCommand => "data/command select",
+            PinRole::Reset => "reset",
+            PinRole::SecondaryCs => "secondary chip select",
+        })
+    }
+}
+
+/// `Target` identifies one of the two target devices that can share the
+/// SPI bus when an auxillary pin is assigned the secondary chip select role.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Target {
+    /// `Primary` is the target device connected to the SPIDriver's own chip
+    /// select signal.
+    Primary,
+
+    /// `Secondary` is the target device connected to the auxillary pin
+    /// assigned the secondary chip select role.
+    Secondary,
+}
+
+/// `DcMode` represents the states of a data/command select signal.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum DcMode {
+    /// `Command` indicates that subsequent bytes are commands, and is
+    /// represented by driving the signal low.
+    Command,
+
+    /// `Data` indicates that subsequent bytes are data, and is represented
+    /// by driving the signal high.
+    Data,
+}
+
+impl<TX, RX, TXErr, RXErr> SPIDriver<TX, RX>
+where
+    TX: serial::Write<u8, Error = TXErr>,
+    RX: serial::Read<u8, Error = RXErr>,
+{
+    /// `set_pin_roles` assigns logical roles to the auxillary pins, for use
+    /// with `set_dc` and `pulse_reset`.
+    pub fn set_pin_roles(&mut self, roles: PinRoles) {
+        self.roles = roles;
+    }
+
+    /// `pin_roles` returns the currently-assigned pin roles.
+    pub fn pin_roles(&self) -> PinRoles {
+        self.roles
+    }
+
+    /// `set_aux` sets the level of the given auxillary pin.
+    pub fn set_aux(
+        &mut self,
+        pin: AuxPin,
+        state: impl Into<PinState>,
+    ) -> Result<(), Error<TXErr, RXErr>> {
+        match pin {
+            AuxPin::A => self.set_a(state),
+            AuxPin::B => self.set_b(state),
+        }
+    }
+
+    /// `set_dc` drives the pin assigned the data/command role to the level
+    /// corresponding to the given mode.
+    ///
+    /// Returns the `Request` error with `RequestError::NoPinRole` if no pin
+    /// has been assigned that role.
+    pub fn set_dc(&mut self, mode: DcMode) -> Result<(), Error<TXErr, RXErr>> {
+        let pin = self
+            .roles
+            .dc
+            .ok_or(Error::new(ErrorKind::Request(RequestError::NoPinRole(
+                PinRole::DataCommand,
+            ))))?;
+        let level = match mode {
+            DcMode::Command => PinState::Low,
+            DcMode::Data => PinState::High,
+        };
+        self.set_aux(pin, level)
+    }
+
+    /// `pulse_reset` resets the target device by driving the pin assigned
+    /// the reset role low and then high again.
+    ///
+    /// The pulse is only as long as it takes to send the second command to
+    /// the SPIDriver, so this is suitable only for target devices that can
+    /// accept a very short reset pulse.
+    ///
+    /// Returns the `Request` error with `RequestError::NoPinRole` if no pin
+    /// has been assigned that role.
+    pub fn pulse_reset(&mut self) -> Result<(), Error<TXErr, RXErr>> {
+        let pin =
+            self.roles
+                .reset
+                .ok_or(Error::new(ErrorKind::Request(RequestError::NoPinRole(
+                    PinRole::Reset,
+                ))))?;
+        self.set_aux(pin, PinState::Low)?;
+        self.set_aux(pin, PinState::High)
+    }
+
+    /// `select_target` selects the given target device, first making sure
+    /// that the other target device is unselected.
+    ///
+    /// The two chip select signals are updated with a single flush, in an
+    /// order that ensures both targets are never selected at the same time.
+    ///
+    /// Returns the `Request` error with `RequestError::NoPinRole` if no pin
+    /// has been assigned the secondary chip select role.
+    pub fn select_target(&mut self, target: Target) -> Result<(), Error<TXErr, RXErr>> {
+        let pin = self.secondary_cs_pin()?;
+        let (cs, aux) = match target {
+            Target::Primary => (CsState::Asserted, PinState::High),
+            Target::Secondary => (CsState::Released, PinState::Low),
+        };
+        // set_pins updates the auxillary pins before asserting the chip
+        // select signal and after releasing it, so whichever target is
+        // currently selected is always unselected first.
+        match pin {
+            AuxPin::A => self.set_pins(Some(cs), Some(aux), None),
+            AuxPin::B => self.set_pins(Some(cs), None, Some(aux)),
+        }
+    }
+
+    /// `unselect_targets` unselects both target devices.
+    ///
+    /// Returns the `Request` error with `RequestError::NoPinRole` if no pin
+    /// has been assigned the secondary chip select role.
+    pub fn unselect_targets(&mut self) -> Result<(), Error<TXErr, RXErr>> {
+        match self.secondary_cs_pin()? {
+            AuxPin::A => self.set_pins(Some(CsState::Released), Some(PinState::High), None),
+            AuxPin::B => self.set_pins(Some(CsState::Released), None, Some(PinState::High)),
+        }
+    }
+
+    /// `with_target` selects the given target device, calls the given
+    /// function, and then unselects both target devices again.
+    ///
+    /// This is like `with_selected`, but for use when two target devices
+    /// share the bus. Both targets are unselected even if the function
+    /// returns an error.
+    pub fn with_target<R, E>(
+        &mut self,
+        target: Target,
+        f: impl FnOnce(&mut Self) -> Result<R, E>,
+    ) -> Result<R, E>
+    where
+        E: From<Error<TXErr, RXErr>>,
+    {
+        self.select_target(target)?;
+        let result = f(self);
+        let unselected = self.unselect_targets();
+        let ret = result?;
+        unselected?;
+        Ok(ret)
+    }
+
+    fn secondary_cs_pin(&self) -> Result<AuxPin, Error<TXErr, RXErr>> {
+        self.roles
+            .secondary_cs
+            .ok_or(Error::new(ErrorKind::Request(RequestError::NoPinRole(
+                PinRole::SecondaryCs,
+            ))))
+    }
+}
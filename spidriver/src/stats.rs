@@ -0,0 +1,127 @@
+use embedded_hal::serial;
+
+use crate::{Clock, SPIDriver};
+
+/// `Stats` counts activity on the connection to an SPIDriver, to help with
+/// diagnosing throughput and reliability problems.
+///
+/// All of the counters wrap around on overflow.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Stats {
+    /// `commands` is the number of protocol commands sent to the device.
+    pub commands: u32,
+
+    /// `bytes_written` is the total number of bytes written to the serial
+    /// port, including command opcodes.
+    pub bytes_written: u64,
+
+    /// `bytes_read` is the total number of bytes read from the serial port.
+    pub bytes_read: u64,
+
+    /// `frames` is the number of write and transfer frames sent. Long
+    /// operations such as `write_all` send one frame per 64 bytes of data.
+    pub frames: u32,
+
+    /// `retries` is the number of frames retried under the retry policy set
+    /// using `SPIDriver::set_retry_policy`.
+    pub retries: u32,
+
+    /// `errors` is the number of communication errors encountered, which
+    /// includes protocol errors, timeouts, and serial port errors, but not
+    /// errors caused by invalid requests.
+    pub errors: u32,
+
+    /// `data_bytes` is the number of bytes of data sent in write and
+    /// transfer frames, excluding the command opcodes.
+    pub data_bytes: u64,
+
+    /// `timed_bytes` is the number of bytes of data sent in frames that were
+    /// timed, as described under `frame_micros`.
+    pub timed_bytes: u64,
+
+    /// `frame_micros` is the total time spent sending write and transfer
+    /// frames, including waiting for the responses to transfers, in
+    /// microseconds. Frames are timed only while a clock is set using
+    /// `SPIDriver::set_stats_clock`.
+    pub frame_micros: u64,
+
+    /// `last_frame_micros` is the time taken by the most recent timed
+    /// frame, in microseconds.
+    pub last_frame_micros: u32,
+
+    /// `max_frame_micros` is the time taken by the slowest timed frame, in
+    /// microseconds.
+    pub max_frame_micros: u32,
+
+    /// `recent_throughput` is a moving average of the throughput of recent
+    /// timed frames, in data bytes per second, which reacts to changes more
+    /// quickly than `throughput`.
+    pub recent_throughput: u32,
+}
+
+impl Stats {
+    /// `throughput` returns the average throughput of all of the timed
+    /// frames, in data bytes per second, or `None` if no frames have been
+    /// timed.
+    ///
+    /// Comparing this with the SPI clock rate shows which of the serial link
+    /// and the SPI bus is limiting throughput: the SPI bus can carry at most
+    /// one byte per eight clock cycles.
+    pub fn throughput(&self) -> Option<u64> {
+        if self.frame_micros == 0 {
+            return None;
+        }
+        Some(self.timed_bytes * 1_000_000 / self.frame_micros)
+    }
+
+    // record_frame updates the counters for a frame of the given length,
+    // which took the given time if it was timed.
+    pub(crate) fn record_frame(&mut self, len: usize, micros: Option<u64>) {
+        self.data_bytes = self.data_bytes.wrapping_add(len as u64);
+        let micros = match micros {
+            Some(micros) => micros.max(1),
+            None => return,
+        };
+        self.timed_bytes = self.timed_bytes.wrapping_add(len as u64);
+        self.frame_micros = self.frame_micros.wrapping_add(micros);
+        let clamped = micros.min(u32::MAX as u64) as u32;
+        self.last_frame_micros = clamped;
+        self.max_frame_micros = self.max_frame_micros.max(clamped);
+
+        // The moving average gives each new frame a weight of one eighth.
+        let rate = (len as u64 * 1_000_000 / micros).min(u32::MAX as u64) as u32;
+        self.recent_throughput = if self.recent_throughput == 0 {
+            rate
+        } else {
+            ((self.recent_throughput as u64 * 7 + rate as u64) / 8) as u32
+        };
+    }
+}
+
+impl<TX, RX, TXErr, RXErr> SPIDriver<TX, RX>
+where
+    TX: serial::Write<u8, Error = TXErr>,
+    RX: serial::Read<u8, Error = RXErr>,
+{
+    /// `stats` returns the activity counters accumulated since the
+    /// `SPIDriver` was created or since the last call to `reset_stats`.
+    pub fn stats(&self) -> Stats {
+        self.ch.stats
+    }
+
+    /// `set_stats_clock` sets or clears the clock used to time each write
+    /// and transfer frame for the timing and throughput figures in `Stats`.
+    ///
+    /// Frames aren't timed by default, because `SPIDriver` itself has no
+    /// access to a clock. When the `std` feature is enabled, `StdClock` is a
+    /// suitable clock for use on a general computing platform.
+    pub fn set_stats_clock(&mut self, clock: Option<&'static (dyn Clock + Sync)>) {
+        self.ch.set_stats_clock(clock);
+    }
+
+    /// `reset_stats` sets all of the activity counters back to zero.
+    pub fn reset_stats(&mut self) {
+        self.ch.stats = Stats::default();
+    }
+}
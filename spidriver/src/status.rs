@@ -0,0 +1,162 @@
+use crate::{CsState, PinState};
+
+/// `REPORT_LEN` is the fixed length of the status report the SPIDriver sends
+/// in response to the `?` command, including its surrounding brackets.
+pub(crate) const REPORT_LEN: usize = 80;
+
+/// `DeviceStatus` is a snapshot of the information the SPIDriver reports
+/// about itself and the target device, as returned by `SPIDriver::status`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct DeviceStatus {
+    /// `model` is the product identifier of the SPIDriver, such as
+    /// "spidriver1".
+    pub model: Identifier,
+
+    /// `serial` is the serial number of the SPIDriver.
+    pub serial: Identifier,
+
+    /// `uptime` is the number of seconds since the SPIDriver was powered on.
+    pub uptime: u32,
+
+    /// `voltage` is the USB supply voltage, in volts.
+    pub voltage: f32,
+
+    /// `current` is the current drawn by the target device, in milliamps.
+    pub current: u32,
+
+    /// `temperature` is the SPIDriver's internal temperature, in degrees
+    /// Celsius.
+    pub temperature: f32,
+
+    /// `a` is the current level of the auxillary "A" pin.
+    pub a: PinState,
+
+    /// `b` is the current level of the auxillary "B" pin.
+    pub b: PinState,
+
+    /// `cs` is the current state of the chip select signal.
+    pub cs: CsState,
+
+    /// `crc` is the SPIDriver's running CRC-16-CCITT of all of the data
+    /// bytes it has sent over SPI.
+    pub crc: u16,
+
+    /// `debug` is the set of debug flags the firmware reports, if any. Only
+    /// some firmware versions include debug flags in their status report.
+    pub debug: Option<u32>,
+}
+
+impl DeviceStatus {
+    // `parse` interprets a raw status report, returning `None` if it is not
+    // in the expected format.
+    //
+    // The report is a sequence of space-separated fields in square brackets,
+    // padded with spaces to a fixed length. Newer firmware may append
+    // additional fields after the ones we know about, so we ignore any
+    // trailing fields we don't recognize.
+    pub(crate) fn parse(raw: &[u8]) -> Option<Self> {
+        let body = match raw {
+            [b'[', body @ .., b']'] => core::str::from_utf8(body).ok()?,
+            _ => return None,
+        };
+        let mut fields = body.split_whitespace();
+        Some(Self {
+            model: Identifier::new(fields.next()?)?,
+            serial: Identifier::new(fields.next()?)?,
+            uptime: fields.next()?.parse().ok()?,
+            voltage: fields.next()?.parse().ok()?,
+            current: fields.next()?.parse().ok()?,
+            temperature: fields.next()?.parse().ok()?,
+            a: parse_level(fields.next()?)?,
+            b: parse_level(fields.next()?)?,
+            cs: parse_level(fields.next()?)?.into(),
+            crc: u16::from_str_radix(fields.next()?, 16).ok()?,
+            debug: fields.next().and_then(|f| u32::from_str_radix(f, 16).ok()),
+        })
+    }
+}
+
+impl core::fmt::Display for DeviceStatus {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(
+            f,
+            "{} {}, up {}s, {:.3} V, {} mA, {:.1} \u{b0}C, A {}, B {}, CS {}, CRC {:04x}",
+            self.model,
+            self.serial,
+            self.uptime,
+            self.voltage,
+            self.current,
+            self.temperature,
+            self.a,
+            self.b,
+            self.cs,
+            self.crc,
+        )?;
+        if let Some(debug) = self.debug {
+            write!(f, ", debug {:x}", debug)?;
+        }
+        Ok(())
+    }
+}
+
+fn parse_level(field: &str) -> Option<PinState> {
+    match field {
+        "0" => Some(PinState::Low),
+        "1" => Some(PinState::High),
+        _ => None,
+    }
+}
+
+/// `Identifier` is a short string reported by the SPIDriver, such as its
+/// model name or serial number.
+///
+/// `Identifier` stores its content inline so that it can be used without
+/// an allocator.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct Identifier {
+    buf: [u8; Identifier::CAPACITY],
+    len: u8,
+}
+
+impl Identifier {
+    /// `CAPACITY` is the maximum length of an identifier, in bytes.
+    pub const CAPACITY: usize = 32;
+
+    fn new(s: &str) -> Option<Self> {
+        if s.len() > Self::CAPACITY {
+            return None;
+        }
+        let mut buf = [0_u8; Self::CAPACITY];
+        buf[..s.len()].copy_from_slice(s.as_bytes());
+        Some(Self {
+            buf,
+            len: s.len() as u8,
+        })
+    }
+
+    /// `as_str` returns the identifier as a string slice.
+    pub fn as_str(&self) -> &str {
+        // We only ever construct an identifier from a valid `str`.
+        core::str::from_utf8(&self.buf[..self.len as usize]).unwrap_or("")
+    }
+}
+
+impl core::fmt::Debug for Identifier {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        core::fmt::Debug::fmt(self.as_str(), f)
+    }
+}
+
+impl core::fmt::Display for Identifier {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[cfg(feature = "defmt")]
+impl defmt::Format for Identifier {
+    fn format(&self, f: defmt::Formatter<'_>) {
+        defmt::write!(f, "{=str}", self.as_str())
+    }
+}
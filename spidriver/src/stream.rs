@@ -0,0 +1,78 @@
+use std::io;
+
+use embedded_hal::serial;
+
+use crate::{Error, Progress, SPIDriver, MAX_FRAME};
+
+impl<TX, RX, TXErr, RXErr> SPIDriver<TX, RX>
+where
+    TX: serial::Write<u8, Error = TXErr>,
+    RX: serial::Read<u8, Error = RXErr>,
+{
+    /// `write_from` is like `write_all`, but reads the data to send from the
+    /// given reader as it goes, one 64-byte protocol frame at a time.
+    ///
+    /// This allows sending large amounts of data, such as a flash image read
+    /// from a file, without holding all of it in memory. Exactly `len` bytes
+    /// are sent, and if the reader ends before then `write_from` returns the
+    /// `Source` error with kind `std::io::ErrorKind::UnexpectedEof`, having
+    /// already sent the data that came before.
+    ///
+    /// This method is available only when the `std` feature is enabled.
+    pub fn write_from(
+        &mut self,
+        src: &mut impl io::Read,
+        len: usize,
+    ) -> Result<(), StreamError<TXErr, RXErr>> {
+        self.check_empty(len)?;
+        let mut buf = [0_u8; MAX_FRAME];
+        let mut progress = Progress::new(len);
+        while progress.done < len {
+            let chunk = &mut buf[..(len - progress.done).min(self.chunk_size)];
+            src.read_exact(chunk).map_err(StreamError::Source)?;
+            let start = self.ch.start_frame();
+            self.write(chunk).map_err(|e| e.at_offset(progress.done))?;
+            self.advance(&mut progress, chunk.len(), start);
+        }
+        Ok(())
+    }
+}
+
+/// `StreamError` represents the ways that streaming data from a reader with
+/// `SPIDriver::write_from` can fail.
+#[derive(Debug)]
+pub enum StreamError<TXErr, RXErr> {
+    /// `Source` indicates that reading from the source of the data failed.
+    Source(io::Error),
+
+    /// `Comms` indicates that sending the data to the SPIDriver failed.
+    Comms(Error<TXErr, RXErr>),
+}
+
+impl<TXErr, RXErr> From<Error<TXErr, RXErr>> for StreamError<TXErr, RXErr> {
+    fn from(err: Error<TXErr, RXErr>) -> Self {
+        StreamError::Comms(err)
+    }
+}
+
+impl<TXErr, RXErr> std::fmt::Display for StreamError<TXErr, RXErr> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StreamError::Source(err) => write!(f, "failed to read data to send: {}", err),
+            StreamError::Comms(err) => err.fmt(f),
+        }
+    }
+}
+
+impl<TXErr, RXErr> std::error::Error for StreamError<TXErr, RXErr>
+where
+    TXErr: std::error::Error + 'static,
+    RXErr: std::error::Error + 'static,
+{
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            StreamError::Source(err) => Some(err),
+            StreamError::Comms(err) => Some(err),
+        }
+    }
+}
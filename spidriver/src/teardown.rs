@@ -0,0 +1,126 @@
+use core::ops::{Deref, DerefMut};
+use embedded_hal::serial;
+
+use crate::channel::Channel;
+use crate::{PinState, SPIDriver};
+
+/// `Teardown` describes the states an `SPIDriver` restores its output pins
+/// to when it is dropped, if teardown on drop is enabled using
+/// `SPIDriver::set_teardown` or `SPIDriverBuilder::teardown`.
+///
+/// On drop, the chip select signal is released, the auxillary pins are
+/// driven to the levels given here, and then the SPIDriver is asked to
+/// disconnect from the SPI signals. This ensures that an application that
+/// panics or returns early doesn't leave the target device selected.
+///
+/// If an earlier error left the connection poisoned, as described under
+/// `ErrorKind::Poisoned`, nothing is sent on drop, because the device might
+/// misinterpret the teardown commands as data.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Teardown {
+    /// `a` is the idle level of the auxillary "A" pin.
+    pub a: PinState,
+
+    /// `b` is the idle level of the auxillary "B" pin.
+    pub b: PinState,
+}
+
+impl Default for Teardown {
+    fn default() -> Self {
+        Self {
+            a: PinState::High,
+            b: PinState::High,
+        }
+    }
+}
+
+impl<TX, RX, TXErr, RXErr> SPIDriver<TX, RX>
+where
+    TX: serial::Write<u8, Error = TXErr>,
+    RX: serial::Read<u8, Error = RXErr>,
+{
+    /// `set_teardown` enables or disables teardown on drop.
+    ///
+    /// Teardown is disabled by default, in which case dropping an
+    /// `SPIDriver` leaves the device in whatever state it was last put in.
+    /// `free` never performs teardown, even when it is enabled.
+    pub fn set_teardown(&mut self, teardown: Option<Teardown>) {
+        self.ch.teardown = teardown;
+    }
+}
+
+// TeardownOnDrop owns the channel of an SPIDriver and performs teardown, if
+// enabled, when it is dropped. SPIDriver itself doesn't implement Drop, so
+// that free can move this out of it and then take the channel without
+// running the teardown.
+//
+// The channel is always present until into_channel takes it, which consumes
+// the TeardownOnDrop, so the rest of the crate can use it as if it were the
+// channel itself.
+#[derive(Debug)]
+pub(crate) struct TeardownOnDrop<TX: serial::Write<u8>, RX: serial::Read<u8>> {
+    ch: Option<Channel<TX, RX>>,
+    teardown: Option<Teardown>,
+}
+
+impl<TX, RX> TeardownOnDrop<TX, RX>
+where
+    TX: serial::Write<u8>,
+    RX: serial::Read<u8>,
+{
+    pub fn new(ch: Channel<TX, RX>) -> Self {
+        Self {
+            ch: Some(ch),
+            teardown: None,
+        }
+    }
+
+    pub fn into_channel(mut self) -> Channel<TX, RX> {
+        self.ch.take().expect("channel already taken")
+    }
+}
+
+impl<TX, RX> Deref for TeardownOnDrop<TX, RX>
+where
+    TX: serial::Write<u8>,
+    RX: serial::Read<u8>,
+{
+    type Target = Channel<TX, RX>;
+
+    fn deref(&self) -> &Channel<TX, RX> {
+        self.ch.as_ref().expect("channel already taken")
+    }
+}
+
+impl<TX, RX> DerefMut for TeardownOnDrop<TX, RX>
+where
+    TX: serial::Write<u8>,
+    RX: serial::Read<u8>,
+{
+    fn deref_mut(&mut self) -> &mut Channel<TX, RX> {
+        self.ch.as_mut().expect("channel already taken")
+    }
+}
+
+impl<TX, RX> Drop for TeardownOnDrop<TX, RX>
+where
+    TX: serial::Write<u8>,
+    RX: serial::Read<u8>,
+{
+    fn drop(&mut self) {
+        let (ch, teardown) = match (self.ch.as_mut(), self.teardown) {
+            (Some(ch), Some(teardown)) => (ch, teardown),
+            _ => return,
+        };
+
+        // These are the same commands as SPIDriver's unselect, set_a, set_b
+        // and disconnect. There's no way to report errors from drop, so we
+        // just make a best effort to get through the whole sequence.
+        let _ = ch.send(b'u', &[]);
+        let _ = ch.send(b'a', &[teardown.a.protocol_arg()]);
+        let _ = ch.send(b'b', &[teardown.b.protocol_arg()]);
+        let _ = ch.send(b'x', &[]);
+        let _ = ch.flush();
+    }
+}
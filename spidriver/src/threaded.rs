@@ -0,0 +1,194 @@
+use std::sync::mpsc;
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+
+use embedded_hal::serial;
+
+use crate::BlockWrite;
+
+/// `ThreadedWriter` is a serial writer that passes everything written to it
+/// on to another serial writer running on a dedicated thread.
+///
+/// Writes return as soon as the data has been queued, so the caller can
+/// continue preparing the next frame while earlier frames are still being
+/// written, which can improve throughput for workloads that stream many
+/// frames. Enable block writes using `SPIDriver::enable_block_writes` so
+/// that each command is queued as a whole.
+///
+/// If the underlying writer fails, the error is returned from the next
+/// write or flush, and from `WriterHandle::wait`, and any data queued in the
+/// meantime is discarded. Flushing waits until all of the queued data has
+/// been written.
+///
+/// This type is available only when the `std` feature is enabled.
+pub struct ThreadedWriter<E> {
+    queue: mpsc::Sender<Message>,
+    shared: Arc<Shared<E>>,
+}
+
+/// `WriterHandle` allows waiting for the data queued on a `ThreadedWriter`
+/// to be written, and recovering the underlying serial writer afterwards.
+///
+/// This type is available only when the `std` feature is enabled.
+pub struct WriterHandle<TX: serial::Write<u8>> {
+    thread: thread::JoinHandle<TX>,
+    shared: Arc<Shared<TX::Error>>,
+}
+
+impl<E> core::fmt::Debug for ThreadedWriter<E> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str("ThreadedWriter")
+    }
+}
+
+impl<TX: serial::Write<u8>> core::fmt::Debug for WriterHandle<TX> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str("WriterHandle")
+    }
+}
+
+enum Message {
+    Data(Vec<u8>),
+    Flush,
+}
+
+struct Shared<E> {
+    state: Mutex<State<E>>,
+    idle: Condvar,
+}
+
+struct State<E> {
+    // The number of queued messages not yet handled by the writer thread.
+    pending: usize,
+
+    // The first error returned by the underlying writer, if it hasn't yet
+    // been reported.
+    err: Option<E>,
+}
+
+impl<E> ThreadedWriter<E> {
+    /// `spawn` starts a thread that writes to the given serial writer, and
+    /// returns a `ThreadedWriter` that queues data for it along with a
+    /// handle for the thread.
+    pub fn spawn<TX>(tx: TX) -> (Self, WriterHandle<TX>)
+    where
+        TX: serial::Write<u8, Error = E> + Send + 'static,
+        E: Send + 'static,
+    {
+        let (queue, messages) = mpsc::channel();
+        let shared = Arc::new(Shared {
+            state: Mutex::new(State {
+                pending: 0,
+                err: None,
+            }),
+            idle: Condvar::new(),
+        });
+        let thread = {
+            let shared = shared.clone();
+            thread::spawn(move || run(tx, messages, &shared))
+        };
+        (
+            Self {
+                queue,
+                shared: shared.clone(),
+            },
+            WriterHandle { thread, shared },
+        )
+    }
+
+    fn send(&mut self, msg: Message) -> Result<(), E> {
+        {
+            let mut state = self.shared.state.lock().unwrap();
+            if let Some(err) = state.err.take() {
+                return Err(err);
+            }
+            state.pending += 1;
+        }
+        self.queue
+            .send(msg)
+            .expect("ThreadedWriter thread has panicked");
+        Ok(())
+    }
+}
+
+impl<E> serial::Write<u8> for ThreadedWriter<E> {
+    type Error = E;
+
+    fn write(&mut self, word: u8) -> nb::Result<(), Self::Error> {
+        self.send(Message::Data(vec![word]))
+            .map_err(nb::Error::Other)
+    }
+
+    fn flush(&mut self) -> nb::Result<(), Self::Error> {
+        self.send(Message::Flush).map_err(nb::Error::Other)?;
+        self.shared.wait().map_err(nb::Error::Other)
+    }
+}
+
+impl<E> BlockWrite for ThreadedWriter<E> {
+    fn write_block(&mut self, data: &[u8]) -> Result<(), Self::Error> {
+        self.send(Message::Data(data.to_vec()))
+    }
+}
+
+impl<TX: serial::Write<u8>> WriterHandle<TX> {
+    /// `wait` blocks until all of the data queued so far has been written,
+    /// returning the error from the underlying writer if writing failed.
+    pub fn wait(&self) -> Result<(), TX::Error> {
+        self.shared.wait()
+    }
+
+    /// `join` waits for the writer thread to exit and returns the
+    /// underlying serial writer.
+    ///
+    /// The thread exits once the `ThreadedWriter` has been dropped, such as
+    /// by dropping the `SPIDriver` that was using it, so `join` blocks
+    /// forever if called while the `ThreadedWriter` is still in use.
+    pub fn join(self) -> TX {
+        match self.thread.join() {
+            Ok(tx) => tx,
+            Err(panic) => std::panic::resume_unwind(panic),
+        }
+    }
+}
+
+impl<E> Shared<E> {
+    fn wait(&self) -> Result<(), E> {
+        let mut state = self.state.lock().unwrap();
+        while state.pending > 0 {
+            state = self.idle.wait(state).unwrap();
+        }
+        match state.err.take() {
+            Some(err) => Err(err),
+            None => Ok(()),
+        }
+    }
+}
+
+// run is the body of the writer thread.
+fn run<TX: serial::Write<u8>>(
+    mut tx: TX,
+    messages: mpsc::Receiver<Message>,
+    shared: &Shared<TX::Error>,
+) -> TX {
+    for msg in messages {
+        // Once writing has failed we discard everything until the error
+        // has been reported, since the data is likely to be incomplete.
+        let failed = shared.state.lock().unwrap().err.is_some();
+        let result = match msg {
+            _ if failed => Ok(()),
+            Message::Data(data) => data.iter().try_for_each(|c| nb::block!(tx.write(*c))),
+            Message::Flush => nb::block!(tx.flush()),
+        };
+
+        let mut state = shared.state.lock().unwrap();
+        if let Err(err) = result {
+            state.err = Some(err);
+        }
+        state.pending -= 1;
+        if state.pending == 0 {
+            shared.idle.notify_all();
+        }
+    }
+    tx
+}
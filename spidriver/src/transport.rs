@@ -0,0 +1,140 @@
+use embedded_hal::serial;
+
+use crate::SPIDriver;
+
+/// `BlockWrite` is implemented by serial writers that can write a whole
+/// slice of bytes with a single call.
+///
+/// The `embedded-hal` serial `Write` trait passes only one byte per call,
+/// which is slow on serial ports provided by an operating system because
+/// each call may become a separate system call. Once enabled using
+/// `SPIDriver::enable_block_writes`, the `SPIDriver` instead passes each
+/// complete command, such as a 65-byte write frame, to `write_block`.
+///
+/// `IoWriter` and `EioWriter` adapt implementations of the standard
+/// library's and the `embedded-io` crate's `Write` traits respectively.
+pub trait BlockWrite: serial::Write<u8> {
+    /// `write_block` writes all of the given bytes, blocking until they
+    /// have been accepted.
+    fn write_block(&mut self, data: &[u8]) -> Result<(), Self::Error>;
+}
+
+// BlockWriter wraps the write_block function of a serial writer so that the
+// types that hold one can still implement Debug.
+pub(crate) struct BlockWriter<TX: serial::Write<u8>>(
+    pub fn(&mut TX, &[u8]) -> Result<(), TX::Error>,
+);
+
+impl<TX: serial::Write<u8>> Clone for BlockWriter<TX> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<TX: serial::Write<u8>> Copy for BlockWriter<TX> {}
+
+impl<TX: serial::Write<u8>> core::fmt::Debug for BlockWriter<TX> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str("BlockWriter")
+    }
+}
+
+impl<TX, RX, TXErr, RXErr> SPIDriver<TX, RX>
+where
+    TX: serial::Write<u8, Error = TXErr>,
+    RX: serial::Read<u8, Error = RXErr>,
+{
+    /// `enable_block_writes` makes the `SPIDriver` write each command to the
+    /// serial writer with a single call to `BlockWrite::write_block`, rather
+    /// than one byte at a time.
+    pub fn enable_block_writes(&mut self)
+    where
+        TX: BlockWrite,
+    {
+        self.ch.block = Some(BlockWriter(TX::write_block));
+    }
+
+    /// `disable_block_writes` returns to writing one byte at a time, undoing
+    /// `enable_block_writes`.
+    pub fn disable_block_writes(&mut self) {
+        self.ch.block = None;
+    }
+}
+
+/// `IoWriter` adapts an implementation of `std::io::Write` for use as the
+/// serial writer of an `SPIDriver`, with support for block writes.
+///
+/// Call `SPIDriver::enable_block_writes` after creating the `SPIDriver` to
+/// make use of block writes.
+///
+/// This type is available only when the `std` feature is enabled.
+#[cfg(feature = "std")]
+#[derive(Debug)]
+pub struct IoWriter<W: std::io::Write>(pub W);
+
+#[cfg(feature = "std")]
+impl<W: std::io::Write> IoWriter<W> {
+    /// `into_inner` returns the wrapped writer.
+    pub fn into_inner(self) -> W {
+        self.0
+    }
+}
+
+#[cfg(feature = "std")]
+impl<W: std::io::Write> serial::Write<u8> for IoWriter<W> {
+    type Error = std::io::Error;
+
+    fn write(&mut self, word: u8) -> nb::Result<(), Self::Error> {
+        self.0.write_all(&[word]).map_err(nb::Error::Other)
+    }
+
+    fn flush(&mut self) -> nb::Result<(), Self::Error> {
+        self.0.flush().map_err(nb::Error::Other)
+    }
+}
+
+#[cfg(feature = "std")]
+impl<W: std::io::Write> BlockWrite for IoWriter<W> {
+    fn write_block(&mut self, data: &[u8]) -> Result<(), Self::Error> {
+        self.0.write_all(data)
+    }
+}
+
+/// `EioWriter` adapts an implementation of `embedded_io::Write` for use as
+/// the serial writer of an `SPIDriver`, with support for block writes.
+///
+/// Call `SPIDriver::enable_block_writes` after creating the `SPIDriver` to
+/// make use of block writes.
+///
+/// This type is available only when the `embedded-io` feature is enabled.
+#[cfg(feature = "embedded-io")]
+#[derive(Debug)]
+pub struct EioWriter<W: embedded_io::Write>(pub W);
+
+#[cfg(feature = "embedded-io")]
+impl<W: embedded_io::Write> EioWriter<W> {
+    /// `into_inner` returns the wrapped writer.
+    pub fn into_inner(self) -> W {
+        self.0
+    }
+}
+
+#[cfg(feature = "embedded-io")]
+impl<W: embedded_io::Write> serial::Write<u8> for EioWriter<W> {
+    type Error = W::Error;
+
+    fn write(&mut self, word: u8) -> nb::Result<(), Self::Error> {
+        self.0.write_all(&[word]).map_err(nb::Error::Other)
+    }
+
+    fn flush(&mut self) -> nb::Result<(), Self::Error> {
+        self.0.flush().map_err(nb::Error::Other)
+    }
+}
+
+#[cfg(feature = "embedded-io")]
+impl<W: embedded_io::Write> BlockWrite for EioWriter<W> {
+    fn write_block(&mut self, data: &[u8]) -> Result<(), Self::Error> {
+        self.0.write_all(data)
+    }
+}
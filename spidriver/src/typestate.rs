@@ -0,0 +1,145 @@
+//! A typestate-based wrapper around `SPIDriver` that only allows sending
+//! data while the target device is selected.
+//!
+//! An `Unselected` wraps an `SPIDriver` and provides only the operations
+//! that don't transfer SPI data. To transfer data, call `Unselected::select`
+//! to obtain a `Selected`, which releases the chip select signal again when
+//! it is dropped or when `Selected::unselect` is called. Forgetting to select
+//! the target device before writing to it is therefore a compile-time error.
+//!
+//! ```rust,ignore
+//! let mut sd = Unselected::new(SPIDriver::new(tx, rx))?;
+//! let mut sel = sd.select()?;
+//! sel.write(&cmd)?;
+//! sel.unselect()?;
+//! ```
+
+use embedded_hal::serial;
+
+use crate::{DeviceStatus, Error, PinState, SPIDriver, SelectGuard};
+
+/// `Unselected` wraps an `SPIDriver` whose target device is not selected.
+#[derive(Debug)]
+pub struct Unselected<TX, RX>
+where
+    TX: serial::Write<u8>,
+    RX: serial::Read<u8>,
+{
+    sd: SPIDriver<TX, RX>,
+}
+
+impl<TX, RX, TXErr, RXErr> Unselected<TX, RX>
+where
+    TX: serial::Write<u8, Error = TXErr>,
+    RX: serial::Read<u8, Error = RXErr>,
+{
+    /// `new` wraps the given `SPIDriver`, first releasing the chip select
+    /// signal so that the wrapper's state matches the device's.
+    pub fn new(mut sd: SPIDriver<TX, RX>) -> Result<Self, Error<TXErr, RXErr>> {
+        sd.unselect()?;
+        Ok(Self { sd })
+    }
+
+    /// `into_inner` consumes the wrapper and returns the `SPIDriver` it
+    /// wraps.
+    pub fn into_inner(self) -> SPIDriver<TX, RX> {
+        self.sd
+    }
+
+    /// `select` asserts the chip select signal and returns a `Selected`
+    /// that allows sending data to the target device.
+    pub fn select(&mut self) -> Result<Selected<'_, TX, RX>, Error<TXErr, RXErr>> {
+        Ok(Selected {
+            guard: self.sd.select_guard()?,
+        })
+    }
+
+    /// `echo` is equivalent to `SPIDriver::echo`.
+    pub fn echo(&mut self, ch: u8) -> Result<u8, Error<TXErr, RXErr>> {
+        self.sd.echo(ch)
+    }
+
+    /// `status` is equivalent to `SPIDriver::status`.
+    pub fn status(&mut self) -> Result<DeviceStatus, Error<TXErr, RXErr>> {
+        self.sd.status()
+    }
+
+    /// `set_a` is equivalent to `SPIDriver::set_a`.
+    pub fn set_a(&mut self, state: impl Into<PinState>) -> Result<(), Error<TXErr, RXErr>> {
+        self.sd.set_a(state)
+    }
+
+    /// `set_b` is equivalent to `SPIDriver::set_b`.
+    pub fn set_b(&mut self, state: impl Into<PinState>) -> Result<(), Error<TXErr, RXErr>> {
+        self.sd.set_b(state)
+    }
+}
+
+/// `Selected` represents an `SPIDriver` whose target device is selected,
+/// and so which can send data to it.
+///
+/// The chip select signal is released when the `Selected` is dropped, or
+/// explicitly by calling `unselect`.
+#[derive(Debug)]
+pub struct Selected<'a, TX, RX>
+where
+    TX: serial::Write<u8>,
+    RX: serial::Read<u8>,
+{
+    guard: SelectGuard<'a, TX, RX>,
+}
+
+impl<'a, TX, RX, TXErr, RXErr> Selected<'a, TX, RX>
+where
+    TX: serial::Write<u8, Error = TXErr>,
+    RX: serial::Read<u8, Error = RXErr>,
+{
+    /// `unselect` releases the chip select signal, returning any error that
+    /// occurs while doing so.
+    pub fn unselect(self) -> Result<(), Error<TXErr, RXErr>> {
+        self.guard.release()
+    }
+
+    /// `write` is equivalent to `SPIDriver::write`.
+    pub fn write(&mut self, data: &[u8]) -> Result<(), Error<TXErr, RXErr>> {
+        self.guard.write(data)
+    }
+
+    /// `write_byte` is equivalent to `SPIDriver::write_byte`.
+    pub fn write_byte(&mut self, b: u8) -> Result<(), Error<TXErr, RXErr>> {
+        self.guard.write_byte(b)
+    }
+
+    /// `write_all` is equivalent to `SPIDriver::write_all`.
+    pub fn write_all(&mut self, data: &[u8]) -> Result<(), Error<TXErr, RXErr>> {
+        self.guard.write_all(data)
+    }
+
+    /// `transfer` is equivalent to `SPIDriver::transfer`.
+    pub fn transfer<'v>(&mut self, data: &'v mut [u8]) -> Result<&'v [u8], Error<TXErr, RXErr>> {
+        self.guard.transfer(data)
+    }
+
+    /// `transfer_all` is equivalent to `SPIDriver::transfer_all`.
+    pub fn transfer_all<'v>(
+        &mut self,
+        data: &'v mut [u8],
+    ) -> Result<&'v [u8], Error<TXErr, RXErr>> {
+        self.guard.transfer_all(data)
+    }
+
+    /// `clock_bytes` is equivalent to `SPIDriver::clock_bytes`.
+    pub fn clock_bytes(&mut self, n: usize) -> Result<(), Error<TXErr, RXErr>> {
+        self.guard.clock_bytes(n)
+    }
+
+    /// `set_a` is equivalent to `SPIDriver::set_a`.
+    pub fn set_a(&mut self, state: impl Into<PinState>) -> Result<(), Error<TXErr, RXErr>> {
+        self.guard.set_a(state)
+    }
+
+    /// `set_b` is equivalent to `SPIDriver::set_b`.
+    pub fn set_b(&mut self, state: impl Into<PinState>) -> Result<(), Error<TXErr, RXErr>> {
+        self.guard.set_b(state)
+    }
+}
@@ -0,0 +1,77 @@
+use std::time::Duration;
+
+use embedded_hal::serial;
+
+use crate::SPIDriver;
+
+/// `WaitStrategy` selects what an `SPIDriver` does while it is waiting for
+/// a response that hasn't yet arrived.
+///
+/// The serial reader is always polled in batches, as described for
+/// `SPIDriver::set_poll_batch`, and the wait strategy applies only after
+/// a whole batch of polls has produced nothing.
+///
+/// This type is available only when the `std` feature is enabled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum WaitStrategy {
+    /// `Spin` polls again immediately, which gives the lowest latency but
+    /// keeps a CPU core busy for as long as the wait lasts. This is the
+    /// default.
+    #[default]
+    Spin,
+
+    /// `Yield` offers the rest of the thread's time slice to other threads
+    /// before polling again.
+    Yield,
+
+    /// `Sleep` puts the thread to sleep before polling again, starting with
+    /// the `initial` duration and doubling it each time nothing arrives,
+    /// up to the `max` duration.
+    ///
+    /// The extra latency is at most `max`, so `max` should be small
+    /// compared with the read timeout.
+    Sleep {
+        /// `initial` is how long to sleep the first time a batch of polls
+        /// produces nothing.
+        initial: Duration,
+
+        /// `max` is the longest to sleep at once.
+        max: Duration,
+    },
+}
+
+impl WaitStrategy {
+    // wait waits once, given the number of times it has already waited
+    // since anything was last received.
+    pub(crate) fn wait(&self, waited: u32) {
+        match *self {
+            WaitStrategy::Spin => {}
+            WaitStrategy::Yield => std::thread::yield_now(),
+            WaitStrategy::Sleep { initial, max } => {
+                let delay = initial
+                    .checked_mul(1 << waited.min(16))
+                    .unwrap_or(max)
+                    .min(max);
+                std::thread::sleep(delay);
+            }
+        }
+    }
+}
+
+impl<TX, RX, TXErr, RXErr> SPIDriver<TX, RX>
+where
+    TX: serial::Write<u8, Error = TXErr>,
+    RX: serial::Read<u8, Error = RXErr>,
+{
+    /// `set_wait_strategy` selects what to do while waiting for a response
+    /// from the device. See `WaitStrategy` for the options.
+    ///
+    /// When running on a host operating system, a strategy other than the
+    /// default `WaitStrategy::Spin` avoids keeping a CPU core busy during
+    /// long transfers, at the expense of some additional latency.
+    ///
+    /// This method is available only when the `std` feature is enabled.
+    pub fn set_wait_strategy(&mut self, strategy: WaitStrategy) {
+        self.ch.wait = strategy;
+    }
+}
@@ -0,0 +1,89 @@
+use std::time::{Duration, Instant};
+
+use embedded_hal::serial;
+
+use crate::{Error, ErrorKind, SPIDriver, Stats};
+
+/// `Watchdog` checks that an SPIDriver is still answering while the
+/// connection is otherwise idle, by periodically sending it an echo command.
+///
+/// The watchdog is cooperative: the caller must call `poll` regularly, such
+/// as from its main loop, and the watchdog only sends a check once the
+/// connection has been idle for the watchdog's interval. Any other activity
+/// on the connection counts as proof that the device is alive.
+///
+/// If the device doesn't answer a check then the watchdog marks the
+/// `SPIDriver` as unresponsive, and subsequent operations fail with the
+/// `DeviceUnresponsive` error until the caller recovers using
+/// `SPIDriver::resync` or `SPIDriver::reset_state`.
+///
+/// `Watchdog` is available only when the `std` feature is enabled, because it
+/// relies on the standard library's monotonic clock.
+#[derive(Debug, Clone)]
+pub struct Watchdog {
+    interval: Duration,
+    timeout: Duration,
+    last_active: Instant,
+    last_stats: Option<Stats>,
+}
+
+impl Watchdog {
+    /// `new` creates a `Watchdog` that checks the device after it has been
+    /// idle for the given interval, and that waits for at most the given
+    /// timeout for each check to be answered.
+    pub fn new(interval: Duration, timeout: Duration) -> Self {
+        Self {
+            interval,
+            timeout,
+            last_active: Instant::now(),
+            last_stats: None,
+        }
+    }
+
+    /// `poll` checks that the given SPIDriver is still responding if the
+    /// connection has been idle for at least the watchdog's interval, and
+    /// otherwise does nothing.
+    ///
+    /// Returns the `DeviceUnresponsive` error if the device didn't answer,
+    /// or if it was already marked as unresponsive. `poll` doesn't check
+    /// the device while the connection is poisoned, because the echo command
+    /// can't be sent until the caller has recovered.
+    pub fn poll<TX, RX, TXErr, RXErr>(
+        &mut self,
+        sd: &mut SPIDriver<TX, RX>,
+    ) -> Result<(), Error<TXErr, RXErr>>
+    where
+        TX: serial::Write<u8, Error = TXErr>,
+        RX: serial::Read<u8, Error = RXErr>,
+    {
+        if sd.is_unresponsive() {
+            return Err(Error::new(ErrorKind::DeviceUnresponsive));
+        }
+        if sd.is_poisoned() {
+            return Ok(());
+        }
+
+        // Any change to the activity counters means that something else
+        // has used the connection since the last poll.
+        let now = Instant::now();
+        let stats = sd.stats();
+        if self.last_stats != Some(stats) {
+            self.last_stats = Some(stats);
+            self.last_active = now;
+            return Ok(());
+        }
+        if now.duration_since(self.last_active) < self.interval {
+            return Ok(());
+        }
+
+        let alive = sd.is_connected(self.timeout);
+        self.last_stats = Some(sd.stats());
+        self.last_active = Instant::now();
+        if !alive {
+            debug!("SPIDriver didn't answer watchdog check");
+            sd.ch.unresponsive = true;
+            return Err(Error::new(ErrorKind::DeviceUnresponsive));
+        }
+        Ok(())
+    }
+}
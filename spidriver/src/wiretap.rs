@@ -0,0 +1,37 @@
+use embedded_hal::serial;
+
+use crate::SPIDriver;
+
+/// `Direction` describes which way the bytes passed to a wire tap were
+/// travelling.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Direction {
+    /// `Outgoing` bytes were written to the serial port.
+    Outgoing,
+
+    /// `Incoming` bytes were read from the serial port.
+    Incoming,
+}
+
+/// `WireTap` is a function that receives the raw bytes written to and read
+/// from the serial port, for debugging low-level framing problems.
+///
+/// Unlike a `ProtocolObserver`, a wire tap sees exactly what is on the wire,
+/// including data sent with `send_raw` and stale bytes that are discarded
+/// without being interpreted. Each call receives one contiguous run of
+/// bytes, such as a complete command or response, which is convenient for
+/// printing as a hex dump.
+pub type WireTap = fn(Direction, &[u8]);
+
+impl<TX, RX, TXErr, RXErr> SPIDriver<TX, RX>
+where
+    TX: serial::Write<u8, Error = TXErr>,
+    RX: serial::Read<u8, Error = RXErr>,
+{
+    /// `set_wire_tap` sets or clears the function to call with the raw
+    /// bytes written to and read from the serial port.
+    pub fn set_wire_tap(&mut self, tap: Option<WireTap>) {
+        self.ch.set_wire_tap(tap);
+    }
+}
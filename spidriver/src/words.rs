@@ -0,0 +1,83 @@
+use embedded_hal::serial;
+
+use crate::{Error, SPIDriver, MAX_FRAME};
+
+/// `Endianness` selects the order in which the bytes of a multi-byte word
+/// are sent over SPI.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Endianness {
+    /// `Big` sends the most significant byte first. This is the convention
+    /// used by most SPI devices.
+    Big,
+
+    /// `Little` sends the least significant byte first.
+    Little,
+}
+
+impl<TX, RX, TXErr, RXErr> SPIDriver<TX, RX>
+where
+    TX: serial::Write<u8, Error = TXErr>,
+    RX: serial::Read<u8, Error = RXErr>,
+{
+    /// `write_u16` sends a single 16-bit word out over the MOSI line, with
+    /// its bytes in the given order.
+    pub fn write_u16(&mut self, v: u16, order: Endianness) -> Result<(), Error<TXErr, RXErr>> {
+        self.write(&match order {
+            Endianness::Big => v.to_be_bytes(),
+            Endianness::Little => v.to_le_bytes(),
+        })
+    }
+
+    /// `write_u32` sends a single 32-bit word out over the MOSI line, with
+    /// its bytes in the given order.
+    pub fn write_u32(&mut self, v: u32, order: Endianness) -> Result<(), Error<TXErr, RXErr>> {
+        self.write(&match order {
+            Endianness::Big => v.to_be_bytes(),
+            Endianness::Little => v.to_le_bytes(),
+        })
+    }
+
+    /// `write_u16s` sends a sequence of 16-bit words out over the MOSI line,
+    /// with the bytes of each word in the given order.
+    ///
+    /// The words are packed into as few 64-byte protocol frames as possible,
+    /// as with `write_all`.
+    pub fn write_u16s(&mut self, vs: &[u16], order: Endianness) -> Result<(), Error<TXErr, RXErr>> {
+        self.write_words(vs, |v| match order {
+            Endianness::Big => v.to_be_bytes(),
+            Endianness::Little => v.to_le_bytes(),
+        })
+    }
+
+    /// `write_u32s` sends a sequence of 32-bit words out over the MOSI line,
+    /// with the bytes of each word in the given order.
+    ///
+    /// The words are packed into as few 64-byte protocol frames as possible,
+    /// as with `write_all`.
+    pub fn write_u32s(&mut self, vs: &[u32], order: Endianness) -> Result<(), Error<TXErr, RXErr>> {
+        self.write_words(vs, |v| match order {
+            Endianness::Big => v.to_be_bytes(),
+            Endianness::Little => v.to_le_bytes(),
+        })
+    }
+
+    fn write_words<W: Copy, const N: usize>(
+        &mut self,
+        vs: &[W],
+        encode: impl Fn(W) -> [u8; N],
+    ) -> Result<(), Error<TXErr, RXErr>> {
+        // Each frame holds at least one word, even if the chunk size is
+        // smaller than a word.
+        let per_frame = (self.chunk_size / N).max(1);
+        let mut buf = [0_u8; MAX_FRAME];
+        for (i, chunk) in vs.chunks(per_frame).enumerate() {
+            for (v, dst) in chunk.iter().zip(buf.chunks_mut(N)) {
+                dst.copy_from_slice(&encode(*v));
+            }
+            self.write(&buf[..chunk.len() * N])
+                .map_err(|e| e.at_offset(i * per_frame * N))?;
+        }
+        Ok(())
+    }
+}